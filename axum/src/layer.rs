@@ -0,0 +1,505 @@
+use std::{
+    future::Future,
+    pin::Pin,
+    sync::Arc,
+    task::{ready, Context, Poll},
+    time::Instant,
+};
+
+use axum::{
+    extract::MatchedPath,
+    http::{self, Request, Response},
+};
+use lasso::{Key, Spur, ThreadedRodeo};
+use measured::{
+    label::{DynamicLabelSet, LabelSet},
+    metric::histogram::Thresholds,
+    CounterVec, FixedCardinalityLabel, GaugeVec, HistogramVec, LabelGroup, MetricGroup,
+};
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+/// The route label used for requests where no route could be extracted
+pub const UNKNOWN_ROUTE: &str = "unknown";
+
+/// The route label used for requests once [`RouteSet`] is full
+pub const OTHER_ROUTE: &str = "other";
+
+/// The default maximum number of distinct routes recorded by [`HttpMetrics`]
+pub const DEFAULT_MAX_ROUTES: usize = 256;
+
+/// The metrics recorded by [`MetricsLayer`]
+#[derive(MetricGroup)]
+pub struct HttpMetrics {
+    /// total number of HTTP requests by route, method, and status class
+    pub http_requests_total: CounterVec<HttpRequestSet>,
+
+    /// duration of HTTP requests by route, method, and status class
+    pub http_request_duration_seconds: HistogramVec<HttpRequestSet, 16>,
+
+    /// number of HTTP requests currently being handled by route and method
+    pub http_requests_in_flight: GaugeVec<InFlightSet>,
+}
+
+impl Default for HttpMetrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl HttpMetrics {
+    /// Create the HTTP metrics, recording at most [`DEFAULT_MAX_ROUTES`] distinct routes
+    pub fn new() -> Self {
+        Self::with_max_routes(DEFAULT_MAX_ROUTES)
+    }
+
+    /// Create the HTTP metrics, recording at most `max_routes` distinct routes.
+    ///
+    /// Any further routes are recorded as [`OTHER_ROUTE`].
+    pub fn with_max_routes(max_routes: usize) -> Self {
+        let routes = Arc::new(RouteSet::new(max_routes));
+        Self {
+            http_requests_total: CounterVec::with_label_set(HttpRequestSet::new(routes.clone())),
+            http_request_duration_seconds: HistogramVec::with_label_set_and_metadata(
+                HttpRequestSet::new(routes.clone()),
+                // starting at 0.1ms up to 3.3s
+                Thresholds::exponential_buckets(0.0001, 2.0),
+            ),
+            http_requests_in_flight: GaugeVec::with_label_set(InFlightSet::new(routes)),
+        }
+    }
+}
+
+/// The labels for [`HttpMetrics::http_requests_total`] and [`HttpMetrics::http_request_duration_seconds`]
+#[derive(Clone, Copy, LabelGroup)]
+#[label(set = HttpRequestSet)]
+pub struct HttpRequest<'a> {
+    /// The route template, eg `/users/:id`
+    #[label(dynamic_with = Arc<RouteSet>)]
+    pub route: &'a str,
+    pub method: Method,
+    pub status: StatusClass,
+}
+
+/// The labels for [`HttpMetrics::http_requests_in_flight`]
+#[derive(Clone, Copy, LabelGroup)]
+#[label(set = InFlightSet)]
+pub struct InFlight<'a> {
+    /// The route template, eg `/users/:id`
+    #[label(dynamic_with = Arc<RouteSet>)]
+    pub route: &'a str,
+    pub method: Method,
+}
+
+/// A [`LabelSet`] of route templates, which stops growing after a maximum number of routes.
+///
+/// Once full, any new route is encoded as [`OTHER_ROUTE`], so a misbehaving client or route extractor
+/// cannot cause the cardinality of the metrics to explode.
+pub struct RouteSet {
+    routes: ThreadedRodeo<Spur>,
+    other: Spur,
+    max_routes: usize,
+}
+
+impl RouteSet {
+    /// Create a new route set which holds at most `max_routes` routes,
+    /// in addition to [`UNKNOWN_ROUTE`] and [`OTHER_ROUTE`].
+    pub fn new(max_routes: usize) -> Self {
+        let routes = ThreadedRodeo::new();
+        routes.get_or_intern_static(UNKNOWN_ROUTE);
+        let other = routes.get_or_intern_static(OTHER_ROUTE);
+        Self {
+            routes,
+            other,
+            max_routes,
+        }
+    }
+}
+
+impl LabelSet for RouteSet {
+    type Value<'a> = &'a str;
+
+    fn dynamic_cardinality(&self) -> Option<usize> {
+        None
+    }
+
+    fn encode(&self, value: Self::Value<'_>) -> Option<usize> {
+        let key = match self.routes.get(value) {
+            Some(key) => key,
+            // concurrent inserts might overshoot the limit slightly, which is fine
+            None if self.routes.len() - 2 < self.max_routes => {
+                self.routes.try_get_or_intern(value).ok()?
+            }
+            None => self.other,
+        };
+        Some(key.into_usize())
+    }
+
+    fn decode(&self, value: usize) -> Self::Value<'_> {
+        self.routes.resolve(&Spur::try_from_usize(value).unwrap())
+    }
+}
+
+impl DynamicLabelSet for RouteSet {}
+
+/// The HTTP request method, as a label value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FixedCardinalityLabel)]
+pub enum Method {
+    Get,
+    Head,
+    Post,
+    Put,
+    Delete,
+    Connect,
+    Options,
+    Trace,
+    Patch,
+    Other,
+}
+
+impl From<&http::Method> for Method {
+    fn from(value: &http::Method) -> Self {
+        match *value {
+            http::Method::GET => Method::Get,
+            http::Method::HEAD => Method::Head,
+            http::Method::POST => Method::Post,
+            http::Method::PUT => Method::Put,
+            http::Method::DELETE => Method::Delete,
+            http::Method::CONNECT => Method::Connect,
+            http::Method::OPTIONS => Method::Options,
+            http::Method::TRACE => Method::Trace,
+            http::Method::PATCH => Method::Patch,
+            _ => Method::Other,
+        }
+    }
+}
+
+/// The class of the HTTP response status code, as a label value
+#[derive(Clone, Copy, Debug, PartialEq, Eq, FixedCardinalityLabel)]
+pub enum StatusClass {
+    #[label(rename = "1xx")]
+    Informational,
+    #[label(rename = "2xx")]
+    Success,
+    #[label(rename = "3xx")]
+    Redirection,
+    #[label(rename = "4xx")]
+    ClientError,
+    #[label(rename = "5xx")]
+    ServerError,
+    Other,
+}
+
+impl From<http::StatusCode> for StatusClass {
+    fn from(value: http::StatusCode) -> Self {
+        match value.as_u16() {
+            100..=199 => StatusClass::Informational,
+            200..=299 => StatusClass::Success,
+            300..=399 => StatusClass::Redirection,
+            400..=499 => StatusClass::ClientError,
+            500..=599 => StatusClass::ServerError,
+            _ => StatusClass::Other,
+        }
+    }
+}
+
+/// Extracts the route template of a request, to be used as the `route` label.
+///
+/// This is implemented for closures of the form `Fn(&Request<B>) -> Option<impl AsRef<str>>`.
+pub trait ExtractRoute<B> {
+    /// The extracted route template
+    type Route: AsRef<str>;
+
+    /// Extract the route template of the request, if it has one.
+    fn extract(&self, req: &Request<B>) -> Option<Self::Route>;
+}
+
+impl<B, F, R> ExtractRoute<B> for F
+where
+    F: Fn(&Request<B>) -> Option<R>,
+    R: AsRef<str>,
+{
+    type Route = R;
+
+    fn extract(&self, req: &Request<B>) -> Option<R> {
+        self(req)
+    }
+}
+
+/// Extracts the route template from axum's [`MatchedPath`].
+///
+/// The `MatchedPath` is only available to layers added to the `Router` with `Router::layer`,
+/// not to layers wrapping the router itself.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MatchedPathRoute;
+
+/// The route template extracted by [`MatchedPathRoute`]
+pub struct MatchedRoute(MatchedPath);
+
+impl AsRef<str> for MatchedRoute {
+    fn as_ref(&self) -> &str {
+        self.0.as_str()
+    }
+}
+
+impl<B> ExtractRoute<B> for MatchedPathRoute {
+    type Route = MatchedRoute;
+
+    fn extract(&self, req: &Request<B>) -> Option<MatchedRoute> {
+        req.extensions()
+            .get::<MatchedPath>()
+            .cloned()
+            .map(MatchedRoute)
+    }
+}
+
+/// A tower [`Layer`] which records [`HttpMetrics`] for every request to an HTTP service.
+///
+/// Requests are labelled by the route template returned by the route extractor, by default [`MatchedPathRoute`].
+/// Failed requests, where the service returns an error rather than a response, are recorded with a `5xx` status.
+#[derive(Clone)]
+pub struct MetricsLayer<E = MatchedPathRoute> {
+    metrics: Arc<HttpMetrics>,
+    extract: E,
+}
+
+impl MetricsLayer {
+    /// Create a new layer which records into the given metrics
+    pub fn new(metrics: Arc<HttpMetrics>) -> Self {
+        Self {
+            metrics,
+            extract: MatchedPathRoute,
+        }
+    }
+}
+
+impl<E> MetricsLayer<E> {
+    /// Use a different way to extract the route template of each request.
+    ///
+    /// ```
+    /// use std::sync::Arc;
+    ///
+    /// use axum::http::Request;
+    /// use measured_axum::{HttpMetrics, MetricsLayer};
+    ///
+    /// // label requests by their first path segment
+    /// let layer = MetricsLayer::new(Arc::new(HttpMetrics::new())).with_route_extractor(
+    ///     |req: &Request<()>| req.uri().path().split('/').nth(1).map(str::to_owned),
+    /// );
+    /// ```
+    pub fn with_route_extractor<E2>(self, extract: E2) -> MetricsLayer<E2> {
+        MetricsLayer {
+            metrics: self.metrics,
+            extract,
+        }
+    }
+}
+
+impl<S, E: Clone> Layer<S> for MetricsLayer<E> {
+    type Service = MetricsService<S, E>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MetricsService {
+            inner,
+            metrics: self.metrics.clone(),
+            extract: self.extract.clone(),
+        }
+    }
+}
+
+/// The service created by [`MetricsLayer`]
+#[derive(Clone)]
+pub struct MetricsService<S, E = MatchedPathRoute> {
+    inner: S,
+    metrics: Arc<HttpMetrics>,
+    extract: E,
+}
+
+impl<S, E, B, ResBody> Service<Request<B>> for MetricsService<S, E>
+where
+    S: Service<Request<B>, Response = Response<ResBody>>,
+    E: ExtractRoute<B>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future, E::Route>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request<B>) -> Self::Future {
+        let request = InFlightRequest {
+            route: self.extract.extract(&req),
+            method: Method::from(req.method()),
+            metrics: self.metrics.clone(),
+            start: Instant::now(),
+        };
+        request
+            .metrics
+            .http_requests_in_flight
+            .inc(request.in_flight_labels());
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            request,
+        }
+    }
+}
+
+/// Tracks an in-flight request. Dropping it, whether the request completed or was cancelled,
+/// removes it from [`HttpMetrics::http_requests_in_flight`].
+struct InFlightRequest<R: AsRef<str>> {
+    route: Option<R>,
+    method: Method,
+    metrics: Arc<HttpMetrics>,
+    start: Instant,
+}
+
+impl<R: AsRef<str>> InFlightRequest<R> {
+    fn route(&self) -> &str {
+        self.route.as_ref().map_or(UNKNOWN_ROUTE, AsRef::as_ref)
+    }
+
+    fn in_flight_labels(&self) -> InFlight<'_> {
+        InFlight {
+            route: self.route(),
+            method: self.method,
+        }
+    }
+
+    fn complete(&self, status: StatusClass) {
+        let labels = HttpRequest {
+            route: self.route(),
+            method: self.method,
+            status,
+        };
+        self.metrics.http_requests_total.inc(labels);
+        self.metrics
+            .http_request_duration_seconds
+            .observe_duration_since(labels, self.start);
+    }
+}
+
+impl<R: AsRef<str>> Drop for InFlightRequest<R> {
+    fn drop(&mut self) {
+        self.metrics
+            .http_requests_in_flight
+            .dec(self.in_flight_labels());
+    }
+}
+
+pin_project! {
+    /// The response future of [`MetricsService`]
+    pub struct ResponseFuture<F, R: AsRef<str>> {
+        #[pin]
+        inner: F,
+        request: InFlightRequest<R>,
+    }
+}
+
+impl<F, R, ResBody, E> Future for ResponseFuture<F, R>
+where
+    F: Future<Output = Result<Response<ResBody>, E>>,
+    R: AsRef<str>,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let this = self.project();
+        let res = ready!(this.inner.poll(cx));
+
+        let status = match &res {
+            Ok(response) => StatusClass::from(response.status()),
+            Err(_) => StatusClass::ServerError,
+        };
+        this.request.complete(status);
+
+        Poll::Ready(res)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        convert::Infallible,
+        future::Future,
+        sync::Arc,
+        task::{Context, Waker},
+    };
+
+    use axum::http::{Request, Response, StatusCode};
+    use measured::{text::BufferedTextEncoder, MetricGroup};
+    use tower::{service_fn, Layer, Service, ServiceExt};
+
+    use super::{HttpMetrics, MetricsLayer};
+
+    fn encode(metrics: &HttpMetrics) -> String {
+        let mut enc = BufferedTextEncoder::new();
+        metrics.collect_group_into(&mut enc).unwrap();
+        String::from_utf8(enc.finish().to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn route_cap() {
+        let metrics = Arc::new(HttpMetrics::with_max_routes(2));
+
+        let service = MetricsLayer::new(metrics.clone())
+            .with_route_extractor(|req: &Request<()>| {
+                let path = req.uri().path();
+                (path != "/").then(|| path.to_owned())
+            })
+            .layer(service_fn(|req: Request<()>| async move {
+                let status = match req.uri().path() {
+                    "/a" => StatusCode::OK,
+                    _ => StatusCode::NOT_FOUND,
+                };
+                let mut response = Response::new(());
+                *response.status_mut() = status;
+                Ok::<_, Infallible>(response)
+            }));
+
+        for path in ["/a", "/a", "/b", "/c", "/d", "/"] {
+            let req = Request::get(path).body(()).unwrap();
+            service.clone().oneshot(req).await.unwrap();
+        }
+
+        let output = encode(&metrics);
+        for line in [
+            "http_requests_total{route=\"/a\",method=\"get\",status=\"2xx\"} 2\n",
+            "http_requests_total{route=\"/b\",method=\"get\",status=\"4xx\"} 1\n",
+            "http_requests_total{route=\"other\",method=\"get\",status=\"4xx\"} 2\n",
+            "http_requests_total{route=\"unknown\",method=\"get\",status=\"4xx\"} 1\n",
+            "http_request_duration_seconds_count{route=\"other\",method=\"get\",status=\"4xx\"} 2\n",
+            "http_requests_in_flight{route=\"/a\",method=\"get\"} 0\n",
+        ] {
+            assert!(output.contains(line), "{line:?} missing from {output}");
+        }
+        assert!(!output.contains("/c"));
+    }
+
+    #[test]
+    fn in_flight() {
+        let metrics = Arc::new(HttpMetrics::new());
+
+        let mut service = MetricsLayer::new(metrics.clone())
+            .with_route_extractor(|_: &Request<()>| Some("/slow"))
+            .layer(service_fn(|_: Request<()>| {
+                std::future::pending::<Result<Response<()>, Infallible>>()
+            }));
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut fut = Box::pin(service.call(Request::post("/slow").body(()).unwrap()));
+        assert!(fut.as_mut().poll(&mut cx).is_pending());
+
+        let output = encode(&metrics);
+        assert!(output.contains("http_requests_in_flight{route=\"/slow\",method=\"post\"} 1\n"));
+
+        // cancelled requests are no longer in flight, and are not counted as completed
+        drop(fut);
+        let output = encode(&metrics);
+        assert!(output.contains("http_requests_in_flight{route=\"/slow\",method=\"post\"} 0\n"));
+        assert!(!output.contains("http_requests_total{"));
+    }
+}
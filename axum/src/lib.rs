@@ -0,0 +1,309 @@
+//! Axum and tower integration for measured.
+//!
+//! ```
+//! use std::sync::Arc;
+//!
+//! use axum::{routing::get, Router};
+//! use measured_axum::{metrics_handler, HttpMetrics, MetricsLayer};
+//!
+//! let metrics = Arc::new(HttpMetrics::new());
+//!
+//! let app: Router = Router::new()
+//!     .route("/users/:id", get(|| async { "hello" }))
+//!     .route("/metrics", get(metrics_handler(metrics.clone()).with_protobuf()))
+//!     .layer(MetricsLayer::new(metrics));
+//! ```
+
+use std::{
+    future::{ready, Ready},
+    io,
+    sync::{Arc, Mutex, PoisonError},
+};
+
+use axum::{
+    body::Body,
+    extract::Request,
+    handler::Handler,
+    http::{header, HeaderMap, HeaderValue, StatusCode},
+    response::Response,
+};
+use measured::{
+    text::{
+        compress::{Compression, Compressor, Format},
+        openmetrics::OpenMetricsEncoder,
+        TextEncoder,
+    },
+    MetricGroup,
+};
+use measured_prometheus_protobuf::ProtoEncoder;
+
+mod layer;
+
+pub use layer::{
+    ExtractRoute, HttpMetrics, HttpRequest, HttpRequestSet, InFlight, InFlightSet,
+    MatchedPathRoute, MatchedRoute, Method, MetricsLayer, MetricsService, ResponseFuture, RouteSet,
+    StatusClass, DEFAULT_MAX_ROUTES, OTHER_ROUTE, UNKNOWN_ROUTE,
+};
+
+type CollectProto<G> = fn(&G, &mut ProtoEncoder<Compressor>) -> io::Result<()>;
+
+struct Encoders {
+    text: TextEncoder<Compressor>,
+    openmetrics: OpenMetricsEncoder<Compressor>,
+    protobuf: ProtoEncoder<Compressor>,
+}
+
+impl Encoders {
+    fn new() -> Self {
+        Self {
+            text: TextEncoder::new(Compressor::new(Compression::Identity)),
+            openmetrics: OpenMetricsEncoder::new(Compressor::new(Compression::Identity)),
+            protobuf: ProtoEncoder::new(Compressor::new(Compression::Identity)),
+        }
+    }
+}
+
+fn set_compression(c: &mut Compressor, compression: Compression) {
+    if c.compression() != compression {
+        *c = Compressor::new(compression);
+    }
+}
+
+/// An axum [`Handler`] which serves a [`MetricGroup`].
+///
+/// The encoders are shared between all clones of the handler and re-used across requests.
+/// See [`metrics_handler`].
+pub struct MetricsHandler<G> {
+    group: Arc<G>,
+    encoders: Arc<Mutex<Encoders>>,
+    protobuf: Option<CollectProto<G>>,
+}
+
+impl<G> Clone for MetricsHandler<G> {
+    fn clone(&self) -> Self {
+        Self {
+            group: self.group.clone(),
+            encoders: self.encoders.clone(),
+            protobuf: self.protobuf,
+        }
+    }
+}
+
+/// Create a handler which serves the metric group.
+///
+/// The response is encoded in the prometheus text format, or in the OpenMetrics text format if the client prefers it in
+/// the `Accept` header. The response is compressed if the client accepts it and the `gzip` or `zstd` feature is enabled.
+pub fn metrics_handler<G>(group: Arc<G>) -> MetricsHandler<G>
+where
+    G: MetricGroup<TextEncoder<Compressor>> + MetricGroup<OpenMetricsEncoder<Compressor>>,
+{
+    MetricsHandler {
+        group,
+        encoders: Arc::new(Mutex::new(Encoders::new())),
+        protobuf: None,
+    }
+}
+
+impl<G> MetricsHandler<G>
+where
+    G: MetricGroup<TextEncoder<Compressor>> + MetricGroup<OpenMetricsEncoder<Compressor>>,
+{
+    /// Also serve the prometheus protobuf format if the client prefers it in the `Accept` header.
+    ///
+    /// Not all metric types can be encoded as protobuf, so this is only available if the group supports it.
+    pub fn with_protobuf(self) -> Self
+    where
+        G: MetricGroup<ProtoEncoder<Compressor>>,
+    {
+        Self {
+            protobuf: Some(<G as MetricGroup<ProtoEncoder<Compressor>>>::collect_group_into),
+            ..self
+        }
+    }
+
+    /// Encode the metric group into a response for a request with the given headers.
+    pub fn render(&self, headers: &HeaderMap) -> Response {
+        let accept = headers.get(header::ACCEPT).and_then(|v| v.to_str().ok());
+        let accept_encoding = headers
+            .get(header::ACCEPT_ENCODING)
+            .and_then(|v| v.to_str().ok());
+
+        let supported: &[Format] = match self.protobuf {
+            Some(_) => &[Format::Text, Format::OpenMetrics, Format::Protobuf],
+            None => &[Format::Text, Format::OpenMetrics],
+        };
+        let format = Format::negotiate_from(accept, supported);
+        let compression = Compression::negotiate(accept_encoding);
+
+        let mut encoders = self.encoders.lock().unwrap_or_else(PoisonError::into_inner);
+        let body = self.encode(&mut encoders, format, compression);
+
+        let body = match body {
+            Ok(body) => body,
+            Err(_) => {
+                // don't leave any partially encoded metrics behind for the next request
+                *encoders = Encoders::new();
+                let mut response = Response::new(Body::empty());
+                *response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return response;
+            }
+        };
+        drop(encoders);
+
+        let mut response = Response::new(Body::from(body));
+        let headers = response.headers_mut();
+        headers.insert(
+            header::CONTENT_TYPE,
+            HeaderValue::from_static(format.content_type()),
+        );
+        if let Some(encoding) = compression.content_encoding() {
+            headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+        }
+        headers.insert(
+            header::VARY,
+            HeaderValue::from_static("accept, accept-encoding"),
+        );
+        response
+    }
+
+    fn encode(
+        &self,
+        encoders: &mut Encoders,
+        format: Format,
+        compression: Compression,
+    ) -> io::Result<axum::body::Bytes> {
+        match (format, self.protobuf) {
+            (Format::OpenMetrics, _) => {
+                let enc = &mut encoders.openmetrics;
+                set_compression(&mut enc.writer, compression);
+                self.group.collect_group_into(enc)?;
+                enc.finish()?;
+                enc.writer.finish()
+            }
+            (Format::Protobuf, Some(collect)) => {
+                let enc = &mut encoders.protobuf;
+                set_compression(&mut enc.writer, compression);
+                collect(&self.group, enc)?;
+                enc.flush()?;
+                enc.writer.finish()
+            }
+            _ => {
+                let enc = &mut encoders.text;
+                set_compression(&mut enc.writer, compression);
+                self.group.collect_group_into(enc)?;
+                enc.flush()?;
+                enc.writer.finish()
+            }
+        }
+    }
+}
+
+impl<G, S> Handler<(), S> for MetricsHandler<G>
+where
+    G: MetricGroup<TextEncoder<Compressor>> + MetricGroup<OpenMetricsEncoder<Compressor>>,
+    G: Send + Sync + 'static,
+{
+    type Future = Ready<Response>;
+
+    fn call(self, req: Request, _state: S) -> Self::Future {
+        ready(self.render(req.headers()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use axum::{
+        body::{to_bytes, Body},
+        http::{header, Request, StatusCode},
+        routing::get,
+        Router,
+    };
+    use tower::ServiceExt;
+
+    use crate::{metrics_handler, HttpMetrics, MetricsLayer};
+
+    async fn get_metrics(app: &Router, accept: &str) -> (String, String) {
+        let response = app
+            .clone()
+            .oneshot(
+                Request::get("/metrics")
+                    .header(header::ACCEPT, accept)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let content_type = response.headers()[header::CONTENT_TYPE]
+            .to_str()
+            .unwrap()
+            .to_owned();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (content_type, String::from_utf8_lossy(&body).into_owned())
+    }
+
+    #[tokio::test]
+    async fn metrics_endpoint() {
+        let metrics = Arc::new(HttpMetrics::new());
+
+        let app = Router::new()
+            .route(
+                "/users/:id",
+                get(|| async { StatusCode::OK }).post(|| async { StatusCode::FORBIDDEN }),
+            )
+            .route(
+                "/metrics",
+                get(metrics_handler(metrics.clone()).with_protobuf()),
+            )
+            .layer(MetricsLayer::new(metrics.clone()));
+
+        for (method, uri) in [
+            ("GET", "/users/1"),
+            ("GET", "/users/2"),
+            ("POST", "/users/1"),
+        ] {
+            let request = Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(request).await.unwrap();
+        }
+
+        let (content_type, body) = get_metrics(&app, "text/plain").await;
+        assert_eq!(content_type, "text/plain; version=0.0.4; charset=utf-8");
+        assert!(body.contains(
+            "http_requests_total{route=\"/users/:id\",method=\"get\",status=\"2xx\"} 2\n"
+        ));
+        assert!(body.contains(
+            "http_requests_total{route=\"/users/:id\",method=\"post\",status=\"4xx\"} 1\n"
+        ));
+        assert!(body.contains(
+            "http_request_duration_seconds_count{route=\"/users/:id\",method=\"get\",status=\"2xx\"} 2\n"
+        ));
+        // the metrics request itself is in flight while encoding
+        assert!(body.contains("http_requests_in_flight{route=\"/metrics\",method=\"get\"} 1\n"));
+
+        let (content_type, body) =
+            get_metrics(&app, "application/openmetrics-text; version=1.0.0").await;
+        assert_eq!(
+            content_type,
+            "application/openmetrics-text; version=1.0.0; charset=utf-8"
+        );
+        assert!(body.contains("# TYPE http_requests counter\n"));
+        assert!(body.contains(
+            "http_requests_total{route=\"/users/:id\",method=\"get\",status=\"2xx\"} 2\n"
+        ));
+        assert!(!body.contains("_total_total"));
+        assert!(body.ends_with("# EOF\n"));
+
+        let (content_type, _) = get_metrics(
+            &app,
+            "application/vnd.google.protobuf;proto=io.prometheus.client.MetricFamily;encoding=delimited",
+        )
+        .await;
+        assert_eq!(content_type, measured_prometheus_protobuf::CONTENT_TYPE);
+    }
+}
@@ -58,6 +58,25 @@ fn measured_sparse(bencher: Bencher) {
         });
 }
 
+#[divan::bench]
+fn measured_sharded(bencher: Bencher) {
+    let error_set = ErrorsSet {
+        kind: StaticLabelSet::new(),
+        route: Rodeo::from_iter(routes()).into_reader(),
+    };
+    let counter_vec = measured::ShardedCounterVec::with_label_set(error_set);
+
+    thread_local! {
+        static RNG: RefCell<SmallRng> = RefCell::new(thread_rng());
+    }
+
+    bencher
+        .with_inputs(|| RNG.with(|rng| get(&mut *rng.borrow_mut())))
+        .bench_values(|(kind, route)| {
+            counter_vec.inc(Error { kind, route });
+        });
+}
+
 #[divan::bench]
 fn prometheus(bencher: Bencher) {
     let registry = prometheus::Registry::new();
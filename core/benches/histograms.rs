@@ -221,9 +221,12 @@ mod fixed_cardinality {
 
 #[divan::bench_group(sample_size = 100000, sample_count = 500)]
 mod no_cardinality {
-    use std::time::Instant;
+    use std::{
+        sync::atomic::{AtomicBool, Ordering},
+        time::Instant,
+    };
 
-    use divan::Bencher;
+    use divan::{black_box, Bencher};
     use measured::metric::histogram::Thresholds;
     use prometheus::exponential_buckets;
 
@@ -237,6 +240,26 @@ mod no_cardinality {
         bencher.bench(|| drop(h.start_timer()));
     }
 
+    /// Observations should not be blocked by a concurrent scrape
+    #[divan::bench]
+    fn measured_while_sampling(bencher: Bencher) {
+        let h =
+            measured::Histogram::with_metadata(Thresholds::<N>::exponential_buckets(0.00001, 2.0));
+
+        let stop = AtomicBool::new(false);
+        std::thread::scope(|s| {
+            s.spawn(|| {
+                while !stop.load(Ordering::Relaxed) {
+                    black_box(h.snapshot());
+                }
+            });
+
+            bencher.bench(|| drop(h.start_timer()));
+
+            stop.store(true, Ordering::Relaxed);
+        });
+    }
+
     #[divan::bench]
     fn prometheus(bencher: Bencher) {
         let registry = prometheus::Registry::new();
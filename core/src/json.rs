@@ -0,0 +1,848 @@
+//! JSON based exporter, for debug endpoints and structured test assertions.
+//!
+//! The document is made up of metric families, each with their samples:
+//!
+//! ```json
+//! {"families":[
+//!   {"name":"http_requests_total","help":"total requests","type":"counter","samples":[
+//!     {"labels":{"method":"get"},"value":3}
+//!   ]},
+//!   {"name":"http_latency_seconds","type":"histogram","samples":[
+//!     {"labels":{},"buckets":[{"le":0.1,"count":1},{"le":"+Inf","count":2}],"sum":0.55,"count":2}
+//!   ]}
+//! ]}
+//! ```
+//!
+//! Label values are always strings. Sample values are numbers, except for `NaN` and infinities,
+//! which are not valid JSON numbers and are written as the strings `"NaN"`, `"+Inf"` and `"-Inf"`.
+
+use std::io::Write;
+
+use crate::{
+    label::{
+        FixedCardinalityLabel, LabelGroup, LabelGroupVisitor, LabelName, LabelValue, LabelVisitor,
+    },
+    metric::{
+        counter::{CounterState, ExemplarCounterState, ShardedCounterState},
+        exemplar::Exemplar,
+        gauge::{FloatGaugeState, GaugeState},
+        group::{Encoding, MetricValue},
+        histogram::{ExemplarHistogramState, HistogramState, Thresholds},
+        info::{InfoLabels, InfoState},
+        name::MetricNameEncoder,
+        native_histogram::{NativeHistogramConfig, NativeHistogramState},
+        state_set::StateSetState,
+        summary::{Quantiles, SummaryState},
+        untyped::UntypedState,
+        windowed_histogram::{Window, WindowedHistogramState},
+        MetricEncoding,
+    },
+    text::{
+        count_value,
+        federate::{FederatedCounterState, FederatedHistogramState, FederatedSummaryState},
+        MetricType,
+    },
+};
+
+/// The content type of the JSON document
+pub const CONTENT_TYPE: &str = "application/json";
+
+/// The JSON encoder helper
+pub struct JsonEncoder<W> {
+    state: State,
+    /// The inner writer for this JSON encoder.
+    pub writer: W,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum State {
+    /// No families have been written yet
+    Init,
+    /// A family has been opened, with a name and help text but no type or samples
+    Help,
+    /// A family has been opened, but no samples have been written
+    Type,
+    /// A family has been opened, and at least one sample has been written
+    Samples,
+}
+
+impl<W: Write> Encoding for JsonEncoder<W> {
+    type Err = std::io::Error;
+
+    /// Start a new metric family with the help text
+    fn write_help(
+        &mut self,
+        name: impl MetricNameEncoder,
+        help: &str,
+    ) -> Result<(), std::io::Error> {
+        self.start_family(&name)?;
+        self.writer.write_all(b",\"help\":")?;
+        write_json_str(help, &mut self.writer)?;
+        self.state = State::Help;
+        Ok(())
+    }
+}
+
+impl<W: Write> JsonEncoder<W> {
+    /// Create a new JSON encoder.
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(w: W) -> Self {
+        Self {
+            state: State::Init,
+            writer: w,
+        }
+    }
+
+    /// Finish the JSON document. The encoder can then be re-used for the next document.
+    pub fn flush(&mut self) -> std::io::Result<()> {
+        match self.state {
+            State::Init => self.writer.write_all(b"{\"families\":[")?,
+            State::Help => self.writer.write_all(b"}")?,
+            State::Type | State::Samples => self.writer.write_all(b"]}")?,
+        }
+        self.writer.write_all(b"]}")?;
+        self.state = State::Init;
+        self.writer.flush()
+    }
+
+    /// Close the previous family, and open a new one with the given name.
+    fn start_family(&mut self, name: &impl MetricNameEncoder) -> std::io::Result<()> {
+        match self.state {
+            State::Init => self.writer.write_all(b"{\"families\":[")?,
+            State::Help => self.writer.write_all(b"},")?,
+            State::Type | State::Samples => self.writer.write_all(b"]},")?,
+        }
+        self.writer.write_all(b"{\"name\":\"")?;
+        name.encode_utf8(&mut self.writer)?;
+        self.writer.write_all(b"\"")
+    }
+
+    /// Write the type of a metric family. This starts a new family, unless the help text was just written.
+    pub fn write_type(
+        &mut self,
+        name: &impl MetricNameEncoder,
+        typ: MetricType,
+    ) -> Result<(), std::io::Error> {
+        if self.state != State::Help {
+            self.start_family(name)?;
+        }
+
+        self.writer.write_all(b",\"type\":")?;
+        match typ {
+            MetricType::Counter => self.writer.write_all(b"\"counter\"")?,
+            MetricType::Histogram => self.writer.write_all(b"\"histogram\"")?,
+            MetricType::Gauge => self.writer.write_all(b"\"gauge\"")?,
+            MetricType::Summary => self.writer.write_all(b"\"summary\"")?,
+            MetricType::Untyped => self.writer.write_all(b"\"untyped\"")?,
+            MetricType::Info => self.writer.write_all(b"\"info\"")?,
+            MetricType::StateSet => self.writer.write_all(b"\"stateset\"")?,
+        }
+        self.writer.write_all(b",\"samples\":[")?;
+        self.state = State::Type;
+        Ok(())
+    }
+
+    /// Write a sample object. `fields` writes the fields that follow the labels, each preceded by a comma.
+    fn write_sample(
+        &mut self,
+        labels: impl LabelGroup,
+        fields: impl FnOnce(&mut W) -> std::io::Result<()>,
+    ) -> std::io::Result<()> {
+        if self.state == State::Samples {
+            self.writer.write_all(b",")?;
+        }
+        self.state = State::Samples;
+
+        self.writer.write_all(b"{\"labels\":")?;
+        write_labels(&mut self.writer, labels)?;
+        fields(&mut self.writer)?;
+        self.writer.write_all(b"}")
+    }
+
+    fn write_value(&mut self, labels: impl LabelGroup, value: Number) -> std::io::Result<()> {
+        self.write_sample(labels, |w| {
+            w.write_all(b",\"value\":")?;
+            value.write(w)
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Uint(u64),
+    Float(f64),
+}
+
+impl Number {
+    fn write(self, w: &mut impl Write) -> std::io::Result<()> {
+        match self {
+            Number::Int(x) => w.write_all(itoa::Buffer::new().format(x).as_bytes()),
+            Number::Uint(x) => w.write_all(itoa::Buffer::new().format(x).as_bytes()),
+            Number::Float(x) if x.is_nan() => w.write_all(b"\"NaN\""),
+            Number::Float(x) if x == f64::INFINITY => w.write_all(b"\"+Inf\""),
+            Number::Float(x) if x == f64::NEG_INFINITY => w.write_all(b"\"-Inf\""),
+            Number::Float(x) => w.write_all(ryu::Buffer::new().format_finite(x).as_bytes()),
+        }
+    }
+}
+
+/// Write a JSON string, including the surrounding quotes.
+fn write_json_str(s: &str, w: &mut impl Write) -> std::io::Result<()> {
+    w.write_all(b"\"")?;
+    let mut start = 0;
+    for (i, b) in s.bytes().enumerate() {
+        let escape: &[u8] = match b {
+            b'"' => b"\\\"",
+            b'\\' => b"\\\\",
+            b'\n' => b"\\n",
+            b'\r' => b"\\r",
+            b'\t' => b"\\t",
+            0..=0x1f => b"",
+            _ => continue,
+        };
+        w.write_all(&s.as_bytes()[start..i])?;
+        if escape.is_empty() {
+            write!(w, "\\u{b:04x}")?;
+        } else {
+            w.write_all(escape)?;
+        }
+        start = i + 1;
+    }
+    w.write_all(&s.as_bytes()[start..])?;
+    w.write_all(b"\"")
+}
+
+/// Write the labels as a JSON object of strings.
+fn write_labels(writer: &mut impl Write, labels: impl LabelGroup) -> std::io::Result<()> {
+    struct Visitor<'a, W> {
+        writer: &'a mut W,
+    }
+    impl<W: Write> LabelVisitor for Visitor<'_, W> {
+        type Output = std::io::Result<()>;
+        fn write_int(self, x: i64) -> std::io::Result<()> {
+            self.write_str(itoa::Buffer::new().format(x))
+        }
+
+        fn write_float(self, x: f64) -> std::io::Result<()> {
+            if x.is_infinite() {
+                if x.is_sign_positive() {
+                    self.write_str("+Inf")
+                } else {
+                    self.write_str("-Inf")
+                }
+            } else if x.is_nan() {
+                self.write_str("NaN")
+            } else {
+                self.write_str(ryu::Buffer::new().format(x))
+            }
+        }
+
+        fn write_str(self, x: &str) -> std::io::Result<()> {
+            write_json_str(x, self.writer)
+        }
+    }
+
+    struct GroupVisitor<'a, W> {
+        first: bool,
+        writer: &'a mut W,
+    }
+    impl<W: Write> LabelGroupVisitor for GroupVisitor<'_, W> {
+        type Output = std::io::Result<()>;
+        fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) -> std::io::Result<()> {
+            if !self.first {
+                self.writer.write_all(b",")?;
+            }
+            self.first = false;
+            self.writer.write_all(b"\"")?;
+            self.writer.write_all(name.as_str().as_bytes())?;
+            self.writer.write_all(b"\":")?;
+            x.visit(Visitor {
+                writer: self.writer,
+            })
+        }
+    }
+
+    writer.write_all(b"{")?;
+    labels.visit_values(&mut GroupVisitor {
+        first: true,
+        writer: &mut *writer,
+    });
+    writer.write_all(b"}")
+}
+
+/// Write an exemplar field, preceded by a comma.
+fn write_exemplar(w: &mut impl Write, exemplar: &Exemplar) -> std::io::Result<()> {
+    let timestamp = exemplar
+        .timestamp()
+        .duration_since(std::time::SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs_f64();
+
+    w.write_all(b",\"exemplar\":{\"labels\":")?;
+    write_labels(w, exemplar)?;
+    w.write_all(b",\"value\":")?;
+    Number::Float(exemplar.value()).write(w)?;
+    w.write_all(b",\"timestamp\":")?;
+    Number::Float(timestamp).write(w)?;
+    w.write_all(b"}")
+}
+
+/// Write the cumulative buckets, sum and count of a histogram, with the exemplar of each bucket if there is one.
+fn write_histogram<W: Write, const N: usize>(
+    enc: &mut JsonEncoder<W>,
+    metadata: &Thresholds<N>,
+    labels: impl LabelGroup,
+    (buckets, inf, sum): ([u64; N], u64, f64),
+    exemplars: &[Option<Exemplar>],
+) -> Result<(), std::io::Error> {
+    let count = buckets.iter().sum::<u64>() + inf;
+    let les = metadata.get().iter().copied().chain([f64::INFINITY]);
+    let cumulative = buckets.into_iter().chain([inf]).scan(0, |c, b| {
+        *c += b;
+        Some(*c)
+    });
+    let exemplars = (0..).map(|i| exemplars.get(i).and_then(Option::as_ref));
+    let buckets = les
+        .zip(cumulative)
+        .zip(exemplars)
+        .map(|((le, c), e)| (le, c, e));
+    write_cumulative_buckets(enc, labels, buckets, sum, count)
+}
+
+/// Write the cumulative buckets, sum and count of a histogram.
+fn write_cumulative_buckets<'a, W: Write>(
+    enc: &mut JsonEncoder<W>,
+    labels: impl LabelGroup,
+    buckets: impl Iterator<Item = (f64, u64, Option<&'a Exemplar>)>,
+    sum: f64,
+    count: u64,
+) -> Result<(), std::io::Error> {
+    enc.write_sample(labels, |w| {
+        w.write_all(b",\"buckets\":[")?;
+        for (i, (le, c, exemplar)) in buckets.enumerate() {
+            if i > 0 {
+                w.write_all(b",")?;
+            }
+            w.write_all(b"{\"le\":")?;
+            Number::Float(le).write(w)?;
+            w.write_all(b",\"count\":")?;
+            Number::Uint(c).write(w)?;
+            if let Some(exemplar) = exemplar {
+                write_exemplar(w, exemplar)?;
+            }
+            w.write_all(b"}")?;
+        }
+        w.write_all(b"],\"sum\":")?;
+        Number::Float(sum).write(w)?;
+        w.write_all(b",\"count\":")?;
+        Number::Uint(count).write(w)
+    })
+}
+
+impl<W: Write, const N: usize> MetricEncoding<JsonEncoder<W>> for HistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_histogram(enc, metadata, labels, self.sample(), &[])
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<JsonEncoder<W>> for ExemplarHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.sample();
+        let exemplars = self.exemplars.lock();
+        write_histogram(enc, metadata, labels, sample, &exemplars)
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<JsonEncoder<W>> for WindowedHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Window<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        self.histogram
+            .collect_into(metadata.thresholds(), labels, name, enc)
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for NativeHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        metadata: &NativeHistogramConfig,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.inner.write().sample();
+
+        let write_buckets = |w: &mut W, buckets: &[(i32, u64)]| {
+            w.write_all(b"[")?;
+            for (i, &(index, count)) in buckets.iter().enumerate() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                w.write_all(b"{\"index\":")?;
+                Number::Int(index as i64).write(w)?;
+                w.write_all(b",\"count\":")?;
+                Number::Uint(count).write(w)?;
+                w.write_all(b"}")?;
+            }
+            w.write_all(b"]")
+        };
+
+        enc.write_sample(labels, |w| {
+            w.write_all(b",\"schema\":")?;
+            Number::Int(metadata.schema() as i64).write(w)?;
+            w.write_all(b",\"zero_threshold\":")?;
+            Number::Float(metadata.zero_threshold()).write(w)?;
+            w.write_all(b",\"zero_count\":")?;
+            Number::Uint(sample.zero).write(w)?;
+            w.write_all(b",\"positive\":")?;
+            write_buckets(w, &sample.positive)?;
+            w.write_all(b",\"negative\":")?;
+            write_buckets(w, &sample.negative)?;
+            w.write_all(b",\"sum\":")?;
+            Number::Float(sample.sum).write(w)?;
+            w.write_all(b",\"count\":")?;
+            Number::Uint(sample.count).write(w)
+        })
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for SummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Quantiles,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let (values, count, sum) = self.inner.lock().sample(metadata);
+        let quantiles = metadata.get().iter().map(|&(q, _)| q).zip(values);
+        write_quantiles(enc, labels, quantiles, sum, count)
+    }
+}
+
+/// Write the quantiles, sum and count of a summary.
+fn write_quantiles<W: Write>(
+    enc: &mut JsonEncoder<W>,
+    labels: impl LabelGroup,
+    quantiles: impl Iterator<Item = (f64, f64)>,
+    sum: f64,
+    count: u64,
+) -> Result<(), std::io::Error> {
+    enc.write_sample(labels, |w| {
+        w.write_all(b",\"quantiles\":[")?;
+        for (i, (quantile, value)) in quantiles.enumerate() {
+            if i > 0 {
+                w.write_all(b",")?;
+            }
+            w.write_all(b"{\"quantile\":")?;
+            Number::Float(quantile).write(w)?;
+            w.write_all(b",\"value\":")?;
+            Number::Float(value).write(w)?;
+            w.write_all(b"}")?;
+        }
+        w.write_all(b"],\"sum\":")?;
+        Number::Float(sum).write(w)?;
+        w.write_all(b",\"count\":")?;
+        Number::Uint(count).write(w)
+    })
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for CounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(core::sync::atomic::Ordering::Relaxed);
+        enc.write_value(labels, Number::Uint(count))
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for ShardedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_value(labels, Number::Uint(self.get()))
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for ExemplarCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(core::sync::atomic::Ordering::Relaxed);
+        let exemplar = self.exemplar.lock();
+        enc.write_sample(labels, |w| {
+            w.write_all(b",\"value\":")?;
+            Number::Uint(count).write(w)?;
+            if let Some(exemplar) = &*exemplar {
+                write_exemplar(w, exemplar)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for GaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Gauge)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let gauge = self.count.load(core::sync::atomic::Ordering::Relaxed);
+        enc.write_value(labels, Number::Int(gauge))
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for FloatGaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Gauge)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_value(labels, Number::Float(self.count.get()))
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for UntypedState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Untyped)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_value(labels, Number::Float(self.value.get()))
+    }
+}
+
+/// Counters are written as integers, unless they have a fractional value
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for FederatedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let value = match count_value(self.value) {
+            MetricValue::Int(x) => Number::Int(x),
+            MetricValue::Float(x) => Number::Float(x),
+        };
+        enc.write_value(labels, value)
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for FederatedHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let buckets = self.buckets.iter().map(|&(le, c)| (le, c, None));
+        write_cumulative_buckets(enc, labels, buckets, self.sum, self.count)
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for FederatedSummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let quantiles = self.quantiles.iter().copied();
+        write_quantiles(enc, labels, quantiles, self.sum, self.count)
+    }
+}
+
+impl<W: Write> MetricEncoding<JsonEncoder<W>> for InfoState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Info)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let info = self.labels.read();
+        enc.write_sample(labels, |w| {
+            w.write_all(b",\"info\":")?;
+            write_labels(w, InfoLabels(&info))
+        })
+    }
+}
+
+impl<T: FixedCardinalityLabel, W: Write> MetricEncoding<JsonEncoder<W>> for StateSetState<T> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::StateSet)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut JsonEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        struct StateVisitor<'a, W> {
+            writer: &'a mut W,
+        }
+        impl<W: Write> LabelVisitor for StateVisitor<'_, W> {
+            type Output = std::io::Result<()>;
+            fn write_int(self, x: i64) -> std::io::Result<()> {
+                self.write_str(itoa::Buffer::new().format(x))
+            }
+            fn write_float(self, x: f64) -> std::io::Result<()> {
+                self.write_str(ryu::Buffer::new().format(x))
+            }
+            fn write_str(self, x: &str) -> std::io::Result<()> {
+                write_json_str(x, self.writer)
+            }
+        }
+
+        let state = self.state.load(core::sync::atomic::Ordering::Relaxed);
+        enc.write_sample(labels, |w| {
+            w.write_all(b",\"states\":{")?;
+            for i in 0..T::cardinality() {
+                if i > 0 {
+                    w.write_all(b",")?;
+                }
+                T::decode(i).visit(StateVisitor { writer: &mut *w })?;
+                if i == state {
+                    w.write_all(b":true")?;
+                } else {
+                    w.write_all(b":false")?;
+                }
+            }
+            w.write_all(b"}")
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        metric::{
+            group::Encoding,
+            histogram::Thresholds,
+            name::{MetricName, Total},
+            MetricFamilyEncoding,
+        },
+        CounterVec, FloatGauge, Histogram, StateSet,
+    };
+
+    use super::{write_json_str, JsonEncoder};
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured_derive::LabelGroup)]
+    #[label(crate = crate, set = RequestLabelSet)]
+    struct RequestLabels {
+        method: Method,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured_derive::FixedCardinalityLabel)]
+    #[label(crate = crate, rename_all = "snake_case")]
+    enum Method {
+        Post,
+        Get,
+    }
+
+    #[test]
+    fn escaped_str() {
+        let mut b = vec![];
+        write_json_str("Hello \\ \"World\"\nbell\x07", &mut b).unwrap();
+        assert_eq!(
+            String::from_utf8(b).unwrap(),
+            r#""Hello \\ \"World\"\nbell\u0007""#
+        );
+    }
+
+    #[test]
+    fn families() {
+        let mut enc = JsonEncoder::new(vec![]);
+
+        let requests = CounterVec::<RequestLabelSet>::new();
+        requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            3,
+        );
+        let name = MetricName::from_str("http_requests").with_suffix(Total);
+        enc.write_help(&name, "total \"requests\"").unwrap();
+        requests.collect_family_into(&name, &mut enc).unwrap();
+
+        let temperature = FloatGauge::new();
+        temperature.set(f64::NAN);
+        temperature
+            .collect_family_into(MetricName::from_str("temperature"), &mut enc)
+            .unwrap();
+
+        let latency = Histogram::with_metadata(Thresholds::with_buckets([0.1]));
+        latency.observe(0.05);
+        latency.observe(0.5);
+        latency
+            .collect_family_into(MetricName::from_str("latency"), &mut enc)
+            .unwrap();
+
+        let method = StateSet::new();
+        method.set(Method::Get);
+        method
+            .collect_family_into(MetricName::from_str("method"), &mut enc)
+            .unwrap();
+
+        enc.flush().unwrap();
+        let actual = String::from_utf8(std::mem::take(&mut enc.writer)).unwrap();
+
+        let expected = concat!(
+            r#"{"families":["#,
+            r#"{"name":"http_requests_total","help":"total \"requests\"","type":"counter","samples":["#,
+            r#"{"labels":{"method":"get"},"value":3}"#,
+            r#"]},"#,
+            r#"{"name":"temperature","type":"gauge","samples":["#,
+            r#"{"labels":{},"value":"NaN"}"#,
+            r#"]},"#,
+            r#"{"name":"latency","type":"histogram","samples":["#,
+            r#"{"labels":{},"buckets":[{"le":0.1,"count":1},{"le":"+Inf","count":2}],"sum":0.55,"count":2}"#,
+            r#"]},"#,
+            r#"{"name":"method","type":"stateset","samples":["#,
+            r#"{"labels":{},"states":{"post":false,"get":true}}"#,
+            r#"]}"#,
+            r#"]}"#,
+        );
+        assert_eq!(actual, expected);
+
+        let doc: serde_json::Value = serde_json::from_str(&actual).unwrap();
+        assert_eq!(doc["families"][2]["samples"][0]["buckets"][1]["count"], 2);
+
+        // the encoder can be re-used
+        enc.flush().unwrap();
+        assert_eq!(enc.writer, br#"{"families":[]}"#);
+    }
+}
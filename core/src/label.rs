@@ -6,7 +6,9 @@ pub(crate) mod group;
 pub(crate) mod name;
 pub(crate) mod value;
 
-pub use group::{ComposedGroup, LabelGroup, LabelGroupSet, LabelGroupVisitor, NoLabels};
+pub use group::{
+    ComposedGroup, LabelGroup, LabelGroupNames, LabelGroupSet, LabelGroupVisitor, NoLabels,
+};
 pub use name::LabelName;
 pub use value::{
     DynamicLabelSet, FixedCardinalityLabel, FixedCardinalitySet, LabelSet, LabelTestVisitor,
@@ -54,6 +54,24 @@ pub trait LabelGroupSet {
     fn decode(&self, value: &Self::Unique) -> Self::Group<'_>;
 }
 
+/// The label names of a [`LabelGroupSet`], which are known without needing any label values.
+///
+/// This is implemented for the set generated by [`derive(LabelGroup)`](crate::LabelGroup).
+pub trait LabelGroupNames {
+    /// Visit the label names in the same order that [`LabelGroup::visit_values`] writes them
+    fn visit_names(&self, v: &mut impl FnMut(&super::LabelName));
+}
+
+/// Forwards only the label names of a [`LabelGroup`]
+pub(crate) struct NameVisitor<F>(pub(crate) F);
+
+impl<F: FnMut(&super::LabelName)> LabelGroupVisitor for NameVisitor<F> {
+    type Output = ();
+    fn write_value(&mut self, name: &super::LabelName, _x: &impl super::LabelValue) {
+        (self.0)(name);
+    }
+}
+
 /// A [`LabelGroup`] with no label pairs
 pub struct NoLabels;
 
@@ -117,6 +135,13 @@ impl<A: LabelGroupSet, B: LabelGroupSet> LabelGroupSet for ComposedGroup<A, B> {
     }
 }
 
+impl<A: LabelGroupNames, B: LabelGroupNames> LabelGroupNames for ComposedGroup<A, B> {
+    fn visit_names(&self, v: &mut impl FnMut(&super::LabelName)) {
+        self.0.visit_names(v);
+        self.1.visit_names(v);
+    }
+}
+
 impl<A: LabelGroup, B: LabelGroup> LabelGroup for ComposedGroup<A, B> {
     fn visit_values(&self, v: &mut impl super::LabelGroupVisitor) {
         self.0.visit_values(v);
@@ -182,6 +207,18 @@ impl<T: LabelGroupSet + ?Sized> LabelGroupSet for Arc<T> {
     }
 }
 
+impl<T: LabelGroupNames + ?Sized> LabelGroupNames for &'static T {
+    fn visit_names(&self, v: &mut impl FnMut(&super::LabelName)) {
+        T::visit_names(self, v);
+    }
+}
+
+impl<T: LabelGroupNames + ?Sized> LabelGroupNames for Arc<T> {
+    fn visit_names(&self, v: &mut impl FnMut(&super::LabelName)) {
+        T::visit_names(self, v);
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::{FixedCardinalityLabel, LabelGroup};
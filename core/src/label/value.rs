@@ -2,7 +2,10 @@ use core::marker::PhantomData;
 
 use crate::LabelGroup;
 
-use super::LabelGroupSet;
+use super::{
+    group::{LabelGroupNames, NameVisitor},
+    LabelGroupSet, LabelName,
+};
 
 /// `StaticLabelSet` is a [`LabelSet`] for a [`FixedCardinalityLabel`]
 pub struct StaticLabelSet<T>(PhantomData<T>);
@@ -68,6 +71,15 @@ impl<T: FixedCardinalityLabel + LabelGroup> LabelGroupSet for StaticLabelSet<T>
     }
 }
 
+impl<T: FixedCardinalityLabel + LabelGroup> LabelGroupNames for StaticLabelSet<T> {
+    fn visit_names(&self, v: &mut impl FnMut(&LabelName)) {
+        // every value of a fixed cardinality label has the same label names
+        if T::cardinality() > 0 {
+            T::decode(0).visit_values(&mut NameVisitor(v));
+        }
+    }
+}
+
 /// A [`LabelVisitor`] that is useful for testing purposes
 #[derive(Default, Debug)]
 pub struct LabelTestVisitor;
@@ -205,6 +217,7 @@ mod tests {
     #[derive(Clone, Copy, PartialEq, Debug, measured_derive::FixedCardinalityLabel)]
     #[label(crate = crate)]
     #[label(singleton = "kind")]
+    #[allow(dead_code)]
     enum ErrorKind {
         User,
         Internal,
@@ -213,6 +226,7 @@ mod tests {
 
     #[derive(MetricGroup, Default)]
     #[metric(crate = crate)]
+    #[allow(dead_code)]
     struct Metrics {
         errors: CounterVec<StaticLabelSet<ErrorKind>>,
     }
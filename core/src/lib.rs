@@ -6,17 +6,26 @@
 extern crate alloc;
 
 use metric::{
-    counter::CounterState,
+    counter::{CounterState, ExemplarCounterState, ShardedCounterState},
     gauge::{FloatGaugeState, GaugeState},
-    histogram::HistogramState,
+    histogram::{ExemplarHistogramState, HistogramState},
+    info::InfoState,
+    native_histogram::NativeHistogramState,
+    state_set::StateSetState,
+    summary::SummaryState,
+    untyped::UntypedState,
+    windowed_histogram::WindowedHistogramState,
     Metric, MetricVec,
 };
 
 #[cfg(any(doc, test))]
 pub mod docs;
+pub mod json;
 pub mod label;
 pub mod metric;
+pub mod registry;
 pub mod text;
+pub mod validate;
 
 /// Implement [`FixedCardinalityLabel`] on an `enum`
 ///
@@ -159,6 +168,7 @@ pub use label::FixedCardinalityLabel;
 /// * `impl LabelGroup for T { ... }`
 /// * `struct TSet { ... }`
 /// * `impl LabelGroupSet for TSet { ... }`
+/// * `impl LabelGroupNames for TSet { ... }`
 /// * `impl TSet { pub fn new(...) -> Self {} }`
 ///     - `new` contains args for all the non-default fields.
 /// * `impl Default for TSet { ... }`
@@ -241,10 +251,20 @@ pub use label::group::LabelGroup;
 /// * `metadata = expr` - The metadata to initialise a [`Metric`] or [`MetricVec`] with.
 /// * `label_set = expr` - The [`LabelGroupSet`](label::LabelGroupSet) to initialise a [`MetricVec`] with.
 /// * `init = expr` - The expression needed to initialise the metric, if it cannot be defaulted.
+/// * `unit = "..."` - The [`Unit`](metric::name::Unit) of the metric. The unit is appended to the metric name if it
+///   doesn't already end with it, and is written in OpenMetrics `# UNIT` lines. The generated `new` function records
+///   the unit in the metadata, so that `observe_duration` converts durations into the unit. Units which are not
+///   units of time are only used for the name. Requires `new(...)`, and cannot be combined with `init`.
+///   The metric must have metadata which can record a unit, see [`UnitMetadata`](metric::name::UnitMetadata).
+/// * `help = "..."` - The help text of the metric. By default, the doc comment on the field is used.
+///   Lines of the doc comment are joined with spaces, and paragraphs are separated by newlines.
+/// * `no_help` - Don't write any help text for the metric, even if the field has a doc comment.
 ///
 /// # Outputs
 ///
 /// * `impl MetricGroup for T { ... }`
+/// * `impl MetricGroupDescribe for T { ... }`
+///     - only usable if every field can be described. See [`metric::describe`].
 /// * `impl MetricGroup { pub fn new(...) -> Self { ... } }`
 pub use measured_derive::MetricGroup;
 
@@ -317,6 +337,188 @@ pub type Histogram<const N: usize> = Metric<HistogramState<N>>;
 /// ```
 pub type HistogramVec<L, const N: usize> = MetricVec<HistogramState<N>, L>;
 
+/// A [`Histogram`] that also keeps the most recent [`Exemplar`](metric::exemplar::Exemplar) of each bucket,
+/// to link the histogram with individual traces.
+///
+/// Exemplars are only encoded by encoders that support them, such as the OpenMetrics encoder.
+pub type ExemplarHistogram<const N: usize> = Metric<ExemplarHistogramState<N>>;
+
+/// A collection of multiple [`ExemplarHistogram`]s, keyed by [`LabelGroup`]s
+pub type ExemplarHistogramVec<L, const N: usize> = MetricVec<ExemplarHistogramState<N>, L>;
+
+/// A [`Metric`] that counts individual observations from an event or sample stream in sparse, exponentially sized buckets.
+/// Unlike a [`Histogram`], the bucket layout does not need to be configured up front.
+///
+/// Native histograms can only be fully represented by the protobuf exposition format.
+/// The text format only exposes the sum and count of observations.
+///
+/// ```
+/// use measured::NativeHistogram;
+/// use measured::metric::native_histogram::NativeHistogramConfig;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // create a histogram where each power of two is split into 8 buckets
+/// let histogram = NativeHistogram::with_metadata(NativeHistogramConfig::new(3));
+/// // observe a value
+/// histogram.observe(1.0);
+///
+/// // sample the histogram and encode the value to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_native_histogram");
+/// histogram.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type NativeHistogram = Metric<NativeHistogramState>;
+
+/// A collection of multiple [`NativeHistogram`]s, keyed by [`LabelGroup`]s
+///
+/// ```
+/// use measured::{NativeHistogramVec, LabelGroup, FixedCardinalityLabel};
+/// use measured::metric::native_histogram::NativeHistogramConfig;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // Define a fixed cardinality label
+///
+/// #[derive(FixedCardinalityLabel, Copy, Clone)]
+/// enum Operation {
+///     Create,
+///     Update,
+///     Delete,
+/// }
+///
+/// // Define a label group, consisting of 1 or more label values
+///
+/// #[derive(LabelGroup)]
+/// #[label(set = MyLabelGroupSet)]
+/// struct MyLabelGroup {
+///     operation: Operation,
+/// }
+///
+/// // create a native histogram vec
+/// let histograms = NativeHistogramVec::with_label_set_and_metadata(
+///     MyLabelGroupSet::new(),
+///     NativeHistogramConfig::default(),
+/// );
+/// // observe a value
+/// histograms.observe(MyLabelGroup { operation: Operation::Create }, 0.5);
+/// histograms.observe(MyLabelGroup { operation: Operation::Delete }, 2.0);
+///
+/// // sample the histograms and encode the values to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_native_histogram");
+/// histograms.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type NativeHistogramVec<L> = MetricVec<NativeHistogramState, L>;
+
+/// A [`Histogram`] that can also estimate percentiles of the observations within a recent time window.
+///
+/// The histogram is still reported cumulatively, the window only affects [`WindowedHistogram::percentile`].
+///
+/// ```
+/// use std::time::Duration;
+///
+/// use measured::WindowedHistogram;
+/// use measured::metric::histogram::Thresholds;
+/// use measured::metric::windowed_histogram::Window;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // create a histogram with 8 buckets starting at 0.01, increasing by 2x each time up to 2.56,
+/// // that keeps the observations of the last 5 minutes, rotated every minute.
+/// let thresholds = Thresholds::<8>::exponential_buckets(0.01, 2.0);
+/// let histogram = WindowedHistogram::with_metadata(Window::new(thresholds, Duration::from_secs(60), 5));
+/// // observe a value
+/// histogram.observe(1.0);
+///
+/// // estimate the median of the recent observations
+/// let median = histogram.percentile(0.5);
+/// assert!(median > 0.64 && median <= 1.28);
+///
+/// // sample the histogram and encode the value to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_windowed_histogram");
+/// histogram.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type WindowedHistogram<const N: usize> = Metric<WindowedHistogramState<N>>;
+
+/// A collection of multiple [`WindowedHistogram`]s, keyed by [`LabelGroup`]s
+pub type WindowedHistogramVec<L, const N: usize> = MetricVec<WindowedHistogramState<N>, L>;
+
+/// A [`Metric`] that tracks the distribution of observations from an event or sample stream,
+/// reporting estimates of configured quantiles, alongside the count and sum of the observations.
+///
+/// Unlike a [`Histogram`], the quantiles are estimated by each individual summary, so they cannot be aggregated.
+///
+/// ```
+/// use measured::Summary;
+/// use measured::metric::summary::Quantiles;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // create a summary that reports the median and the 99th percentile
+/// let summary = Summary::with_metadata(Quantiles::new([(0.5, 0.05), (0.99, 0.001)]));
+/// // observe a value
+/// summary.observe(1.0);
+///
+/// // sample the summary and encode the value to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_summary");
+/// summary.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type Summary = Metric<SummaryState>;
+
+/// A collection of multiple [`Summary`]s, keyed by [`LabelGroup`]s
+///
+/// ```
+/// use measured::{SummaryVec, LabelGroup, FixedCardinalityLabel};
+/// use measured::metric::summary::Quantiles;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // Define a fixed cardinality label
+///
+/// #[derive(FixedCardinalityLabel, Copy, Clone)]
+/// enum Operation {
+///     Create,
+///     Update,
+///     Delete,
+/// }
+///
+/// // Define a label group, consisting of 1 or more label values
+///
+/// #[derive(LabelGroup)]
+/// #[label(set = MyLabelGroupSet)]
+/// struct MyLabelGroup {
+///     operation: Operation,
+/// }
+///
+/// // create a summary vec
+/// let summaries = SummaryVec::with_label_set_and_metadata(
+///     MyLabelGroupSet::new(),
+///     Quantiles::default(),
+/// );
+/// // observe a value
+/// summaries.observe(MyLabelGroup { operation: Operation::Create }, 0.5);
+/// summaries.observe(MyLabelGroup { operation: Operation::Delete }, 2.0);
+///
+/// // sample the summaries and encode the values to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_summary");
+/// summaries.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type SummaryVec<L> = MetricVec<SummaryState, L>;
+
 /// A [`Metric`] that represents a single numerical value that only ever goes up.
 ///
 /// ```
@@ -378,6 +580,68 @@ pub type Counter = Metric<CounterState>;
 /// ```
 pub type CounterVec<L> = MetricVec<CounterState, L>;
 
+/// A [`Counter`] that also keeps the most recent [`Exemplar`](metric::exemplar::Exemplar),
+/// to link the counter with an individual trace.
+///
+/// Exemplars are only encoded by encoders that support them, such as the OpenMetrics encoder.
+///
+/// ```
+/// use measured::{ExemplarCounter, LabelGroup};
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::openmetrics::OpenMetricsEncoder;
+///
+/// #[derive(LabelGroup)]
+/// #[label(set = TraceSet)]
+/// struct Trace<'a> {
+///     #[label(dynamic_with = lasso::ThreadedRodeo)]
+///     trace_id: &'a str,
+/// }
+///
+/// // create a counter
+/// let counter = ExemplarCounter::new();
+/// // increment the counter value, recording the current trace
+/// counter.inc_with_exemplar(Trace { trace_id: "4bf92f3577b34da6" });
+///
+/// // sample the counter and encode the value and exemplar to the OpenMetrics format.
+/// let mut encoder = OpenMetricsEncoder::new(Vec::new());
+/// let name = MetricName::from_str("my_first_counter");
+/// counter.collect_family_into(name, &mut encoder).unwrap();
+/// encoder.finish().unwrap();
+/// ```
+pub type ExemplarCounter = Metric<ExemplarCounterState>;
+
+/// A collection of multiple [`ExemplarCounter`]s, keyed by [`LabelGroup`]s
+pub type ExemplarCounterVec<L> = MetricVec<ExemplarCounterState, L>;
+
+/// A [`Counter`] that is split into per-thread shards, for counters that are incremented from many threads at once.
+///
+/// Each shard lives in its own cache line, so a sharded counter uses much more memory than a [`Counter`].
+/// It is encoded exactly the same as a [`Counter`].
+///
+/// ```
+/// use measured::ShardedCounter;
+/// use measured::metric::name::{MetricName, Total};
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // create a sharded counter
+/// let counter = ShardedCounter::new();
+/// // increment the counter value
+/// counter.inc();
+/// assert_eq!(counter.get(), 1);
+///
+/// // sample the counter and encode the value to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_sharded_counter").with_suffix(Total);
+/// counter.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type ShardedCounter = Metric<ShardedCounterState>;
+
+/// A collection of multiple [`ShardedCounter`]s, keyed by [`LabelGroup`]s
+pub type ShardedCounterVec<L> = MetricVec<ShardedCounterState, L>;
+
 /// A [`Metric`] that represents a single numerical value that can go up or down over time.
 ///
 /// ```
@@ -499,3 +763,97 @@ pub type FloatGauge = Metric<FloatGaugeState>;
 /// let bytes = text_encoder.finish();
 /// ```
 pub type FloatGaugeVec<L> = MetricVec<FloatGaugeState, L>;
+
+/// A [`Metric`] that exposes a set of labels with a constant value of `1`, such as the version of a build.
+///
+/// ```
+/// use measured::{Info, LabelGroup};
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // Define the info labels
+///
+/// #[derive(LabelGroup)]
+/// #[label(set = BuildInfoSet)]
+/// struct BuildInfo<'a> {
+///     #[label(dynamic_with = lasso::ThreadedRodeo)]
+///     version: &'a str,
+/// }
+///
+/// // create an info metric
+/// let info = Info::new();
+/// // set the info labels
+/// info.set_info(BuildInfo { version: env!("CARGO_PKG_VERSION") });
+///
+/// // sample the info and encode the value to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_build");
+/// info.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type Info = Metric<InfoState>;
+
+/// A collection of multiple [`Info`]s, keyed by [`LabelGroup`]s
+pub type InfoVec<L> = MetricVec<InfoState, L>;
+
+/// A [`Metric`] that represents which of a fixed set of states something is currently in.
+///
+/// Each [`FixedCardinalityLabel`] variant is sampled as a separate series, labelled with the metric name.
+/// The current state has the value `1`, and all other states have the value `0`.
+///
+/// ```
+/// use measured::{StateSet, FixedCardinalityLabel};
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // Define the possible states
+///
+/// #[derive(FixedCardinalityLabel, Copy, Clone)]
+/// enum Connection {
+///     Connecting,
+///     Connected,
+///     Disconnected,
+/// }
+///
+/// // create a state set
+/// let state = StateSet::new();
+/// // set the current state
+/// state.set(Connection::Connected);
+///
+/// // sample the state set and encode the values to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_state_set");
+/// state.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type StateSet<T> = Metric<StateSetState<T>>;
+
+/// A collection of multiple [`StateSet`]s, keyed by [`LabelGroup`]s
+pub type StateSetVec<T, L> = MetricVec<StateSetState<T>, L>;
+
+/// A [`Metric`] that represents a single numerical value with no specific type,
+/// such as a value forwarded from another monitoring system.
+///
+/// ```
+/// use measured::Untyped;
+/// use measured::metric::name::MetricName;
+/// use measured::metric::MetricFamilyEncoding;
+/// use measured::text::BufferedTextEncoder;
+///
+/// // create an untyped metric
+/// let untyped = Untyped::new();
+/// // set the value
+/// untyped.set(4.5);
+///
+/// // sample the value and encode it to a textual format.
+/// let mut text_encoder = BufferedTextEncoder::new();
+/// let name = MetricName::from_str("my_first_untyped");
+/// untyped.collect_family_into(name, &mut text_encoder);
+/// let bytes = text_encoder.finish();
+/// ```
+pub type Untyped = Metric<UntypedState>;
+
+/// A collection of multiple [`Untyped`] metrics, keyed by [`LabelGroup`]s
+pub type UntypedVec<L> = MetricVec<UntypedState, L>;
@@ -13,11 +13,19 @@ use crossbeam_utils::CachePadded;
 use self::{group::Encoding, name::MetricNameEncoder};
 
 pub mod counter;
+pub mod describe;
+pub mod exemplar;
 pub mod gauge;
 pub mod group;
 pub mod histogram;
+pub mod info;
 pub mod name;
+pub mod native_histogram;
 mod sparse;
+pub mod state_set;
+pub mod summary;
+pub mod untyped;
+pub mod windowed_histogram;
 
 /// Defines a metric
 pub trait MetricType: Default {
@@ -381,7 +389,7 @@ impl<M: MetricType, L: LabelGroupSet> MetricVec<M, L> {
     ///
     /// # Panics
     /// Can panic or cause strange behaviour if the label ID comes from a different metric family.
-    pub fn get_metric_mut(&mut self, id: LabelId<L>) -> MetricMut<M> {
+    pub fn get_metric_mut(&mut self, id: LabelId<L>) -> MetricMut<'_, M> {
         MetricMut(self.metrics.get_metric_mut(id.0), &self.metadata)
     }
 
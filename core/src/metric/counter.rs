@@ -1,12 +1,22 @@
 //! All things counters. See [`Counter`]
 
-use core::sync::atomic::AtomicU64;
+use core::{
+    cell::Cell,
+    sync::atomic::{AtomicU64, AtomicUsize, Ordering},
+};
+use std::sync::OnceLock;
+
+use crossbeam_utils::CachePadded;
+use parking_lot::Mutex;
 
-use crate::{label::LabelGroupSet, Counter, CounterVec, LabelGroup};
+use crate::{
+    label::LabelGroupSet, Counter, CounterVec, ExemplarCounter, ExemplarCounterVec, LabelGroup,
+    ShardedCounter, ShardedCounterVec,
+};
 
 use super::{
-    group::Encoding, name::MetricNameEncoder, MetricEncoding, MetricLockGuard, MetricMut,
-    MetricType,
+    exemplar::Exemplar, group::Encoding, name::MetricNameEncoder, MetricEncoding, MetricLockGuard,
+    MetricMut, MetricType,
 };
 
 #[derive(Default)]
@@ -101,6 +111,234 @@ impl MetricType for CounterState {
     type Metadata = ();
 }
 
+#[derive(Default)]
+/// The internal state that is used by [`ExemplarCounter`] and [`ExemplarCounterVec`]
+///
+/// This is kept separate from [`CounterState`] so that counters without exemplars remain a single atomic.
+pub struct ExemplarCounterState {
+    /// The current count
+    pub count: AtomicU64,
+    /// The most recently recorded exemplar
+    pub exemplar: Mutex<Option<Exemplar>>,
+}
+
+/// A reference to a specific exemplar counter.
+pub type ExemplarCounterLockGuard<'a> = MetricLockGuard<'a, ExemplarCounterState>;
+/// A mut reference to a specific exemplar counter.
+pub type ExemplarCounterMut<'a> = MetricMut<'a, ExemplarCounterState>;
+
+impl ExemplarCounterState {
+    /// Increment the counter value by 1
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(&self, x: u64) {
+        self.count
+            .fetch_add(x, core::sync::atomic::Ordering::Relaxed);
+    }
+
+    /// Increment the counter value by `x`, replacing the exemplar with the given labels
+    pub fn inc_by_with_exemplar(&self, x: u64, exemplar: impl LabelGroup) {
+        self.inc_by(x);
+        *self.exemplar.lock() = Some(Exemplar::new(exemplar, x as f64));
+    }
+}
+
+impl ExemplarCounterMut<'_> {
+    /// Increment the counter value by 1
+    pub fn inc(mut self) {
+        *self.count.get_mut() += 1;
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(mut self, x: u64) {
+        *self.count.get_mut() += x;
+    }
+
+    /// Increment the counter value by `x`, replacing the exemplar with the given labels
+    pub fn inc_by_with_exemplar(mut self, x: u64, exemplar: impl LabelGroup) {
+        *self.count.get_mut() += x;
+        *self.exemplar.get_mut() = Some(Exemplar::new(exemplar, x as f64));
+    }
+}
+
+impl<L: LabelGroupSet> ExemplarCounterVec<L> {
+    /// Increment the counter value by 1, keyed by the label group
+    pub fn inc(&self, label: L::Group<'_>) {
+        self.get_metric(self.with_labels(label)).inc();
+    }
+
+    /// Increment the counter value by `y`, keyed by the label group
+    pub fn inc_by(&self, label: L::Group<'_>, y: u64) {
+        self.get_metric(self.with_labels(label)).inc_by(y);
+    }
+
+    /// Increment the counter value by 1, keyed by the label group, replacing the exemplar with the given labels
+    pub fn inc_with_exemplar(&self, label: L::Group<'_>, exemplar: impl LabelGroup) {
+        self.inc_by_with_exemplar(label, 1, exemplar);
+    }
+
+    /// Increment the counter value by `y`, keyed by the label group, replacing the exemplar with the given labels
+    pub fn inc_by_with_exemplar(&self, label: L::Group<'_>, y: u64, exemplar: impl LabelGroup) {
+        self.get_metric(self.with_labels(label))
+            .inc_by_with_exemplar(y, exemplar);
+    }
+}
+
+impl ExemplarCounter {
+    /// Increment the counter value by 1
+    pub fn inc(&self) {
+        self.get_metric().inc()
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(&self, x: u64) {
+        self.get_metric().inc_by(x)
+    }
+
+    /// Increment the counter value by 1, replacing the exemplar with the given labels
+    pub fn inc_with_exemplar(&self, exemplar: impl LabelGroup) {
+        self.get_metric().inc_by_with_exemplar(1, exemplar)
+    }
+
+    /// Increment the counter value by `x`, replacing the exemplar with the given labels
+    pub fn inc_by_with_exemplar(&self, x: u64, exemplar: impl LabelGroup) {
+        self.get_metric().inc_by_with_exemplar(x, exemplar)
+    }
+}
+
+impl MetricType for ExemplarCounterState {
+    /// [`ExemplarCounter`]s require no additional metadata
+    type Metadata = ();
+}
+
+/// The internal state that is used by [`ShardedCounter`] and [`ShardedCounterVec`]
+///
+/// The count is split over a number of cache padded shards, and each thread increments its own shard.
+/// This avoids contention on a single cache line when a counter is incremented from many threads,
+/// at the cost of using much more memory per counter. The shards are summed when the counter is sampled.
+pub struct ShardedCounterState {
+    /// One count per shard. The number of shards is always a power of 2
+    shards: Box<[CachePadded<AtomicU64>]>,
+}
+
+impl Default for ShardedCounterState {
+    fn default() -> Self {
+        Self {
+            shards: (0..shard_amount())
+                .map(|_| CachePadded::new(AtomicU64::new(0)))
+                .collect(),
+        }
+    }
+}
+
+/// The number of shards, the number of CPU cores rounded up to the next power of 2
+fn shard_amount() -> usize {
+    static SHARD_AMOUNT: OnceLock<usize> = OnceLock::new();
+    *SHARD_AMOUNT.get_or_init(|| {
+        std::thread::available_parallelism()
+            .map_or(1, usize::from)
+            .next_power_of_two()
+    })
+}
+
+/// Each thread is assigned a shard index in a round-robin fashion
+fn thread_shard() -> usize {
+    static NEXT_SHARD: AtomicUsize = AtomicUsize::new(0);
+    thread_local! {
+        static SHARD: Cell<Option<usize>> = const { Cell::new(None) };
+    }
+    SHARD.with(|shard| {
+        shard.get().unwrap_or_else(|| {
+            let s = NEXT_SHARD.fetch_add(1, Ordering::Relaxed);
+            shard.set(Some(s));
+            s
+        })
+    })
+}
+
+/// A reference to a specific sharded counter.
+pub type ShardedCounterLockGuard<'a> = MetricLockGuard<'a, ShardedCounterState>;
+/// A mut reference to a specific sharded counter.
+pub type ShardedCounterMut<'a> = MetricMut<'a, ShardedCounterState>;
+
+impl ShardedCounterState {
+    /// Create a new sharded counter, starting at the given value
+    pub fn new(value: u64) -> Self {
+        let state = Self::default();
+        state.shards[0].store(value, Ordering::Relaxed);
+        state
+    }
+
+    /// Increment the counter value by 1
+    pub fn inc(&self) {
+        self.inc_by(1);
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(&self, x: u64) {
+        // the number of shards is always a power of 2
+        let shard = thread_shard() & (self.shards.len() - 1);
+        self.shards[shard].fetch_add(x, Ordering::Relaxed);
+    }
+
+    /// Sum the counter value across all shards
+    pub fn get(&self) -> u64 {
+        self.shards
+            .iter()
+            .map(|s| s.load(Ordering::Relaxed))
+            .fold(0, u64::wrapping_add)
+    }
+}
+
+impl ShardedCounterMut<'_> {
+    /// Increment the counter value by 1
+    pub fn inc(mut self) {
+        *self.shards[0].get_mut() += 1;
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(mut self, x: u64) {
+        *self.shards[0].get_mut() += x;
+    }
+}
+
+impl<L: LabelGroupSet> ShardedCounterVec<L> {
+    /// Increment the counter value by 1, keyed by the label group
+    pub fn inc(&self, label: L::Group<'_>) {
+        self.get_metric(self.with_labels(label)).inc();
+    }
+
+    /// Increment the counter value by `y`, keyed by the label group
+    pub fn inc_by(&self, label: L::Group<'_>, y: u64) {
+        self.get_metric(self.with_labels(label)).inc_by(y);
+    }
+}
+
+impl ShardedCounter {
+    /// Increment the counter value by 1
+    pub fn inc(&self) {
+        self.get_metric().inc()
+    }
+
+    /// Increment the counter value by `x`
+    pub fn inc_by(&self, x: u64) {
+        self.get_metric().inc_by(x)
+    }
+
+    /// Sum the counter value across all shards
+    pub fn get(&self) -> u64 {
+        self.get_metric().get()
+    }
+}
+
+impl MetricType for ShardedCounterState {
+    /// [`ShardedCounter`]s require no additional metadata
+    type Metadata = ();
+}
+
 pub fn write_counter<Enc: Encoding>(
     enc: &mut Enc,
     name: impl MetricNameEncoder,
@@ -115,3 +353,26 @@ where
     }
     .collect_into(&(), labels, name, enc)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ShardedCounterState;
+
+    #[test]
+    fn sharded_counter_sums_shards() {
+        let counter = ShardedCounterState::new(5);
+
+        std::thread::scope(|s| {
+            for _ in 0..8 {
+                s.spawn(|| {
+                    for _ in 0..1000 {
+                        counter.inc();
+                    }
+                    counter.inc_by(10);
+                });
+            }
+        });
+
+        assert_eq!(counter.get(), 5 + 8 * 1010);
+    }
+}
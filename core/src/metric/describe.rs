@@ -0,0 +1,409 @@
+//! A static catalogue of the metric families in a [`MetricGroup`](super::group::MetricGroup).
+//!
+//! `#[derive(MetricGroup)]` also implements [`MetricGroupDescribe`], which lists every metric family
+//! without collecting any values. This is useful for generating documentation, or for linting dashboards and alerts
+//! against the metrics an application actually exports.
+//!
+//! ```
+//! use measured::{
+//!     metric::{describe::MetricGroupDescribe, histogram::Thresholds},
+//!     text::MetricType,
+//!     Counter, Histogram, MetricGroup,
+//! };
+//!
+//! #[derive(MetricGroup)]
+//! #[metric(new())]
+//! struct Http {
+//!     /// number of requests
+//!     requests_total: Counter,
+//!     /// request latency
+//!     #[metric(metadata = Thresholds::with_buckets([0.1, 1.0]))]
+//!     latency_seconds: Histogram<2>,
+//! }
+//!
+//! #[derive(MetricGroup)]
+//! #[metric(new())]
+//! struct App {
+//!     #[metric(namespace = "http")]
+//!     #[metric(init = Http::new())]
+//!     http: Http,
+//! }
+//!
+//! let catalogue = App::new().describe();
+//! assert_eq!(catalogue[0].name, "http_requests_total");
+//! assert_eq!(catalogue[0].metric_type, MetricType::Counter);
+//! assert_eq!(catalogue[0].help.as_deref(), Some("number of requests"));
+//! assert_eq!(catalogue[1].name, "http_latency_seconds");
+//! assert_eq!(catalogue[1].buckets, [0.1, 1.0]);
+//! ```
+
+use std::sync::Arc;
+
+use crate::{
+    label::{ComposedGroup, LabelGroupNames, LabelGroupSet},
+    metric::name::Unit,
+    text::{
+        federate::{FederatedCounterState, FederatedHistogramState, FederatedSummaryState},
+        MetricType as Type,
+    },
+};
+
+use super::{
+    counter::{CounterState, ExemplarCounterState, ShardedCounterState},
+    gauge::{FloatGaugeState, GaugeState},
+    histogram::{ExemplarHistogramState, HistogramState},
+    info::InfoState,
+    native_histogram::NativeHistogramState,
+    state_set::StateSetState,
+    summary::SummaryState,
+    untyped::UntypedState,
+    windowed_histogram::WindowedHistogramState,
+    Metric, MetricType, MetricVec,
+};
+
+/// The description of a single metric family
+#[derive(Debug, Clone, PartialEq)]
+pub struct MetricDescription {
+    /// The full name of the metric family, including any namespaces
+    pub name: String,
+    /// The type of the metric family
+    pub metric_type: Type,
+    /// The help text of the metric family
+    pub help: Option<String>,
+    /// The unit of the metric family
+    pub unit: Option<Unit>,
+    /// The names of the labels on the metric family.
+    ///
+    /// This does not include labels that the metric type adds itself, such as `le` for histograms.
+    pub label_names: Vec<String>,
+    /// The upper bounds of the histogram buckets. Empty for other metric types.
+    pub buckets: Vec<f64>,
+}
+
+/// Describes the type of an individual metric
+pub trait MetricTypeDescribe: MetricType {
+    /// The type of metric family this metric is written as
+    const METRIC_TYPE: Type;
+
+    /// The upper bounds of the histogram buckets, if there are any
+    fn buckets(_metadata: &Self::Metadata) -> Vec<f64> {
+        vec![]
+    }
+}
+
+/// Describes a single [`Metric`] or [`MetricVec`]
+pub trait MetricFamilyDescribe {
+    /// Add the description of this metric family with the given name, help text and unit
+    fn describe_family_into(
+        &self,
+        name: String,
+        help: Option<&str>,
+        unit: Option<Unit>,
+        out: &mut Vec<MetricDescription>,
+    );
+}
+
+/// Describes all the metric families in a group, without collecting any values.
+///
+/// Implemented by `#[derive(MetricGroup)]`.
+pub trait MetricGroupDescribe {
+    /// Add the descriptions of all metric families in this group, prefixing their names with the namespace
+    fn describe_group_into(&self, namespace: Option<&str>, out: &mut Vec<MetricDescription>);
+
+    /// Describe all the metric families in this group
+    fn describe(&self) -> Vec<MetricDescription> {
+        let mut out = vec![];
+        self.describe_group_into(None, &mut out);
+        out
+    }
+}
+
+/// Join a name onto an optional namespace, the same way [`WithNamespace`](super::name::WithNamespace) does
+pub fn with_namespace(namespace: Option<&str>, name: &str) -> String {
+    match namespace {
+        Some(namespace) => format!("{namespace}_{name}"),
+        None => name.to_owned(),
+    }
+}
+
+impl<M: MetricTypeDescribe> MetricFamilyDescribe for Metric<M> {
+    fn describe_family_into(
+        &self,
+        name: String,
+        help: Option<&str>,
+        unit: Option<Unit>,
+        out: &mut Vec<MetricDescription>,
+    ) {
+        out.push(MetricDescription {
+            name,
+            metric_type: M::METRIC_TYPE,
+            help: help.map(str::to_owned),
+            unit,
+            label_names: vec![],
+            buckets: M::buckets(&self.metadata),
+        });
+    }
+}
+
+impl<M: MetricTypeDescribe, L: LabelGroupSet + LabelGroupNames> MetricFamilyDescribe
+    for MetricVec<M, L>
+{
+    fn describe_family_into(
+        &self,
+        name: String,
+        help: Option<&str>,
+        unit: Option<Unit>,
+        out: &mut Vec<MetricDescription>,
+    ) {
+        let mut label_names = vec![];
+        self.label_set
+            .visit_names(&mut |name| label_names.push(name.as_str().to_owned()));
+
+        out.push(MetricDescription {
+            name,
+            metric_type: M::METRIC_TYPE,
+            help: help.map(str::to_owned),
+            unit,
+            label_names,
+            buckets: M::buckets(&self.metadata),
+        });
+    }
+}
+
+impl<M: MetricFamilyDescribe> MetricFamilyDescribe for Option<M> {
+    fn describe_family_into(
+        &self,
+        name: String,
+        help: Option<&str>,
+        unit: Option<Unit>,
+        out: &mut Vec<MetricDescription>,
+    ) {
+        if let Some(this) = self {
+            this.describe_family_into(name, help, unit, out);
+        }
+    }
+}
+
+impl<G: MetricGroupDescribe + ?Sized> MetricGroupDescribe for &G {
+    fn describe_group_into(&self, namespace: Option<&str>, out: &mut Vec<MetricDescription>) {
+        G::describe_group_into(self, namespace, out);
+    }
+}
+
+impl<G: MetricGroupDescribe + ?Sized> MetricGroupDescribe for Arc<G> {
+    fn describe_group_into(&self, namespace: Option<&str>, out: &mut Vec<MetricDescription>) {
+        G::describe_group_into(self, namespace, out);
+    }
+}
+
+impl<G: MetricGroupDescribe> MetricGroupDescribe for Option<G> {
+    fn describe_group_into(&self, namespace: Option<&str>, out: &mut Vec<MetricDescription>) {
+        if let Some(this) = self {
+            this.describe_group_into(namespace, out);
+        }
+    }
+}
+
+impl<A: MetricGroupDescribe, B: MetricGroupDescribe> MetricGroupDescribe for ComposedGroup<A, B> {
+    fn describe_group_into(&self, namespace: Option<&str>, out: &mut Vec<MetricDescription>) {
+        self.0.describe_group_into(namespace, out);
+        self.1.describe_group_into(namespace, out);
+    }
+}
+
+impl MetricTypeDescribe for CounterState {
+    const METRIC_TYPE: Type = Type::Counter;
+}
+
+impl MetricTypeDescribe for ExemplarCounterState {
+    const METRIC_TYPE: Type = Type::Counter;
+}
+
+impl MetricTypeDescribe for ShardedCounterState {
+    const METRIC_TYPE: Type = Type::Counter;
+}
+
+impl MetricTypeDescribe for GaugeState {
+    const METRIC_TYPE: Type = Type::Gauge;
+}
+
+impl MetricTypeDescribe for FloatGaugeState {
+    const METRIC_TYPE: Type = Type::Gauge;
+}
+
+impl<const N: usize> MetricTypeDescribe for HistogramState<N> {
+    const METRIC_TYPE: Type = Type::Histogram;
+    fn buckets(metadata: &Self::Metadata) -> Vec<f64> {
+        metadata.get().to_vec()
+    }
+}
+
+impl<const N: usize> MetricTypeDescribe for ExemplarHistogramState<N> {
+    const METRIC_TYPE: Type = Type::Histogram;
+    fn buckets(metadata: &Self::Metadata) -> Vec<f64> {
+        metadata.get().to_vec()
+    }
+}
+
+impl<const N: usize> MetricTypeDescribe for WindowedHistogramState<N> {
+    const METRIC_TYPE: Type = Type::Histogram;
+    fn buckets(metadata: &Self::Metadata) -> Vec<f64> {
+        metadata.thresholds().get().to_vec()
+    }
+}
+
+impl MetricTypeDescribe for NativeHistogramState {
+    const METRIC_TYPE: Type = Type::Histogram;
+}
+
+impl MetricTypeDescribe for SummaryState {
+    const METRIC_TYPE: Type = Type::Summary;
+}
+
+impl MetricTypeDescribe for UntypedState {
+    const METRIC_TYPE: Type = Type::Untyped;
+}
+
+impl MetricTypeDescribe for InfoState {
+    const METRIC_TYPE: Type = Type::Info;
+}
+
+impl<T: crate::FixedCardinalityLabel> MetricTypeDescribe for StateSetState<T> {
+    const METRIC_TYPE: Type = Type::StateSet;
+}
+
+impl MetricTypeDescribe for FederatedCounterState {
+    const METRIC_TYPE: Type = Type::Counter;
+}
+
+impl MetricTypeDescribe for FederatedHistogramState {
+    const METRIC_TYPE: Type = Type::Histogram;
+}
+
+impl MetricTypeDescribe for FederatedSummaryState {
+    const METRIC_TYPE: Type = Type::Summary;
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        metric::histogram::Thresholds, text::MetricType, Counter, CounterVec,
+        FixedCardinalityLabel, Gauge, HistogramVec, LabelGroup, MetricGroup,
+    };
+
+    use super::{MetricDescription, MetricGroupDescribe, Unit};
+
+    #[derive(FixedCardinalityLabel, Clone, Copy)]
+    #[label(crate = crate)]
+    enum Method {
+        Get,
+        Post,
+    }
+
+    #[derive(LabelGroup)]
+    #[label(crate = crate, set = RequestSet)]
+    struct Request {
+        method: Method,
+        #[label(rename = "status_code")]
+        status: Method,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate, new())]
+    struct Http {
+        /// number of requests
+        requests_total: CounterVec<RequestSet>,
+        /// request latency
+        #[metric(unit = "seconds")]
+        #[metric(metadata = Thresholds::with_buckets([0.1, 1.0]))]
+        latency: HistogramVec<RequestSet, 2>,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate, new())]
+    struct Inner {
+        #[metric(namespace = "http")]
+        #[metric(init = Http::new())]
+        http: Http,
+        in_flight: Gauge,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate, new())]
+    struct App {
+        #[metric(namespace = "api")]
+        #[metric(init = Inner::new())]
+        inner: Inner,
+        #[metric(flatten)]
+        #[metric(init = Inner::new())]
+        other: Inner,
+        /// number of events
+        #[metric(rename = "events")]
+        events_total: Counter,
+    }
+
+    #[test]
+    fn describe() {
+        let describe =
+            |name: &str, metric_type, help: Option<&str>, labels: &[&str], buckets: &[f64]| {
+                MetricDescription {
+                    name: name.to_owned(),
+                    metric_type,
+                    help: help.map(str::to_owned),
+                    unit: None,
+                    label_names: labels.iter().map(|&l| l.to_owned()).collect(),
+                    buckets: buckets.to_vec(),
+                }
+            };
+        let labels = &["method", "status_code"];
+
+        assert_eq!(
+            App::new().describe(),
+            [
+                describe(
+                    "api_http_requests_total",
+                    MetricType::Counter,
+                    Some("number of requests"),
+                    labels,
+                    &[]
+                ),
+                MetricDescription {
+                    unit: Some(Unit::SECONDS),
+                    ..describe(
+                        "api_http_latency_seconds",
+                        MetricType::Histogram,
+                        Some("request latency"),
+                        labels,
+                        &[0.1, 1.0],
+                    )
+                },
+                describe("api_in_flight", MetricType::Gauge, None, &[], &[]),
+                describe(
+                    "http_requests_total",
+                    MetricType::Counter,
+                    Some("number of requests"),
+                    labels,
+                    &[]
+                ),
+                MetricDescription {
+                    unit: Some(Unit::SECONDS),
+                    ..describe(
+                        "http_latency_seconds",
+                        MetricType::Histogram,
+                        Some("request latency"),
+                        labels,
+                        &[0.1, 1.0],
+                    )
+                },
+                describe("in_flight", MetricType::Gauge, None, &[], &[]),
+                describe(
+                    "events",
+                    MetricType::Counter,
+                    Some("number of events"),
+                    &[],
+                    &[]
+                ),
+            ]
+        );
+    }
+}
@@ -0,0 +1,153 @@
+//! Exemplars link individual observations to external data, such as the trace that produced them.
+
+use std::time::SystemTime;
+
+use crate::label::{LabelGroup, LabelGroupVisitor, LabelName, LabelValue, LabelVisitor};
+
+/// An `Exemplar` is a single observation, recorded with its own set of labels and the time it was observed.
+///
+/// The labels are usually used to hold trace ids, eg `trace_id="4bf92f3577b34da6"`.
+/// Since exemplars are stored until they are replaced, the labels are copied out of the
+/// [`LabelGroup`] that they were recorded with.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Exemplar {
+    labels: OwnedLabels,
+    value: f64,
+    timestamp: SystemTime,
+}
+
+impl Exemplar {
+    /// Record a new exemplar, observed now.
+    pub fn new(labels: impl LabelGroup, value: f64) -> Self {
+        Self::with_timestamp(labels, value, SystemTime::now())
+    }
+
+    /// Record a new exemplar, observed at the given time.
+    pub fn with_timestamp(labels: impl LabelGroup, value: f64, timestamp: SystemTime) -> Self {
+        Self {
+            labels: copy_labels(labels),
+            value,
+            timestamp,
+        }
+    }
+
+    /// The observed value
+    pub fn value(&self) -> f64 {
+        self.value
+    }
+
+    /// The time the value was observed
+    pub fn timestamp(&self) -> SystemTime {
+        self.timestamp
+    }
+}
+
+/// Label names and values, copied out of a [`LabelGroup`].
+pub(crate) type OwnedLabels = Box<[(Box<str>, Box<str>)]>;
+
+/// Copy the label names and values out of a [`LabelGroup`], so they can be stored.
+pub(crate) fn copy_labels(labels: impl LabelGroup) -> OwnedLabels {
+    struct Visitor(Vec<(Box<str>, Box<str>)>);
+    impl LabelGroupVisitor for Visitor {
+        type Output = ();
+        fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+            let value = x.visit(ValueVisitor);
+            self.0.push((name.as_str().into(), value));
+        }
+    }
+
+    struct ValueVisitor;
+    impl LabelVisitor for ValueVisitor {
+        type Output = Box<str>;
+        fn write_int(self, x: i64) -> Box<str> {
+            itoa::Buffer::new().format(x).into()
+        }
+        fn write_float(self, x: f64) -> Box<str> {
+            if x.is_infinite() {
+                if x.is_sign_positive() {
+                    "+Inf".into()
+                } else {
+                    "-Inf".into()
+                }
+            } else if x.is_nan() {
+                "NaN".into()
+            } else {
+                ryu::Buffer::new().format(x).into()
+            }
+        }
+        fn write_str(self, x: &str) -> Box<str> {
+            x.into()
+        }
+    }
+
+    let mut visitor = Visitor(Vec::new());
+    labels.visit_values(&mut visitor);
+    visitor.0.into_boxed_slice()
+}
+
+/// The exemplar labels
+impl LabelGroup for Exemplar {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        for (name, value) in &*self.labels {
+            v.write_value(LabelName::from_str(name), &&**value);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use crate::label::{LabelGroup, LabelGroupVisitor, LabelName, LabelTestVisitor, LabelValue};
+
+    use super::Exemplar;
+
+    struct Trace<'a> {
+        trace_id: &'a str,
+        sampled: Sampled,
+    }
+
+    impl LabelGroup for Trace<'_> {
+        fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+            const TRACE_ID: &LabelName = LabelName::from_str("trace_id");
+            const SAMPLED: &LabelName = LabelName::from_str("sampled");
+            v.write_value(TRACE_ID, &self.trace_id);
+            v.write_value(SAMPLED, &self.sampled);
+        }
+    }
+
+    #[derive(Clone, Copy, measured_derive::FixedCardinalityLabel)]
+    #[label(crate = crate, rename_all = "snake_case")]
+    enum Sampled {
+        Yes,
+    }
+
+    #[test]
+    fn copies_labels() {
+        struct Visitor(Vec<String>);
+        impl LabelGroupVisitor for Visitor {
+            type Output = ();
+            fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+                self.0
+                    .push(format!("{}={}", name.as_str(), x.visit(LabelTestVisitor)));
+            }
+        }
+
+        let trace_id = String::from("4bf92f3577b34da6");
+        let exemplar = Exemplar::with_timestamp(
+            Trace {
+                trace_id: &trace_id,
+                sampled: Sampled::Yes,
+            },
+            0.25,
+            SystemTime::UNIX_EPOCH,
+        );
+        drop(trace_id);
+
+        let mut visitor = Visitor(vec![]);
+        exemplar.visit_values(&mut visitor);
+        assert_eq!(visitor.0, ["trace_id=4bf92f3577b34da6", "sampled=yes"]);
+        assert_eq!(exemplar.value(), 0.25);
+        assert_eq!(exemplar.timestamp(), SystemTime::UNIX_EPOCH);
+    }
+}
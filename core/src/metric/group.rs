@@ -5,7 +5,7 @@ use std::sync::Arc;
 pub use crate::label::ComposedGroup;
 
 use super::{
-    name::{MetricNameEncoder, WithNamespace},
+    name::{MetricNameEncoder, Unit, WithNamespace},
     MetricEncoding,
 };
 
@@ -23,6 +23,14 @@ pub trait Encoding {
 
     /// Write the help text for a metric
     fn write_help(&mut self, name: impl MetricNameEncoder, help: &str) -> Result<(), Self::Err>;
+
+    /// Write the unit of a metric. The metric name must end with the unit.
+    ///
+    /// Only some formats support units, so this does nothing by default.
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Self::Err> {
+        let _ = (name, unit);
+        Ok(())
+    }
 }
 
 impl<E: Encoding> Encoding for &mut E {
@@ -30,6 +38,9 @@ impl<E: Encoding> Encoding for &mut E {
     fn write_help(&mut self, name: impl MetricNameEncoder, help: &str) -> Result<(), Self::Err> {
         E::write_help(self, name, help)
     }
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Self::Err> {
+        E::write_unit(self, name, unit)
+    }
 }
 
 /// A `MetricGroup` defines a group of [`MetricFamilyEncoding`](super::MetricFamilyEncoding)s
@@ -100,6 +111,15 @@ impl<E: Encoding> Encoding for WithNamespace<E> {
             help,
         )
     }
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Self::Err> {
+        self.inner.write_unit(
+            WithNamespace {
+                namespace: self.namespace,
+                inner: name,
+            },
+            unit,
+        )
+    }
 }
 
 impl<M: MetricEncoding<E>, E: Encoding> MetricEncoding<WithNamespace<E>> for M {
@@ -154,7 +174,7 @@ mod tests {
 
     use crate::{
         metric::histogram::Thresholds, text::BufferedTextEncoder, Counter, CounterVec, Gauge,
-        Histogram,
+        Histogram, Info, StateSet, Untyped,
     };
 
     use super::MetricGroup;
@@ -276,6 +296,64 @@ http_request_errors{kind="network",route="/api/v1/products"} 0
 http_request_errors{kind="network",route="/api/v1/products/:id"} 0
 http_request_errors{kind="network",route="/api/v1/products/:id/owner"} 0
 http_request_errors{kind="network",route="/api/v1/products/:id/purchase"} 0
+"#
+        );
+    }
+
+    #[derive(LabelGroup)]
+    #[label(crate = crate, set = BuildInfoSet)]
+    struct BuildInfo<'a> {
+        #[label(dynamic_with = lasso::ThreadedRodeo)]
+        version: &'a str,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, FixedCardinalityLabel)]
+    #[label(crate = crate, rename_all = "snake_case")]
+    enum Connection {
+        Connecting,
+        Connected,
+        Disconnected,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    #[metric(new())]
+    struct StatusMetrics {
+        /// build information
+        build: Info,
+
+        /// the state of the upstream connection
+        upstream: StateSet<Connection>,
+
+        /// a value forwarded from elsewhere
+        forwarded: Untyped,
+    }
+
+    #[test]
+    fn info_state_set_untyped() {
+        let group = StatusMetrics::new();
+
+        group.build.set_info(BuildInfo { version: "1.2.3" });
+        group.upstream.set(Connection::Connected);
+        group.forwarded.set(2.5);
+
+        let mut text_encoder = BufferedTextEncoder::new();
+        group.collect_group_into(&mut text_encoder).unwrap();
+        assert_eq!(
+            text_encoder.finish(),
+            r#"# HELP build_info build information
+# TYPE build_info gauge
+build_info{version="1.2.3"} 1
+
+# HELP upstream the state of the upstream connection
+# TYPE upstream gauge
+upstream{upstream="connecting"} 0
+upstream{upstream="connected"} 1
+upstream{upstream="disconnected"} 0
+
+# HELP forwarded a value forwarded from elsewhere
+# TYPE forwarded untyped
+forwarded 2.5
 "#
         );
     }
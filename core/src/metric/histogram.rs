@@ -5,10 +5,18 @@ use std::{
     time::Duration,
 };
 
-use parking_lot::RwLock;
+use parking_lot::Mutex;
 
-use super::{gauge::AtomicF64, MetricLockGuard, MetricMut, MetricType};
-use crate::{label::LabelGroupSet, Histogram, HistogramVec};
+use super::{
+    exemplar::Exemplar,
+    gauge::AtomicF64,
+    name::{duration_in, duration_unit_matches, Unit, UnitMetadata},
+    MetricLockGuard, MetricMut, MetricType,
+};
+use crate::{
+    label::{LabelGroup, LabelGroupSet},
+    ExemplarHistogram, ExemplarHistogramVec, Histogram, HistogramVec,
+};
 
 /// The inner state of a histogram.
 ///
@@ -22,6 +30,8 @@ pub struct HistogramStateInner<const N: usize> {
     pub inf: AtomicU64,
     /// The accumulated sum
     pub sum: AtomicF64,
+    /// The number of completed observations
+    pub count: AtomicU64,
 }
 
 impl<const N: usize> HistogramStateInner<N> {
@@ -34,6 +44,8 @@ impl<const N: usize> HistogramStateInner<N> {
             self.inf.fetch_add(1, Ordering::Relaxed);
         }
         self.sum.inc_by(x);
+        // release the bucket and sum updates to the sampler
+        self.count.fetch_add(1, Ordering::Release);
     }
 
     /// Add a single observation to the [`Histogram`].
@@ -46,24 +58,139 @@ impl<const N: usize> HistogramStateInner<N> {
         }
         let v = self.sum.get_ex();
         self.sum.set_mut(v + x);
+        *self.count.get_mut() += 1;
+    }
+
+    /// Read the bucket counts, the `+Inf` count and the accumulated sum, and reset them to zero.
+    ///
+    /// This must only be called when there are no concurrent observations.
+    fn take(&self) -> ([u64; N], u64, f64) {
+        let buckets = core::array::from_fn(|i| self.buckets[i].swap(0, Ordering::Relaxed));
+        let inf = self.inf.swap(0, Ordering::Relaxed);
+        let sum = self.sum.get();
+        self.sum.set(0.0);
+        self.count.store(0, Ordering::Relaxed);
+        (buckets, inf, sum)
     }
 
-    pub(crate) fn sample(&mut self) -> ([u64; N], u64, f64) {
-        let mut output = [0; N];
-        #[allow(clippy::needless_range_loop)]
-        for i in 0..N {
-            output[i] = *self.buckets[i].get_mut();
+    /// Add the bucket counts, `+Inf` count, sum and number of observations into this state.
+    fn add(&self, (buckets, inf, sum): &([u64; N], u64, f64), count: u64) {
+        for (b, n) in self.buckets.iter().zip(buckets) {
+            b.fetch_add(*n, Ordering::Relaxed);
         }
-        (output, *self.inf.get_mut(), self.sum.get_ex())
+        self.inf.fetch_add(*inf, Ordering::Relaxed);
+        self.sum.inc_by(*sum);
+        self.count.fetch_add(count, Ordering::Release);
     }
 }
 
+/// A point-in-time copy of a [`Histogram`]. See [`Histogram::snapshot`]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct HistogramSnapshot<const N: usize> {
+    /// The upper bounds of each bucket
+    pub le: [f64; N],
+    /// The number of observed values in each bucket. These are not cumulative.
+    pub buckets: [u64; N],
+    /// The number of observed values that are greater than the highest bucket
+    pub inf: u64,
+    /// The accumulated sum
+    pub sum: f64,
+    /// The total number of observed values
+    pub count: u64,
+}
+
+impl<const N: usize> HistogramSnapshot<N> {
+    fn new(thresholds: &Thresholds<N>, (buckets, inf, sum): ([u64; N], u64, f64)) -> Self {
+        Self {
+            le: thresholds.le,
+            buckets,
+            inf,
+            sum,
+            count: buckets.iter().sum::<u64>() + inf,
+        }
+    }
+
+    /// Estimate the `q`-quantile of the observations, where `q` is within `0..=1`.
+    ///
+    /// The estimate is linearly interpolated within the bucket that the quantile falls into,
+    /// the same as the prometheus `histogram_quantile` function.
+    /// * If there are no observations, the quantile is `NaN`.
+    /// * If the quantile falls into the `+Inf` bucket, the upper bound of the highest bucket is returned.
+    /// * If the lowest bucket has a positive upper bound, its lower bound is assumed to be `0`.
+    pub fn quantile(&self, q: f64) -> f64 {
+        Thresholds {
+            le: self.le,
+            unit: None,
+        }
+        .quantile(&self.buckets, self.inf, q)
+    }
+}
+
+/// The top bit of [`HistogramState::count_and_hot`]
+const HOT_BIT: u64 = 1 << 63;
+
 /// The state of a histogram. See also [`HistogramStateInner`]
+///
+/// Observations do not take any locks. There are two copies of the inner state, a 'hot' one and a 'cold' one.
+/// Observations are always recorded into the hot state. When sampling, the two are swapped.
+/// Once all in-flight observations into the now-cold state have completed, it holds a consistent view of the histogram.
+/// It is then read, merged into the new hot state and reset.
+///
+/// Keeping two copies of the inner state doubles the memory of each histogram.
 pub struct HistogramState<const N: usize> {
-    /// A rwlock over the inner histogram state.
-    /// The read lock is acquired for observations.
-    /// The write lock is acquired for sampling.
-    pub inner: RwLock<HistogramStateInner<N>>,
+    /// The lower 63 bits count the number of observations that have started.
+    /// The top bit is the index of the hot state.
+    count_and_hot: AtomicU64,
+    inner: [HistogramStateInner<N>; 2],
+    /// Only one sample can swap the hot and cold states at a time.
+    sample_lock: Mutex<()>,
+}
+
+impl<const N: usize> HistogramState<N> {
+    /// Add a single observation into the given bucket, where bucket `N` is the `+Inf` bucket.
+    ///
+    /// # Panics
+    /// Will panic if the bucket is greater than `N`
+    pub fn observe(&self, bucket: usize, x: f64) {
+        // an observation that is started must complete, or sampling would wait for it forever
+        assert!(bucket <= N);
+        let n = self.count_and_hot.fetch_add(1, Ordering::Acquire);
+        self.inner[(n >> 63) as usize].observe(bucket, x);
+    }
+
+    /// Add a single observation into the given bucket, where bucket `N` is the `+Inf` bucket.
+    ///
+    /// # Panics
+    /// Will panic if the bucket is greater than `N`
+    pub fn observe_mut(&mut self, bucket: usize, x: f64) {
+        assert!(bucket <= N);
+        let n = self.count_and_hot.get_mut();
+        let hot = (*n >> 63) as usize;
+        *n += 1;
+        self.inner[hot].observe_mut(bucket, x);
+    }
+
+    /// Read the current bucket counts, the `+Inf` count and the accumulated sum.
+    ///
+    /// This waits for any in-flight observations to complete, so that the counts and the sum are consistent.
+    pub fn sample(&self) -> ([u64; N], u64, f64) {
+        let _guard = self.sample_lock.lock();
+
+        // swap the hot and cold states. new observations will go into the new hot state
+        let n = self.count_and_hot.fetch_add(HOT_BIT, Ordering::AcqRel);
+        let started = n & !HOT_BIT;
+        let cold = &self.inner[(n >> 63) as usize];
+        let hot = &self.inner[(!n >> 63) as usize];
+
+        // wait for the in-flight observations into the cold state
+        while cold.count.load(Ordering::Acquire) != started {
+            std::thread::yield_now();
+        }
+
+        let sample = cold.take();
+        hot.add(&sample, started);
+        sample
+    }
 }
 
 /// A shared ref to an individual histogram
@@ -71,16 +198,28 @@ pub type HistogramLockGuard<'a, const N: usize> = MetricLockGuard<'a, HistogramS
 /// A unique ref to an individual histogram
 pub type HistogramMut<'a, const N: usize> = MetricMut<'a, HistogramState<N>>;
 
-impl<const N: usize> Default for HistogramState<N> {
+impl<const N: usize> Default for HistogramStateInner<N> {
     fn default() -> Self {
         #[allow(clippy::declare_interior_mutable_const)]
         const ZERO: AtomicU64 = AtomicU64::new(0);
         Self {
-            inner: RwLock::new(HistogramStateInner {
-                buckets: [ZERO; N],
-                inf: ZERO,
-                sum: AtomicF64::ZERO,
-            }),
+            buckets: [ZERO; N],
+            inf: ZERO,
+            sum: AtomicF64::ZERO,
+            count: ZERO,
+        }
+    }
+}
+
+impl<const N: usize> Default for HistogramState<N> {
+    fn default() -> Self {
+        Self {
+            count_and_hot: AtomicU64::new(0),
+            inner: [
+                HistogramStateInner::default(),
+                HistogramStateInner::default(),
+            ],
+            sample_lock: Mutex::new(()),
         }
     }
 }
@@ -92,6 +231,7 @@ impl<const N: usize> MetricType for HistogramState<N> {
 /// `Thresholds` defines the size of buckets used in a [`Histogram`]
 pub struct Thresholds<const N: usize> {
     le: [f64; N],
+    unit: Option<Unit>,
 }
 
 impl<const N: usize> Thresholds<N> {
@@ -113,7 +253,10 @@ impl<const N: usize> Thresholds<N> {
 
         let buckets = core::array::from_fn(|i| start * factor.powi(i as i32));
 
-        Thresholds { le: buckets }
+        Thresholds {
+            le: buckets,
+            unit: None,
+        }
     }
 
     /// Create `N` buckets, each `width`  wide, where the lowest bucket has an upper bound of `start`.
@@ -129,7 +272,10 @@ impl<const N: usize> Thresholds<N> {
 
         let buckets = core::array::from_fn(|i| start + width * i as f64);
 
-        Thresholds { le: buckets }
+        Thresholds {
+            le: buckets,
+            unit: None,
+        }
     }
 
     /// Create the histogram thresholds with the given sizes
@@ -143,28 +289,94 @@ impl<const N: usize> Thresholds<N> {
                 "consecutive histogram buckets must not decrease or be equal",
             );
         }
-        Thresholds { le: buckets }
+        Thresholds {
+            le: buckets,
+            unit: None,
+        }
     }
 
     /// View the bucket upper bounds
     pub fn get(&self) -> &[f64; N] {
         &self.le
     }
+
+    /// Set the [`Unit`] of time of the histogram. `observe_duration` converts durations into this unit.
+    ///
+    /// # Panics
+    /// Will panic if the unit is not a unit of time
+    pub fn with_unit(self, unit: Unit) -> Self {
+        assert!(
+            unit.is_time(),
+            "cannot observe durations in {:?}",
+            unit.as_str()
+        );
+        Self {
+            unit: Some(unit),
+            ..self
+        }
+    }
+
+    /// The [`Unit`] of the histogram, if one was set
+    pub fn unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// Estimate the `q`-quantile from the bucket counts and the `+Inf` count. See [`HistogramSnapshot::quantile`]
+    pub(crate) fn quantile(&self, buckets: &[u64; N], inf: u64, q: f64) -> f64 {
+        if q.is_nan() {
+            return f64::NAN;
+        }
+        if q < 0.0 {
+            return f64::NEG_INFINITY;
+        }
+        if q > 1.0 {
+            return f64::INFINITY;
+        }
+
+        let count = buckets.iter().sum::<u64>() + inf;
+        if count == 0 {
+            return f64::NAN;
+        }
+
+        let rank = q * count as f64;
+        let mut seen = 0;
+        for (i, &n) in buckets.iter().enumerate() {
+            if n > 0 && (seen + n) as f64 >= rank {
+                let end = self.le[i];
+                let start = match i {
+                    0 if end > 0.0 => 0.0,
+                    0 => return end,
+                    _ => self.le[i - 1],
+                };
+                return start + (end - start) * (rank - seen as f64) / n as f64;
+            }
+            seen += n;
+        }
+
+        // the quantile is in the +Inf bucket
+        self.le.last().copied().unwrap_or(f64::NAN)
+    }
 }
 
 impl<const N: usize> HistogramLockGuard<'_, N> {
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    pub fn snapshot(self) -> HistogramSnapshot<N> {
+        HistogramSnapshot::new(self.metadata(), self.sample())
+    }
+
     /// Add a single observation to the [`Histogram`].
     pub fn observe(self, x: f64) {
         let bucket = self.metadata().le.partition_point(|le| x > *le);
-        self.inner.read().observe(bucket, x);
+        HistogramState::observe(&self, bucket, x);
     }
 
-    /// Observe the duration in seconds
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
     pub fn observe_duration(self, duration: std::time::Duration) {
-        self.observe(duration.as_secs_f64());
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
     }
 
-    /// Observe the duration in seconds since the given instant
+    /// Observe the duration since the given instant. See `observe_duration`
     pub fn observe_duration_since(self, since: std::time::Instant) -> std::time::Duration {
         let d = since.elapsed();
         self.observe_duration(d);
@@ -173,18 +385,25 @@ impl<const N: usize> HistogramLockGuard<'_, N> {
 }
 
 impl<const N: usize> HistogramMut<'_, N> {
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    pub fn snapshot(mut self) -> HistogramSnapshot<N> {
+        let MetricMut(metric, metadata) = &mut self;
+        HistogramSnapshot::new(metadata, metric.sample())
+    }
+
     /// Add a single observation to the [`Histogram`].
     pub fn observe(mut self, x: f64) {
         let bucket = self.metadata().le.partition_point(|le| x > *le);
-        self.inner.get_mut().observe(bucket, x);
+        HistogramState::observe_mut(&mut self, bucket, x);
     }
 
-    /// Observe the duration in seconds
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
     pub fn observe_duration(self, duration: std::time::Duration) {
-        self.observe(duration.as_secs_f64());
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
     }
 
-    /// Observe the duration in seconds since the given instant
+    /// Observe the duration since the given instant. See `observe_duration`
     pub fn observe_duration_since(self, since: std::time::Instant) -> std::time::Duration {
         let d = since.elapsed();
         self.observe_duration(d);
@@ -198,6 +417,35 @@ impl<const N: usize> Histogram<N> {
         self.get_metric().observe(x);
     }
 
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, duration: std::time::Duration) {
+        self.get_metric().observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(&self, since: std::time::Instant) -> std::time::Duration {
+        self.get_metric().observe_duration_since(since)
+    }
+
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    ///
+    /// ```
+    /// use measured::Histogram;
+    /// use measured::metric::histogram::Thresholds;
+    ///
+    /// let histogram = Histogram::with_metadata(Thresholds::with_buckets([0.1, 0.2, 0.4, 0.8]));
+    /// histogram.observe(0.15);
+    /// histogram.observe(0.3);
+    ///
+    /// let snapshot = histogram.snapshot();
+    /// assert_eq!(snapshot.count, 2);
+    /// assert_eq!(snapshot.buckets, [0, 1, 1, 0]);
+    /// assert_eq!(snapshot.quantile(0.5), 0.2);
+    /// ```
+    pub fn snapshot(&self) -> HistogramSnapshot<N> {
+        self.get_metric().snapshot()
+    }
+
     /// Create a [`HistogramVecTimer`] object that automatically observes a duration when the timer is dropped.
     pub fn start_timer(&self) -> HistogramTimer<'_, N> {
         HistogramTimer {
@@ -213,6 +461,11 @@ impl<L: LabelGroupSet, const N: usize> HistogramVec<L, N> {
         self.get_metric(self.with_labels(label)).observe(y);
     }
 
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum, keyed by the label group.
+    pub fn snapshot(&self, label: L::Group<'_>) -> HistogramSnapshot<N> {
+        self.get_metric(self.with_labels(label)).snapshot()
+    }
+
     /// Create a [`HistogramVecTimer`] object that automatically observes a duration when the timer is dropped.
     ///
     /// # Panics
@@ -225,12 +478,13 @@ impl<L: LabelGroupSet, const N: usize> HistogramVec<L, N> {
         }
     }
 
-    /// Observe the duration in seconds
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
     pub fn observe_duration(&self, label: L::Group<'_>, duration: std::time::Duration) {
-        self.observe(label, duration.as_secs_f64());
+        self.get_metric(self.with_labels(label))
+            .observe_duration(duration);
     }
 
-    /// Observe the duration in seconds since the given instant
+    /// Observe the duration since the given instant. See `observe_duration`
     pub fn observe_duration_since(
         &self,
         label: L::Group<'_>,
@@ -242,6 +496,144 @@ impl<L: LabelGroupSet, const N: usize> HistogramVec<L, N> {
     }
 }
 
+/// The internal state that is used by [`ExemplarHistogram`] and [`ExemplarHistogramVec`]
+///
+/// This is kept separate from [`HistogramState`] so that histograms without exemplars do not pay for the exemplar storage.
+#[derive(Default)]
+pub struct ExemplarHistogramState<const N: usize> {
+    /// The bucket counts and sum of the histogram
+    pub histogram: HistogramState<N>,
+    /// The most recent exemplar for each bucket, followed by the `+Inf` bucket.
+    /// This is empty until the first exemplar is recorded.
+    pub exemplars: Mutex<Box<[Option<Exemplar>]>>,
+}
+
+/// A shared ref to an individual exemplar histogram
+pub type ExemplarHistogramLockGuard<'a, const N: usize> =
+    MetricLockGuard<'a, ExemplarHistogramState<N>>;
+/// A unique ref to an individual exemplar histogram
+pub type ExemplarHistogramMut<'a, const N: usize> = MetricMut<'a, ExemplarHistogramState<N>>;
+
+impl<const N: usize> ExemplarHistogramState<N> {
+    /// Read the current bucket counts, the `+Inf` count and the accumulated sum. See [`HistogramState::sample`]
+    pub fn sample(&self) -> ([u64; N], u64, f64) {
+        self.histogram.sample()
+    }
+
+    fn record_exemplar(exemplars: &mut Box<[Option<Exemplar>]>, bucket: usize, exemplar: Exemplar) {
+        if exemplars.is_empty() {
+            *exemplars = (0..=N).map(|_| None).collect();
+        }
+        exemplars[bucket] = Some(exemplar);
+    }
+}
+
+impl<const N: usize> MetricType for ExemplarHistogramState<N> {
+    type Metadata = Thresholds<N>;
+}
+
+impl<const N: usize> ExemplarHistogramLockGuard<'_, N> {
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    pub fn snapshot(self) -> HistogramSnapshot<N> {
+        HistogramSnapshot::new(self.metadata(), self.sample())
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`].
+    pub fn observe(self, x: f64) {
+        let bucket = self.metadata().le.partition_point(|le| x > *le);
+        self.histogram.observe(bucket, x);
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`], replacing the exemplar of its bucket with the given labels.
+    pub fn observe_with_exemplar(self, x: f64, exemplar: impl LabelGroup) {
+        let bucket = self.metadata().le.partition_point(|le| x > *le);
+        self.histogram.observe(bucket, x);
+        let exemplar = Exemplar::new(exemplar, x);
+        ExemplarHistogramState::<N>::record_exemplar(&mut self.exemplars.lock(), bucket, exemplar);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+}
+
+impl<const N: usize> ExemplarHistogramMut<'_, N> {
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    pub fn snapshot(mut self) -> HistogramSnapshot<N> {
+        let MetricMut(metric, metadata) = &mut self;
+        HistogramSnapshot::new(metadata, metric.sample())
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`].
+    pub fn observe(mut self, x: f64) {
+        let bucket = self.metadata().le.partition_point(|le| x > *le);
+        self.histogram.observe_mut(bucket, x);
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`], replacing the exemplar of its bucket with the given labels.
+    pub fn observe_with_exemplar(mut self, x: f64, exemplar: impl LabelGroup) {
+        let bucket = self.metadata().le.partition_point(|le| x > *le);
+        self.histogram.observe_mut(bucket, x);
+        let exemplar = Exemplar::new(exemplar, x);
+        ExemplarHistogramState::<N>::record_exemplar(self.exemplars.get_mut(), bucket, exemplar);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+}
+
+impl<const N: usize> ExemplarHistogram<N> {
+    /// Add a single observation to the [`ExemplarHistogram`].
+    pub fn observe(&self, x: f64) {
+        self.get_metric().observe(x);
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`], replacing the exemplar of its bucket with the given labels.
+    pub fn observe_with_exemplar(&self, x: f64, exemplar: impl LabelGroup) {
+        self.get_metric().observe_with_exemplar(x, exemplar);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, duration: Duration) {
+        self.get_metric().observe_duration(duration);
+    }
+
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum.
+    pub fn snapshot(&self) -> HistogramSnapshot<N> {
+        self.get_metric().snapshot()
+    }
+}
+
+impl<L: LabelGroupSet, const N: usize> ExemplarHistogramVec<L, N> {
+    /// Add a single observation to the [`ExemplarHistogram`], keyed by the label group.
+    pub fn observe(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).observe(y);
+    }
+
+    /// Add a single observation to the [`ExemplarHistogram`], keyed by the label group,
+    /// replacing the exemplar of its bucket with the given labels.
+    pub fn observe_with_exemplar(&self, label: L::Group<'_>, y: f64, exemplar: impl LabelGroup) {
+        self.get_metric(self.with_labels(label))
+            .observe_with_exemplar(y, exemplar);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram, keyed by the label group.
+    pub fn observe_duration(&self, label: L::Group<'_>, duration: Duration) {
+        self.get_metric(self.with_labels(label))
+            .observe_duration(duration);
+    }
+
+    /// Take a [`HistogramSnapshot`] of the current bucket counts and sum, keyed by the label group.
+    pub fn snapshot(&self, label: L::Group<'_>) -> HistogramSnapshot<N> {
+        self.get_metric(self.with_labels(label)).snapshot()
+    }
+}
+
 /// See [`HistogramVec::start_timer`]
 pub struct HistogramVecTimer<'a, L: LabelGroupSet, const N: usize> {
     vec: Option<&'a HistogramVec<L, N>>,
@@ -296,3 +688,105 @@ impl<'a, const N: usize> Drop for HistogramTimer<'a, N> {
         }
     }
 }
+
+impl<const N: usize> UnitMetadata for Thresholds<N> {
+    fn with_unit(self, unit: Unit) -> Self {
+        if unit.is_time() {
+            Thresholds::with_unit(self, unit)
+        } else {
+            self
+        }
+    }
+    fn matches_unit(&self, unit: Unit) -> bool {
+        duration_unit_matches(self.unit, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HistogramSnapshot, HistogramState, Thresholds};
+
+    #[test]
+    fn snapshot_quantile() {
+        let thresholds = Thresholds::with_buckets([1.0, 2.0, 4.0, 8.0]);
+        let mut state = HistogramState::default();
+
+        let empty = HistogramSnapshot::new(&thresholds, state.sample());
+        assert_eq!(empty.count, 0);
+        assert!(empty.quantile(0.5).is_nan());
+
+        // 10 observations in each of the `0.0..1.0` and `2.0..4.0` buckets, and 5 above the highest bucket
+        for _ in 0..10 {
+            state.observe_mut(0, 0.5);
+            state.observe(2, 3.0);
+        }
+        // sampling swaps the hot and cold states, but the counts remain cumulative
+        state.sample();
+        for _ in 0..5 {
+            state.observe_mut(4, 10.0);
+        }
+
+        let snapshot = HistogramSnapshot::new(&thresholds, state.sample());
+        assert_eq!(snapshot.buckets, [10, 0, 10, 0]);
+        assert_eq!(snapshot.inf, 5);
+        assert_eq!(snapshot.count, 25);
+        assert_eq!(snapshot.sum, 85.0);
+
+        assert_eq!(snapshot.quantile(0.0), 0.0);
+        assert_eq!(snapshot.quantile(0.2), 0.5);
+        assert_eq!(snapshot.quantile(0.4), 1.0);
+        assert_eq!(snapshot.quantile(0.6), 3.0);
+        // falls into the +Inf bucket
+        assert_eq!(snapshot.quantile(0.9), 8.0);
+        assert_eq!(snapshot.quantile(-1.0), f64::NEG_INFINITY);
+        assert_eq!(snapshot.quantile(2.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn consistent_concurrent_samples() {
+        let state = HistogramState::<2>::default();
+        let threads = 4;
+        let n = 10_000;
+
+        std::thread::scope(|s| {
+            for t in 0..threads {
+                let state = &state;
+                s.spawn(move || {
+                    for _ in 0..n {
+                        state.observe(t % 3, 1.0);
+                    }
+                });
+            }
+
+            let mut last = 0;
+            while last < threads * n {
+                // every observation adds 1 to the sum, so the sum must always match the count
+                let (buckets, inf, sum) = state.sample();
+                let count = buckets.iter().sum::<u64>() + inf;
+                assert_eq!(sum, count as f64);
+                assert!(count >= last as u64);
+                last = count as usize;
+            }
+        });
+
+        let (buckets, inf, sum) = state.sample();
+        assert_eq!(buckets, [20_000, 10_000]);
+        assert_eq!(inf, 10_000);
+        assert_eq!(sum, 40_000.0);
+    }
+
+    #[test]
+    fn out_of_range_bucket() {
+        let state = HistogramState::<2>::default();
+        state.observe(1, 1.0);
+
+        let res = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| state.observe(3, 1.0)));
+        assert!(res.is_err());
+
+        // the rejected observation was never started, so sampling does not wait for it
+        let (buckets, inf, sum) = state.sample();
+        assert_eq!(buckets, [0, 1]);
+        assert_eq!(inf, 0);
+        assert_eq!(sum, 1.0);
+    }
+}
@@ -0,0 +1,78 @@
+//! All things info metrics. See [`Info`]
+
+use parking_lot::RwLock;
+
+use super::{
+    exemplar::{copy_labels, OwnedLabels},
+    MetricLockGuard, MetricMut, MetricType,
+};
+use crate::{
+    label::{LabelGroupSet, LabelGroupVisitor, LabelName},
+    Info, InfoVec, LabelGroup,
+};
+
+/// The internal state that is used by [`Info`] and [`InfoVec`]
+#[derive(Default)]
+pub struct InfoState {
+    /// The info labels. These are copied out of the [`LabelGroup`] they were set with.
+    pub labels: RwLock<OwnedLabels>,
+}
+
+impl InfoState {
+    /// Create a new info state with the given info labels
+    pub fn new(labels: impl LabelGroup) -> Self {
+        Self {
+            labels: RwLock::new(copy_labels(labels)),
+        }
+    }
+}
+
+/// A reference to a specific info metric.
+pub type InfoLockGuard<'a> = MetricLockGuard<'a, InfoState>;
+
+/// A mut reference to a specific info metric.
+pub type InfoMut<'a> = MetricMut<'a, InfoState>;
+
+impl MetricType for InfoState {
+    /// [`Info`]s require no additional metadata
+    type Metadata = ();
+}
+
+impl Info {
+    /// Replace the info labels
+    pub fn set_info(&self, labels: impl LabelGroup) {
+        self.get_metric().set_info(labels)
+    }
+}
+
+impl InfoLockGuard<'_> {
+    /// Replace the info labels
+    pub fn set_info(self, labels: impl LabelGroup) {
+        *self.labels.write() = copy_labels(labels);
+    }
+}
+
+impl InfoMut<'_> {
+    /// Replace the info labels
+    pub fn set_info(mut self, labels: impl LabelGroup) {
+        *self.labels.get_mut() = copy_labels(labels);
+    }
+}
+
+impl<L: LabelGroupSet> InfoVec<L> {
+    /// Replace the info labels, keyed by the label group
+    pub fn set_info(&self, label: L::Group<'_>, labels: impl LabelGroup) {
+        self.get_metric(self.with_labels(label)).set_info(labels);
+    }
+}
+
+/// The info labels, as stored in an [`InfoState`]
+pub(crate) struct InfoLabels<'a>(pub(crate) &'a [(Box<str>, Box<str>)]);
+
+impl LabelGroup for InfoLabels<'_> {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        for (name, value) in self.0 {
+            v.write_value(LabelName::from_str(name), &&**value);
+        }
+    }
+}
@@ -2,6 +2,10 @@
 
 use std::io::Write;
 
+use crate::label::LabelGroupSet;
+
+use super::{Metric, MetricType, MetricVec};
+
 /// `MetricName` represents a type that can be encoded into the name of a metric when collected.
 pub trait MetricNameEncoder {
     /// Encoded this name into the given bytes buffer according to the Prometheus metric name encoding specification.
@@ -100,7 +104,7 @@ impl MetricName {
     /// # Panics
     /// Will panic if the string contains invalid characters
     #[must_use]
-    pub const fn from_str(value: &'static str) -> &Self {
+    pub const fn from_str(value: &'static str) -> &'static Self {
         const_assert_metric_name(value);
 
         // SAFETY: `MetricName` is transparent over `str`. There's no way to do this safely.
@@ -120,6 +124,14 @@ impl MetricName {
         Ok(unsafe { &*(value as *const str as *const MetricName) })
     }
 
+    /// Construct a [`MetricName`] from a string that is already known to be a valid metric name.
+    pub(crate) fn from_str_unchecked(value: &str) -> &Self {
+        debug_assert!(try_assert_metric_name(value).is_ok());
+
+        // SAFETY: `MetricName` is transparent over `str`.
+        unsafe { &*(value as *const str as *const MetricName) }
+    }
+
     /// Add a namespace prefix to this metric name.
     #[must_use]
     pub const fn in_namespace(&self, ns: &'static str) -> WithNamespace<&'_ Self> {
@@ -155,6 +167,9 @@ impl MetricNameEncoder for MetricName {
 /// * [`Count`] - Used internally for histograms
 /// * [`Sum`] - Used internally for histograms
 /// * [`Bucket`] - Used internally for histograms
+/// * [`Created`] - Used internally for OpenMetrics creation timestamps
+/// * [`Info`] - Used internally for OpenMetrics info metrics
+/// * [`Unit`] - The unit of a metric, such as `_seconds`
 pub trait Suffix {
     /// Write `_` followed by the suffix value with to the underlying writer
     fn encode_text(&self, b: &mut impl Write) -> std::io::Result<()>;
@@ -234,6 +249,10 @@ pub struct Count;
 pub struct Sum;
 /// `_bucket`. A [`Suffix`] that is used internally for histograms
 pub struct Bucket;
+/// `_created`. A [`Suffix`] that is used internally for OpenMetrics creation timestamps
+pub struct Created;
+/// `_info`. A [`Suffix`] that is used internally for OpenMetrics info metrics
+pub struct Info;
 
 impl Suffix for Total {
     fn encode_text(&self, b: &mut impl Write) -> std::io::Result<()> {
@@ -270,3 +289,159 @@ impl Suffix for Bucket {
         7
     }
 }
+
+impl Suffix for Created {
+    fn encode_text(&self, b: &mut impl Write) -> std::io::Result<()> {
+        b.write_all(b"_created")
+    }
+    fn encode_len(&self) -> usize {
+        8
+    }
+}
+
+impl Suffix for Info {
+    fn encode_text(&self, b: &mut impl Write) -> std::io::Result<()> {
+        b.write_all(b"_info")
+    }
+    fn encode_len(&self) -> usize {
+        5
+    }
+}
+
+/// The unit of a metric, such as `seconds` or `bytes`.
+///
+/// The unit is written as a [`Suffix`] of the metric name, and OpenMetrics encoders also write it in a `# UNIT` line.
+/// Prometheus recommends using base units, so prefer [`Unit::SECONDS`] over [`Unit::MILLISECONDS`].
+///
+/// Use `#[metric(unit = "seconds")]` with [`derive(MetricGroup)`](crate::MetricGroup) to add a unit to a metric.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Unit(&'static str);
+
+impl Unit {
+    /// Time, in seconds
+    pub const SECONDS: Self = Self("seconds");
+    /// Time, in milliseconds
+    pub const MILLISECONDS: Self = Self("milliseconds");
+    /// Time, in microseconds
+    pub const MICROSECONDS: Self = Self("microseconds");
+    /// Time, in nanoseconds
+    pub const NANOSECONDS: Self = Self("nanoseconds");
+    /// Data, in bytes
+    pub const BYTES: Self = Self("bytes");
+    /// A ratio, usually between 0 and 1
+    pub const RATIO: Self = Self("ratio");
+
+    /// Construct a [`Unit`] from a string, can be used in const expressions.
+    ///
+    /// # Panics
+    /// Will panic if the string contains invalid metric name characters
+    #[must_use]
+    pub const fn new(unit: &'static str) -> Self {
+        const_assert_metric_name(unit);
+        Self(unit)
+    }
+
+    /// Get the unit as a string
+    pub const fn as_str(&self) -> &'static str {
+        self.0
+    }
+
+    /// How many of this unit there are in one second, if this is a unit of time
+    const fn per_second(&self) -> Option<f64> {
+        match self.0.as_bytes() {
+            b"seconds" => Some(1.0),
+            b"milliseconds" => Some(1e3),
+            b"microseconds" => Some(1e6),
+            b"nanoseconds" => Some(1e9),
+            _ => None,
+        }
+    }
+
+    /// Whether this is a unit of time
+    pub const fn is_time(&self) -> bool {
+        self.per_second().is_some()
+    }
+
+    /// Convert the duration into this unit.
+    ///
+    /// # Panics
+    /// Will panic if this is not a unit of time
+    pub fn convert_duration(&self, duration: std::time::Duration) -> f64 {
+        match self.per_second() {
+            Some(per_second) => duration.as_secs_f64() * per_second,
+            None => panic!("cannot observe a duration in {:?}", self.0),
+        }
+    }
+}
+
+impl Suffix for Unit {
+    fn encode_text(&self, b: &mut impl Write) -> std::io::Result<()> {
+        b.write_all(b"_")?;
+        b.write_all(self.0.as_bytes())
+    }
+    fn encode_len(&self) -> usize {
+        1 + self.0.len()
+    }
+}
+
+/// Metric metadata which can record the [`Unit`] of its metric.
+///
+/// This lets `observe_duration` convert durations into the unit of time of a histogram or summary.
+/// [`derive(MetricGroup)`](crate::MetricGroup) uses this to apply the `#[metric(unit = "...")]` attribute when
+/// constructing metrics in the generated `new` function.
+pub trait UnitMetadata {
+    /// Record the unit of the metric. Units which are not units of time are not recorded, since only durations
+    /// are converted.
+    fn with_unit(self, unit: Unit) -> Self;
+
+    /// Whether durations observed by the metric are converted into the unit.
+    ///
+    /// Always true if the unit is not a unit of time, or if the metric cannot observe durations.
+    fn matches_unit(&self, _unit: Unit) -> bool {
+        true
+    }
+}
+
+impl UnitMetadata for () {
+    fn with_unit(self, _unit: Unit) -> Self {}
+}
+
+/// A [`Metric`] or [`MetricVec`] whose metadata can record a [`Unit`]. See [`UnitMetadata`]
+pub trait MetricFamilyUnit {
+    /// Whether durations observed by the metrics in this family are converted into the unit.
+    fn matches_unit(&self, unit: Unit) -> bool;
+}
+
+impl<M: MetricType> MetricFamilyUnit for Metric<M>
+where
+    M::Metadata: UnitMetadata,
+{
+    fn matches_unit(&self, unit: Unit) -> bool {
+        self.metadata.matches_unit(unit)
+    }
+}
+
+impl<M: MetricType, L: LabelGroupSet> MetricFamilyUnit for MetricVec<M, L>
+where
+    M::Metadata: UnitMetadata,
+{
+    fn matches_unit(&self, unit: Unit) -> bool {
+        self.metadata.matches_unit(unit)
+    }
+}
+
+impl<T: MetricFamilyUnit> MetricFamilyUnit for Option<T> {
+    fn matches_unit(&self, unit: Unit) -> bool {
+        self.as_ref().is_none_or(|this| this.matches_unit(unit))
+    }
+}
+
+/// Whether the recorded unit of time, which defaults to seconds, matches the unit
+pub(crate) fn duration_unit_matches(recorded: Option<Unit>, unit: Unit) -> bool {
+    !unit.is_time() || recorded.unwrap_or(Unit::SECONDS) == unit
+}
+
+/// Convert the duration into the unit, or seconds if there is no unit
+pub(crate) fn duration_in(unit: Option<Unit>, duration: std::time::Duration) -> f64 {
+    unit.unwrap_or(Unit::SECONDS).convert_duration(duration)
+}
@@ -0,0 +1,449 @@
+//! All things native histograms. See [`NativeHistogram`]
+
+use std::{
+    collections::HashMap,
+    hash::BuildHasherDefault,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use parking_lot::{RwLock, RwLockWriteGuard};
+
+use super::{
+    gauge::AtomicF64,
+    name::{duration_in, duration_unit_matches, Unit, UnitMetadata},
+    MetricLockGuard, MetricMut, MetricType,
+};
+use crate::{label::LabelGroupSet, NativeHistogram, NativeHistogramVec};
+
+type BucketMap = HashMap<i32, AtomicU64, BuildHasherDefault<rustc_hash::FxHasher>>;
+
+/// The inner state of a native histogram.
+///
+/// Native histograms have sparse buckets with exponentially growing boundaries, as described by
+/// the [`NativeHistogramConfig`] schema. Only buckets that have been observed are stored.
+#[derive(Default)]
+pub struct NativeHistogramStateInner {
+    /// The buckets for positive observations, keyed by their bucket index
+    pub positive: BucketMap,
+    /// The buckets for negative observations, keyed by their bucket index
+    pub negative: BucketMap,
+    /// The number of observed values within the zero threshold
+    pub zero: AtomicU64,
+    /// The total number of observed values
+    pub count: AtomicU64,
+    /// The accumulated sum
+    pub sum: AtomicF64,
+}
+
+/// A sample of a [`NativeHistogram`], taken at collection time.
+#[derive(Debug, Clone, PartialEq)]
+pub struct NativeHistogramSample {
+    /// The non-empty positive buckets, sorted by their bucket index
+    pub positive: Vec<(i32, u64)>,
+    /// The non-empty negative buckets, sorted by their bucket index
+    pub negative: Vec<(i32, u64)>,
+    /// The number of observed values within the zero threshold
+    pub zero: u64,
+    /// The total number of observed values
+    pub count: u64,
+    /// The accumulated sum
+    pub sum: f64,
+}
+
+impl NativeHistogramStateInner {
+    /// Read the current state of the buckets.
+    pub fn sample(&mut self) -> NativeHistogramSample {
+        fn sorted(buckets: &mut BucketMap) -> Vec<(i32, u64)> {
+            let mut buckets: Vec<(i32, u64)> = buckets
+                .iter_mut()
+                .map(|(k, v)| (*k, *v.get_mut()))
+                .filter(|(_, v)| *v > 0)
+                .collect();
+            buckets.sort_unstable_by_key(|(k, _)| *k);
+            buckets
+        }
+
+        NativeHistogramSample {
+            positive: sorted(&mut self.positive),
+            negative: sorted(&mut self.negative),
+            zero: *self.zero.get_mut(),
+            count: *self.count.get_mut(),
+            sum: self.sum.get_ex(),
+        }
+    }
+}
+
+/// The state of a native histogram. See also [`NativeHistogramStateInner`]
+#[derive(Default)]
+pub struct NativeHistogramState {
+    /// A rwlock over the inner histogram state.
+    /// The read lock is acquired for observations into existing buckets.
+    /// The write lock is acquired for inserting new buckets, and for sampling.
+    pub inner: RwLock<NativeHistogramStateInner>,
+}
+
+/// A shared ref to an individual native histogram
+pub type NativeHistogramLockGuard<'a> = MetricLockGuard<'a, NativeHistogramState>;
+/// A unique ref to an individual native histogram
+pub type NativeHistogramMut<'a> = MetricMut<'a, NativeHistogramState>;
+
+impl MetricType for NativeHistogramState {
+    type Metadata = NativeHistogramConfig;
+}
+
+/// `NativeHistogramConfig` defines the bucket schema used in a [`NativeHistogram`]
+///
+/// The buckets are base-2 exponential. For a schema `n`, each power of two is divided into `2^n`
+/// logarithmic buckets, so each bucket boundary is the previous boundary times `2^(2^-n)`.
+pub struct NativeHistogramConfig {
+    schema: i32,
+    zero_threshold: f64,
+    /// The upper bounds of each bucket within `[0.5, 1)` for positive schemas.
+    bounds: Box<[f64]>,
+    unit: Option<Unit>,
+}
+
+impl Default for NativeHistogramConfig {
+    /// Schema 3, which has a bucket growth factor of ~1.09, with a zero threshold of `2^-128`.
+    fn default() -> Self {
+        Self::new(3)
+    }
+}
+
+impl NativeHistogramConfig {
+    /// The default zero threshold, `2^-128`, as used by the prometheus go client.
+    pub const DEFAULT_ZERO_THRESHOLD: f64 = 2.938735877055719e-39;
+
+    /// Create a new native histogram bucket schema.
+    ///
+    /// # Panics
+    /// The function panics if `schema` is not within `-4..=8`.
+    pub fn new(schema: i32) -> Self {
+        assert!(
+            (-4..=8).contains(&schema),
+            "native histogram schema must be within -4..=8, schema: {schema}",
+        );
+
+        let bounds = if schema > 0 {
+            let n = 1 << schema;
+            (0..n).map(|i| (i as f64 / n as f64).exp2() / 2.0).collect()
+        } else {
+            Box::default()
+        };
+
+        Self {
+            schema,
+            zero_threshold: Self::DEFAULT_ZERO_THRESHOLD,
+            bounds,
+            unit: None,
+        }
+    }
+
+    /// Set the width of the zero bucket. Observations with an absolute value less than or equal to the
+    /// threshold are counted in the zero bucket.
+    ///
+    /// # Panics
+    /// The function panics if `zero_threshold` is negative.
+    pub fn with_zero_threshold(self, zero_threshold: f64) -> Self {
+        assert!(
+            zero_threshold >= 0.0,
+            "native histogram zero threshold must not be negative, zero_threshold: {zero_threshold}",
+        );
+        Self {
+            zero_threshold,
+            ..self
+        }
+    }
+
+    /// Set the [`Unit`] of time of the histogram. `observe_duration` converts durations into this unit.
+    ///
+    /// # Panics
+    /// Will panic if the unit is not a unit of time
+    pub fn with_unit(self, unit: Unit) -> Self {
+        assert!(
+            unit.is_time(),
+            "cannot observe durations in {:?}",
+            unit.as_str()
+        );
+        Self {
+            unit: Some(unit),
+            ..self
+        }
+    }
+
+    /// The [`Unit`] of the histogram, if one was set
+    pub fn unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// View the bucket schema
+    pub fn schema(&self) -> i32 {
+        self.schema
+    }
+
+    /// View the zero threshold
+    pub fn zero_threshold(&self) -> f64 {
+        self.zero_threshold
+    }
+
+    /// Find the bucket index for the given absolute value.
+    ///
+    /// Bucket `i` covers the range `(base^(i-1), base^i]`.
+    /// The value must be positive, values within the zero threshold are not assigned a bucket.
+    pub(crate) fn bucket_index(&self, x: f64) -> i32 {
+        debug_assert!(x > 0.0);
+        if x == f64::INFINITY {
+            // infinity gets the bucket just above the largest finite value
+            return self.bucket_index(f64::MAX) + 1;
+        }
+
+        let (frac, exp) = frexp(x);
+        if self.schema > 0 {
+            let index = self.bounds.partition_point(|b| *b < frac) as i32;
+            index + (exp - 1) * self.bounds.len() as i32
+        } else {
+            // exact powers of two are the upper bound of the previous bucket
+            let key = if frac == 0.5 { exp - 1 } else { exp };
+            let offset = (1 << -self.schema) - 1;
+            (key + offset) >> -self.schema
+        }
+    }
+}
+
+/// Split a positive finite float into a fraction within `[0.5, 1)` and a power of two.
+fn frexp(x: f64) -> (f64, i32) {
+    const EXP_MASK: u64 = 0x7ff << 52;
+
+    let bits = x.to_bits();
+    let exp = ((bits & EXP_MASK) >> 52) as i32;
+    if exp == 0 {
+        // subnormal values. scale up into the normal range first
+        let (frac, exp) = frexp(x * 2f64.powi(64));
+        return (frac, exp - 64);
+    }
+
+    let frac = f64::from_bits((bits & !EXP_MASK) | (1022 << 52));
+    (frac, exp - 1022)
+}
+
+impl NativeHistogramStateInner {
+    fn buckets(&self, x: f64) -> Option<&BucketMap> {
+        if x > 0.0 {
+            Some(&self.positive)
+        } else if x < 0.0 {
+            Some(&self.negative)
+        } else {
+            None
+        }
+    }
+
+    fn buckets_mut(&mut self, x: f64) -> Option<&mut BucketMap> {
+        if x > 0.0 {
+            Some(&mut self.positive)
+        } else if x < 0.0 {
+            Some(&mut self.negative)
+        } else {
+            None
+        }
+    }
+}
+
+impl NativeHistogramState {
+    fn observe(&self, config: &NativeHistogramConfig, x: f64) {
+        let bucket = (x.abs() > config.zero_threshold).then(|| config.bucket_index(x.abs()));
+
+        let mut inner = self.inner.read();
+        let counter = match bucket {
+            // NaN observations only contribute to the sum and count
+            _ if x.is_nan() => None,
+            None => Some(&inner.zero),
+            Some(key) => match inner.buckets(x).and_then(|b| b.get(&key)) {
+                Some(counter) => Some(counter),
+                None => {
+                    drop(inner);
+                    let mut write = self.inner.write();
+                    if let Some(buckets) = write.buckets_mut(x) {
+                        buckets.entry(key).or_default();
+                    }
+                    inner = RwLockWriteGuard::downgrade(write);
+                    inner.buckets(x).and_then(|b| b.get(&key))
+                }
+            },
+        };
+
+        if let Some(counter) = counter {
+            counter.fetch_add(1, Ordering::Relaxed);
+        }
+        inner.count.fetch_add(1, Ordering::Relaxed);
+        inner.sum.inc_by(x);
+    }
+
+    fn observe_mut(&mut self, config: &NativeHistogramConfig, x: f64) {
+        let inner = self.inner.get_mut();
+        if x.is_nan() {
+        } else if x.abs() <= config.zero_threshold {
+            *inner.zero.get_mut() += 1;
+        } else {
+            let key = config.bucket_index(x.abs());
+            if let Some(buckets) = inner.buckets_mut(x) {
+                *buckets.entry(key).or_default().get_mut() += 1;
+            }
+        }
+        *inner.count.get_mut() += 1;
+        let v = inner.sum.get_ex();
+        inner.sum.set_mut(v + x);
+    }
+}
+
+impl NativeHistogramLockGuard<'_> {
+    /// Add a single observation to the [`NativeHistogram`].
+    pub fn observe(self, x: f64) {
+        NativeHistogramState::observe(&self, self.metadata(), x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: std::time::Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+}
+
+impl NativeHistogramMut<'_> {
+    /// Add a single observation to the [`NativeHistogram`].
+    pub fn observe(mut self, x: f64) {
+        let MetricMut(metric, metadata) = &mut self;
+        metric.observe_mut(metadata, x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: std::time::Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+}
+
+impl NativeHistogram {
+    /// Add a single observation to the [`NativeHistogram`].
+    pub fn observe(&self, x: f64) {
+        self.get_metric().observe(x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, duration: Duration) {
+        self.get_metric().observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(&self, since: std::time::Instant) -> Duration {
+        self.get_metric().observe_duration_since(since)
+    }
+}
+
+impl<L: LabelGroupSet> NativeHistogramVec<L> {
+    /// Add a single observation to the [`NativeHistogram`], keyed by the label group.
+    pub fn observe(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).observe(y);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, label: L::Group<'_>, duration: Duration) {
+        self.get_metric(self.with_labels(label))
+            .observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(
+        &self,
+        label: L::Group<'_>,
+        since: std::time::Instant,
+    ) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(label, d);
+        d
+    }
+}
+
+impl UnitMetadata for NativeHistogramConfig {
+    fn with_unit(self, unit: Unit) -> Self {
+        if unit.is_time() {
+            NativeHistogramConfig::with_unit(self, unit)
+        } else {
+            self
+        }
+    }
+    fn matches_unit(&self, unit: Unit) -> bool {
+        duration_unit_matches(self.unit, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::NativeHistogram;
+
+    use super::NativeHistogramConfig;
+
+    #[test]
+    fn bucket_index() {
+        let schema0 = NativeHistogramConfig::new(0);
+        assert_eq!(schema0.bucket_index(1.0), 0);
+        assert_eq!(schema0.bucket_index(1.5), 1);
+        assert_eq!(schema0.bucket_index(2.0), 1);
+        assert_eq!(schema0.bucket_index(3.0), 2);
+        assert_eq!(schema0.bucket_index(0.3), -1);
+        assert_eq!(schema0.bucket_index(0.25), -2);
+
+        let schema_neg = NativeHistogramConfig::new(-1);
+        assert_eq!(schema_neg.bucket_index(1.0), 0);
+        assert_eq!(schema_neg.bucket_index(3.0), 1);
+        assert_eq!(schema_neg.bucket_index(4.0), 1);
+        assert_eq!(schema_neg.bucket_index(5.0), 2);
+
+        let schema3 = NativeHistogramConfig::new(3);
+        assert_eq!(schema3.bucket_index(1.0), 0);
+        assert_eq!(schema3.bucket_index(2.0), 8);
+        assert_eq!(schema3.bucket_index(1.05), 1);
+        assert_eq!(schema3.bucket_index(0.5), -8);
+        assert_eq!(schema3.bucket_index(f64::MIN_POSITIVE / 4.0), -8 * 1024);
+        assert_eq!(schema3.bucket_index(f64::MAX), 8 * 1024);
+        assert_eq!(schema3.bucket_index(f64::INFINITY), 8 * 1024 + 1);
+
+        // bucket `i` covers `(base^(i-1), base^i]`
+        let base = 2f64.powf(2f64.powi(-3));
+        for i in -20..20 {
+            let mid = base.powf(i as f64 - 0.5);
+            assert_eq!(schema3.bucket_index(mid), i);
+        }
+    }
+
+    #[test]
+    fn sample() {
+        let mut h = NativeHistogram::with_metadata(NativeHistogramConfig::new(0));
+        h.observe(1.0);
+        h.observe(1.5);
+        h.observe(-3.0);
+        h.observe(0.0);
+        h.get_metric_mut().observe(1.75);
+
+        let sample = h.get_metric_mut().inner.get_mut().sample();
+        assert_eq!(sample.positive, [(0, 1), (1, 2)]);
+        assert_eq!(sample.negative, [(2, 1)]);
+        assert_eq!(sample.zero, 1);
+        assert_eq!(sample.count, 5);
+        assert_eq!(sample.sum, 1.25);
+    }
+}
@@ -0,0 +1,117 @@
+//! All things state sets. See [`StateSet`]
+
+use core::{
+    marker::PhantomData,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::{
+    label::{FixedCardinalityLabel, LabelGroupSet, LabelGroupVisitor, LabelName},
+    LabelGroup, StateSet, StateSetVec,
+};
+
+use super::{name::MetricNameEncoder, MetricLockGuard, MetricMut, MetricType};
+
+/// The internal state that is used by [`StateSet`] and [`StateSetVec`]
+pub struct StateSetState<T> {
+    /// The encoded current state. Any value outside of `0..T::cardinality()` means no state is set.
+    pub state: AtomicUsize,
+    states: PhantomData<T>,
+}
+
+impl<T: FixedCardinalityLabel> Default for StateSetState<T> {
+    fn default() -> Self {
+        Self {
+            state: AtomicUsize::new(usize::MAX),
+            states: PhantomData,
+        }
+    }
+}
+
+impl<T: FixedCardinalityLabel> StateSetState<T> {
+    /// Create a new state set state with the given state
+    pub fn new(state: T) -> Self {
+        Self {
+            state: AtomicUsize::new(state.encode()),
+            states: PhantomData,
+        }
+    }
+
+    /// Get the current state, if one has been set
+    pub fn get(&self) -> Option<T> {
+        decode(self.state.load(Ordering::Relaxed))
+    }
+}
+
+fn decode<T: FixedCardinalityLabel>(state: usize) -> Option<T> {
+    (state < T::cardinality()).then(|| T::decode(state))
+}
+
+/// A reference to a specific state set.
+pub type StateSetLockGuard<'a, T> = MetricLockGuard<'a, StateSetState<T>>;
+
+/// A mut reference to a specific state set.
+pub type StateSetMut<'a, T> = MetricMut<'a, StateSetState<T>>;
+
+impl<T: FixedCardinalityLabel> MetricType for StateSetState<T> {
+    /// [`StateSet`]s require no additional metadata
+    type Metadata = ();
+}
+
+impl<T: FixedCardinalityLabel> StateSet<T> {
+    /// Set the current state. All other states are unset
+    pub fn set(&self, state: T) {
+        self.get_metric().set(state)
+    }
+
+    /// Get the current state, if one has been set
+    pub fn get(&self) -> Option<T> {
+        self.get_metric().get()
+    }
+}
+
+impl<T: FixedCardinalityLabel> StateSetLockGuard<'_, T> {
+    /// Set the current state. All other states are unset
+    pub fn set(self, state: T) {
+        self.state.store(state.encode(), Ordering::Relaxed);
+    }
+}
+
+impl<T: FixedCardinalityLabel> StateSetMut<'_, T> {
+    /// Set the current state. All other states are unset
+    pub fn set(mut self, state: T) {
+        *self.state.get_mut() = state.encode();
+    }
+}
+
+impl<T: FixedCardinalityLabel, L: LabelGroupSet> StateSetVec<T, L> {
+    /// Set the current state, keyed by the label group. All other states are unset
+    pub fn set(&self, label: L::Group<'_>, state: T) {
+        self.get_metric(self.with_labels(label)).set(state);
+    }
+}
+
+/// The label name that identifies each state in a state set.
+///
+/// The label name is the same as the metric name. Colons are not allowed in label names,
+/// so they are replaced with underscores.
+pub(crate) fn state_set_label_name(name: impl MetricNameEncoder) -> String {
+    let mut buf = Vec::with_capacity(name.encode_len());
+    name.encode_utf8(&mut buf)
+        .expect("writing into a vec should not fail");
+    String::from_utf8(buf)
+        .expect("metric names should be valid utf8")
+        .replace(':', "_")
+}
+
+/// The label that identifies each state in a state set.
+pub(crate) struct StateSetLabel<'a, T> {
+    pub(crate) name: &'a LabelName,
+    pub(crate) state: T,
+}
+
+impl<T: FixedCardinalityLabel> LabelGroup for StateSetLabel<'_, T> {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        v.write_value(self.name, &self.state);
+    }
+}
@@ -0,0 +1,432 @@
+//! All things summaries. See [`Summary`]
+
+use std::time::Duration;
+
+use parking_lot::Mutex;
+
+use super::{
+    name::{duration_in, duration_unit_matches, Unit, UnitMetadata},
+    MetricLockGuard, MetricMut, MetricType,
+};
+use crate::{label::LabelGroupSet, Summary, SummaryVec};
+
+/// The number of observations that are buffered before they are merged into the quantile sketch.
+const BUFFER_SIZE: usize = 500;
+
+/// The inner state of a summary.
+///
+/// The quantiles are estimated with a biased quantile sketch, as described by Cormode, Korn, Muthukrishnan and Srivastava
+/// in "Effective Computation of Biased Quantiles over Data Streams". Rather than storing every observation,
+/// the sketch only keeps as many samples as are needed to answer the configured [`Quantiles`] within their allowed error.
+#[derive(Default)]
+pub struct SummaryStateInner {
+    /// Observations that have not yet been merged into the sketch
+    buffer: Vec<f64>,
+    /// The compressed sketch, sorted by value
+    samples: Vec<Sample>,
+    /// The total number of observed values
+    pub count: u64,
+    /// The accumulated sum
+    pub sum: f64,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sample {
+    value: f64,
+    /// The difference in rank between this sample and the previous sample
+    width: f64,
+    /// The uncertainty in the rank of this sample
+    delta: f64,
+}
+
+impl SummaryStateInner {
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(&mut self, quantiles: &Quantiles, x: f64) {
+        if !quantiles.targets.is_empty() && !x.is_nan() {
+            self.buffer.push(x);
+            if self.buffer.len() >= BUFFER_SIZE {
+                self.flush(quantiles);
+            }
+        }
+        self.count += 1;
+        self.sum += x;
+    }
+
+    /// Estimate the values of each of the [`Quantiles`], and read the count and accumulated sum.
+    ///
+    /// The quantile values are `NaN` if nothing has been observed yet.
+    pub fn sample(&mut self, quantiles: &Quantiles) -> (Vec<f64>, u64, f64) {
+        let values = if self.samples.is_empty() && !self.buffer.is_empty() {
+            // when there hasn't been enough data to fill the buffer, the exact quantiles can be read from the buffer.
+            self.buffer.sort_unstable_by(f64::total_cmp);
+            let n = self.buffer.len() as f64;
+            quantiles
+                .targets
+                .iter()
+                .map(|&(q, _)| self.buffer[((n * q).ceil() as usize).saturating_sub(1)])
+                .collect()
+        } else {
+            self.flush(quantiles);
+            quantiles
+                .targets
+                .iter()
+                .map(|&(q, _)| self.query(quantiles, q))
+                .collect()
+        };
+        (values, self.count, self.sum)
+    }
+
+    /// The number of samples that the sketch currently holds.
+    pub fn len(&self) -> usize {
+        self.samples.len() + self.buffer.len()
+    }
+
+    /// Whether the sketch currently holds no samples.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The number of observations merged into the sketch
+    fn n(&self) -> f64 {
+        self.samples.iter().map(|s| s.width).sum()
+    }
+
+    fn flush(&mut self, quantiles: &Quantiles) {
+        if self.buffer.is_empty() {
+            return;
+        }
+        self.buffer.sort_unstable_by(f64::total_cmp);
+        self.merge(quantiles);
+        self.buffer.clear();
+        self.compress(quantiles);
+    }
+
+    fn merge(&mut self, quantiles: &Quantiles) {
+        let mut n = self.n();
+        let mut r = 0.0;
+        let mut i = 0;
+        for &value in &self.buffer {
+            while i < self.samples.len() && self.samples[i].value <= value {
+                r += self.samples[i].width;
+                i += 1;
+            }
+
+            // new minimum and maximum values have an exact rank
+            let delta = if i == 0 || i == self.samples.len() {
+                0.0
+            } else {
+                (quantiles.invariant(n, r).floor() - 1.0).max(0.0)
+            };
+            self.samples.insert(
+                i,
+                Sample {
+                    value,
+                    width: 1.0,
+                    delta,
+                },
+            );
+
+            i += 1;
+            r += 1.0;
+            n += 1.0;
+        }
+    }
+
+    fn compress(&mut self, quantiles: &Quantiles) {
+        let Some(mut x) = self.samples.last().copied() else {
+            return;
+        };
+        let n = self.n();
+        let mut xi = self.samples.len() - 1;
+        let mut r = n - 1.0 - x.width;
+
+        for i in (0..self.samples.len() - 1).rev() {
+            let c = self.samples[i];
+            if c.width + x.width + x.delta <= quantiles.invariant(n, r) {
+                x.width += c.width;
+                self.samples[xi] = x;
+                self.samples.remove(i);
+                xi -= 1;
+            } else {
+                x = c;
+                xi = i;
+            }
+            r -= c.width;
+        }
+    }
+
+    fn query(&self, quantiles: &Quantiles, q: f64) -> f64 {
+        let Some(first) = self.samples.first() else {
+            return f64::NAN;
+        };
+
+        let n = self.n();
+        let mut t = (q * n).ceil();
+        t += (quantiles.invariant(n, t) / 2.0).ceil();
+
+        let mut prev = first;
+        let mut r = 0.0;
+        for c in &self.samples[1..] {
+            r += prev.width;
+            if r + c.width + c.delta > t {
+                return prev.value;
+            }
+            prev = c;
+        }
+        prev.value
+    }
+}
+
+/// The state of a summary. See also [`SummaryStateInner`]
+#[derive(Default)]
+pub struct SummaryState {
+    /// A mutex over the inner summary state.
+    /// The lock is acquired for both observations and sampling.
+    pub inner: Mutex<SummaryStateInner>,
+}
+
+/// A shared ref to an individual summary
+pub type SummaryLockGuard<'a> = MetricLockGuard<'a, SummaryState>;
+/// A unique ref to an individual summary
+pub type SummaryMut<'a> = MetricMut<'a, SummaryState>;
+
+impl MetricType for SummaryState {
+    type Metadata = Quantiles;
+}
+
+/// `Quantiles` defines which quantiles a [`Summary`] reports, and the allowed error for each quantile.
+pub struct Quantiles {
+    targets: Box<[(f64, f64)]>,
+    unit: Option<Unit>,
+}
+
+impl Default for Quantiles {
+    /// The median, 90th and 99th percentiles, with errors of 5%, 1% and 0.1% respectively.
+    fn default() -> Self {
+        Self::new([(0.5, 0.05), (0.9, 0.01), (0.99, 0.001)])
+    }
+}
+
+impl Quantiles {
+    /// Create the summary quantiles from pairs of `(quantile, error)`.
+    ///
+    /// For instance, `(0.99, 0.001)` reports the 99th percentile, where the reported value
+    /// lies somewhere between the 98.9th and 99.1th percentile.
+    ///
+    /// # Panics
+    /// The function panics if any quantile or error is not strictly between 0 and 1.
+    /// The minimum and maximum (quantiles 0 and 1) cannot be tracked within an error bound,
+    /// and an error of 0 would keep every observation.
+    pub fn new(targets: impl IntoIterator<Item = (f64, f64)>) -> Self {
+        let targets: Box<[(f64, f64)]> = targets.into_iter().collect();
+        for &(quantile, error) in &*targets {
+            assert!(
+                quantile > 0.0 && quantile < 1.0,
+                "summary quantiles must be strictly between 0 and 1, quantile: {quantile}",
+            );
+            assert!(
+                error > 0.0 && error < 1.0,
+                "summary quantile errors must be strictly between 0 and 1, error: {error}",
+            );
+        }
+        Self {
+            targets,
+            unit: None,
+        }
+    }
+
+    /// View the quantiles and their allowed errors
+    pub fn get(&self) -> &[(f64, f64)] {
+        &self.targets
+    }
+
+    /// Set the [`Unit`] of time of the summary. `observe_duration` converts durations into this unit.
+    ///
+    /// # Panics
+    /// Will panic if the unit is not a unit of time
+    pub fn with_unit(self, unit: Unit) -> Self {
+        assert!(
+            unit.is_time(),
+            "cannot observe durations in {:?}",
+            unit.as_str()
+        );
+        Self {
+            unit: Some(unit),
+            ..self
+        }
+    }
+
+    /// The [`Unit`] of the summary, if one was set
+    pub fn unit(&self) -> Option<Unit> {
+        self.unit
+    }
+
+    /// The maximum allowed rank uncertainty at rank `r`, out of `n` observations.
+    fn invariant(&self, n: f64, r: f64) -> f64 {
+        self.targets
+            .iter()
+            .map(|&(q, e)| {
+                if q * n <= r {
+                    (2.0 * e * r) / q
+                } else {
+                    (2.0 * e * (n - r)) / (1.0 - q)
+                }
+            })
+            .fold(f64::MAX, f64::min)
+    }
+}
+
+impl SummaryLockGuard<'_> {
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(self, x: f64) {
+        self.inner.lock().observe(self.metadata(), x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the summary. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: std::time::Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+}
+
+impl SummaryMut<'_> {
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(mut self, x: f64) {
+        let MetricMut(metric, metadata) = &mut self;
+        metric.inner.get_mut().observe(metadata, x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the summary. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().unit, duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: std::time::Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+}
+
+impl Summary {
+    /// Add a single observation to the [`Summary`].
+    pub fn observe(&self, x: f64) {
+        self.get_metric().observe(x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the summary. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, duration: Duration) {
+        self.get_metric().observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(&self, since: std::time::Instant) -> Duration {
+        self.get_metric().observe_duration_since(since)
+    }
+}
+
+impl<L: LabelGroupSet> SummaryVec<L> {
+    /// Add a single observation to the [`Summary`], keyed by the label group.
+    pub fn observe(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).observe(y);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the summary. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, label: L::Group<'_>, duration: Duration) {
+        self.get_metric(self.with_labels(label))
+            .observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(
+        &self,
+        label: L::Group<'_>,
+        since: std::time::Instant,
+    ) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(label, d);
+        d
+    }
+}
+
+impl UnitMetadata for Quantiles {
+    fn with_unit(self, unit: Unit) -> Self {
+        if unit.is_time() {
+            Quantiles::with_unit(self, unit)
+        } else {
+            self
+        }
+    }
+    fn matches_unit(&self, unit: Unit) -> bool {
+        duration_unit_matches(self.unit, unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Quantiles, SummaryStateInner};
+
+    #[test]
+    fn quantile_error() {
+        let quantiles = Quantiles::default();
+        let mut summary = SummaryStateInner::default();
+
+        // observe 0..100_000 in a scrambled order
+        let n = 100_000;
+        for i in 0..n {
+            summary.observe(&quantiles, ((i * 7919) % n) as f64);
+        }
+
+        let (values, count, sum) = summary.sample(&quantiles);
+        assert_eq!(count, n);
+        assert_eq!(sum, (n * (n - 1) / 2) as f64);
+
+        for (&(q, e), value) in quantiles.get().iter().zip(values) {
+            // the value is also its rank
+            let rank = value / n as f64;
+            assert!(
+                (q - e..=q + e).contains(&rank),
+                "quantile {q} was {rank}, outside of the allowed error {e}"
+            );
+        }
+
+        // the sketch holds far fewer samples than were observed
+        assert!(summary.len() < 2000, "{}", summary.len());
+    }
+
+    #[test]
+    fn empty() {
+        let quantiles = Quantiles::new([(0.5, 0.05)]);
+        let mut summary = SummaryStateInner::default();
+
+        let (values, count, sum) = summary.sample(&quantiles);
+        assert!(values[0].is_nan());
+        assert_eq!(count, 0);
+        assert_eq!(sum, 0.0);
+
+        summary.observe(&quantiles, 4.0);
+        let (values, _, _) = summary.sample(&quantiles);
+        assert_eq!(values, [4.0]);
+    }
+
+    #[test]
+    #[should_panic = "summary quantile errors must be strictly between 0 and 1"]
+    fn zero_error() {
+        Quantiles::new([(0.5, 0.0)]);
+    }
+
+    #[test]
+    #[should_panic = "summary quantiles must be strictly between 0 and 1"]
+    fn max_quantile() {
+        Quantiles::new([(1.0, 0.01)]);
+    }
+}
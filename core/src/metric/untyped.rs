@@ -0,0 +1,81 @@
+//! All things untyped metrics. See [`Untyped`]
+
+use crate::{label::LabelGroupSet, Untyped, UntypedVec};
+
+use super::{gauge::AtomicF64, MetricLockGuard, MetricMut, MetricType};
+
+#[derive(Default)]
+/// The internal state that is used by [`Untyped`] and [`UntypedVec`]
+pub struct UntypedState {
+    /// The current value
+    pub value: AtomicF64,
+}
+
+impl UntypedState {
+    /// Create a new untyped metric state with the given value
+    pub fn new(value: f64) -> Self {
+        Self {
+            value: AtomicF64::new(value),
+        }
+    }
+}
+
+/// A reference to a specific untyped metric.
+pub type UntypedLockGuard<'a> = MetricLockGuard<'a, UntypedState>;
+
+/// A mut reference to a specific untyped metric.
+pub type UntypedMut<'a> = MetricMut<'a, UntypedState>;
+
+impl Untyped {
+    /// Increment the value by `x`
+    pub fn inc_by(&self, x: f64) {
+        self.get_metric().inc_by(x)
+    }
+
+    /// Set the value to `x`
+    pub fn set(&self, x: f64) {
+        self.get_metric().set(x)
+    }
+}
+
+impl UntypedLockGuard<'_> {
+    /// Increment the value by `x`
+    pub fn inc_by(self, x: f64) {
+        self.value.inc_by(x);
+    }
+
+    /// Set the value to `x`
+    pub fn set(self, x: f64) {
+        self.value.set(x);
+    }
+}
+
+impl UntypedMut<'_> {
+    /// Increment the value by `x`
+    pub fn inc_by(mut self, x: f64) {
+        let x = self.value.get_ex() + x;
+        self.value.set_mut(x);
+    }
+
+    /// Set the value to `x`
+    pub fn set(mut self, x: f64) {
+        self.value.set_mut(x);
+    }
+}
+
+impl<L: LabelGroupSet> UntypedVec<L> {
+    /// Increment the value by `y`, keyed by the label group
+    pub fn inc_by(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).inc_by(y);
+    }
+
+    /// Set the value to `y`, keyed by the label group
+    pub fn set(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).set(y);
+    }
+}
+
+impl MetricType for UntypedState {
+    /// [`Untyped`] metrics require no additional metadata
+    type Metadata = ();
+}
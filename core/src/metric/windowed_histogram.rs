@@ -0,0 +1,346 @@
+//! All things windowed histograms. See [`WindowedHistogram`]
+
+use std::{
+    sync::atomic::Ordering,
+    time::{Duration, Instant},
+};
+
+use parking_lot::RwLock;
+
+use super::{
+    histogram::{HistogramState, HistogramStateInner, Thresholds},
+    name::{duration_in, Unit, UnitMetadata},
+    MetricLockGuard, MetricMut, MetricType,
+};
+use crate::{label::LabelGroupSet, WindowedHistogram, WindowedHistogramVec};
+
+/// The state of a windowed histogram.
+///
+/// Every observation is recorded twice. Once into a cumulative [`HistogramState`], which is what gets encoded,
+/// and once into a ring of [`HistogramStateInner`]s that only cover the most recent [`Window`].
+pub struct WindowedHistogramState<const N: usize> {
+    /// The cumulative histogram, as reported to prometheus.
+    pub histogram: HistogramState<N>,
+    /// The ring of recent histograms.
+    /// The read lock is acquired for observations.
+    /// The write lock is acquired when the ring is rotated.
+    ring: RwLock<Ring<N>>,
+}
+
+impl<const N: usize> Default for WindowedHistogramState<N> {
+    fn default() -> Self {
+        Self {
+            histogram: HistogramState::default(),
+            ring: RwLock::new(Ring {
+                slots: Box::default(),
+                current: 0,
+                rotated_at: Instant::now(),
+            }),
+        }
+    }
+}
+
+/// A shared ref to an individual windowed histogram
+pub type WindowedHistogramLockGuard<'a, const N: usize> =
+    MetricLockGuard<'a, WindowedHistogramState<N>>;
+/// A unique ref to an individual windowed histogram
+pub type WindowedHistogramMut<'a, const N: usize> = MetricMut<'a, WindowedHistogramState<N>>;
+
+impl<const N: usize> MetricType for WindowedHistogramState<N> {
+    type Metadata = Window<N>;
+}
+
+/// `Window` defines the buckets used in a [`WindowedHistogram`], and how long observations are kept for
+/// when computing [`percentile`](WindowedHistogram::percentile)s.
+///
+/// The window is made up of `slots` intervals. Each time an interval passes, the oldest interval is discarded.
+/// This means the percentiles cover somewhere between `(slots - 1) * interval` and `slots * interval` of observations.
+pub struct Window<const N: usize> {
+    thresholds: Thresholds<N>,
+    interval: Duration,
+    slots: usize,
+}
+
+impl<const N: usize> Window<N> {
+    /// Create the window, from the bucket thresholds and how many intervals it is made up of.
+    ///
+    /// # Panics
+    /// The function panics if `interval` is zero, or if `slots` is zero.
+    pub fn new(thresholds: Thresholds<N>, interval: Duration, slots: usize) -> Self {
+        assert!(!interval.is_zero(), "window interval must not be zero");
+        assert!(slots > 0, "window must have at least one slot");
+        Self {
+            thresholds,
+            interval,
+            slots,
+        }
+    }
+
+    /// View the bucket thresholds
+    pub fn thresholds(&self) -> &Thresholds<N> {
+        &self.thresholds
+    }
+
+    /// The interval after which the ring is rotated
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// The number of intervals that make up the window
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+}
+
+struct Ring<const N: usize> {
+    slots: Box<[HistogramStateInner<N>]>,
+    current: usize,
+    rotated_at: Instant,
+}
+
+impl<const N: usize> Ring<N> {
+    fn is_expired(&self, window: &Window<N>, now: Instant) -> bool {
+        self.slots.is_empty() || now.saturating_duration_since(self.rotated_at) >= window.interval
+    }
+
+    /// Discard the slots of any intervals that have passed since the last rotation.
+    fn rotate(&mut self, window: &Window<N>, now: Instant) {
+        if self.slots.is_empty() {
+            self.slots = (0..window.slots).map(|_| Default::default()).collect();
+            self.rotated_at = now;
+            return;
+        }
+
+        let elapsed = now.saturating_duration_since(self.rotated_at);
+        let passed = (elapsed.as_nanos() / window.interval.as_nanos()) as usize;
+        if passed >= self.slots.len() {
+            self.slots.iter_mut().for_each(|s| *s = Default::default());
+            self.rotated_at = now;
+        } else {
+            for _ in 0..passed {
+                self.current = (self.current + 1) % self.slots.len();
+                self.slots[self.current] = Default::default();
+            }
+            self.rotated_at += window.interval * passed as u32;
+        }
+    }
+
+    fn percentile(&self, window: &Window<N>, q: f64) -> f64 {
+        let mut buckets = [0; N];
+        let mut inf = 0;
+        for slot in &*self.slots {
+            for (b, s) in buckets.iter_mut().zip(&slot.buckets) {
+                *b += s.load(Ordering::Relaxed);
+            }
+            inf += slot.inf.load(Ordering::Relaxed);
+        }
+        window.thresholds.quantile(&buckets, inf, q)
+    }
+}
+
+impl<const N: usize> WindowedHistogramState<N> {
+    fn observe_at(&self, window: &Window<N>, x: f64, now: Instant) {
+        let bucket = window.thresholds.get().partition_point(|le| x > *le);
+        self.histogram.observe(bucket, x);
+
+        let mut ring = self.ring.read();
+        if ring.is_expired(window, now) {
+            drop(ring);
+            self.ring.write().rotate(window, now);
+            ring = self.ring.read();
+        }
+        ring.slots[ring.current].observe(bucket, x);
+    }
+
+    fn observe_at_mut(&mut self, window: &Window<N>, x: f64, now: Instant) {
+        let bucket = window.thresholds.get().partition_point(|le| x > *le);
+        self.histogram.observe_mut(bucket, x);
+
+        let ring = self.ring.get_mut();
+        if ring.is_expired(window, now) {
+            ring.rotate(window, now);
+        }
+        let current = ring.current;
+        ring.slots[current].observe_mut(bucket, x);
+    }
+
+    fn percentile_at(&self, window: &Window<N>, q: f64, now: Instant) -> f64 {
+        let mut ring = self.ring.read();
+        if ring.is_expired(window, now) {
+            drop(ring);
+            self.ring.write().rotate(window, now);
+            ring = self.ring.read();
+        }
+        ring.percentile(window, q)
+    }
+}
+
+impl<const N: usize> WindowedHistogramLockGuard<'_, N> {
+    /// Add a single observation to the [`WindowedHistogram`].
+    pub fn observe(self, x: f64) {
+        self.observe_at(self.metadata(), x, Instant::now());
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().thresholds().unit(), duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+
+    /// Estimate the `q`-quantile of the observations within the last [`Window`], where `q` is within `0..=1`.
+    ///
+    /// The estimate is linearly interpolated within the bucket that the quantile falls into.
+    /// The result is `NaN` if there were no observations within the window.
+    pub fn percentile(self, q: f64) -> f64 {
+        self.percentile_at(self.metadata(), q, Instant::now())
+    }
+}
+
+impl<const N: usize> WindowedHistogramMut<'_, N> {
+    /// Add a single observation to the [`WindowedHistogram`].
+    pub fn observe(mut self, x: f64) {
+        let MetricMut(metric, metadata) = &mut self;
+        metric.observe_at_mut(metadata, x, Instant::now());
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(self, duration: Duration) {
+        let x = duration_in(self.metadata().thresholds().unit(), duration);
+        self.observe(x);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(self, since: Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(d);
+        d
+    }
+
+    /// Estimate the `q`-quantile of the observations within the last [`Window`], where `q` is within `0..=1`.
+    ///
+    /// The estimate is linearly interpolated within the bucket that the quantile falls into.
+    /// The result is `NaN` if there were no observations within the window.
+    pub fn percentile(self, q: f64) -> f64 {
+        self.percentile_at(self.metadata(), q, Instant::now())
+    }
+}
+
+impl<const N: usize> WindowedHistogram<N> {
+    /// Add a single observation to the [`WindowedHistogram`].
+    pub fn observe(&self, x: f64) {
+        self.get_metric().observe(x);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, duration: Duration) {
+        self.get_metric().observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(&self, since: Instant) -> Duration {
+        self.get_metric().observe_duration_since(since)
+    }
+
+    /// Estimate the `q`-quantile of the observations within the last [`Window`], where `q` is within `0..=1`.
+    ///
+    /// The estimate is linearly interpolated within the bucket that the quantile falls into.
+    /// The result is `NaN` if there were no observations within the window.
+    pub fn percentile(&self, q: f64) -> f64 {
+        self.get_metric().percentile(q)
+    }
+}
+
+impl<L: LabelGroupSet, const N: usize> WindowedHistogramVec<L, N> {
+    /// Add a single observation to the [`WindowedHistogram`], keyed by the label group.
+    pub fn observe(&self, label: L::Group<'_>, y: f64) {
+        self.get_metric(self.with_labels(label)).observe(y);
+    }
+
+    /// Observe the duration, converted into the [`Unit`] of the histogram. Seconds are used if there is no unit.
+    pub fn observe_duration(&self, label: L::Group<'_>, duration: Duration) {
+        self.get_metric(self.with_labels(label))
+            .observe_duration(duration);
+    }
+
+    /// Observe the duration since the given instant. See `observe_duration`
+    pub fn observe_duration_since(&self, label: L::Group<'_>, since: Instant) -> Duration {
+        let d = since.elapsed();
+        self.observe_duration(label, d);
+        d
+    }
+
+    /// Estimate the `q`-quantile of the observations within the last [`Window`], keyed by the label group.
+    ///
+    /// See [`WindowedHistogram::percentile`]
+    pub fn percentile(&self, label: L::Group<'_>, q: f64) -> f64 {
+        self.get_metric(self.with_labels(label)).percentile(q)
+    }
+}
+
+impl<const N: usize> UnitMetadata for Window<N> {
+    fn with_unit(self, unit: Unit) -> Self {
+        Self {
+            thresholds: UnitMetadata::with_unit(self.thresholds, unit),
+            ..self
+        }
+    }
+    fn matches_unit(&self, unit: Unit) -> bool {
+        self.thresholds.matches_unit(unit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::metric::histogram::Thresholds;
+
+    use super::{Window, WindowedHistogramState};
+
+    #[test]
+    fn percentile_over_window() {
+        let window = Window::new(
+            Thresholds::<4>::with_buckets([1.0, 2.0, 3.0, 4.0]),
+            Duration::from_secs(10),
+            3,
+        );
+        let histogram = WindowedHistogramState::default();
+        let start = Instant::now();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(histogram.percentile_at(&window, 0.5, at(0)).is_nan());
+
+        // 100 observations in the `2.0..3.0` bucket
+        for _ in 0..100 {
+            histogram.observe_at(&window, 2.5, at(0));
+        }
+        assert_eq!(histogram.percentile_at(&window, 0.5, at(1)), 2.5);
+
+        // 100 observations in the `0.0..1.0` bucket
+        for _ in 0..100 {
+            histogram.observe_at(&window, 0.5, at(15));
+        }
+        assert_eq!(histogram.percentile_at(&window, 0.25, at(15)), 0.5);
+        assert_eq!(histogram.percentile_at(&window, 0.75, at(25)), 2.5);
+
+        // the first observations have left the window
+        assert_eq!(histogram.percentile_at(&window, 0.5, at(30)), 0.5);
+        assert_eq!(histogram.percentile_at(&window, 1.0, at(30)), 1.0);
+
+        // everything has left the window
+        assert!(histogram.percentile_at(&window, 0.5, at(120)).is_nan());
+
+        // the cumulative histogram keeps everything
+        let (buckets, inf, sum) = histogram.histogram.sample();
+        assert_eq!(buckets, [100, 0, 100, 0]);
+        assert_eq!(inf, 0);
+        assert_eq!(sum, 300.0);
+    }
+}
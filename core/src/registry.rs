@@ -0,0 +1,389 @@
+//! A registry of metric groups which can be added and removed at runtime.
+//!
+//! Most applications should define their metrics statically with `#[derive(MetricGroup)]`.
+//! A [`Registry`] is useful when metrics are only known at runtime, such as metrics for plugins that are loaded
+//! and unloaded while the application is running.
+//!
+//! ```
+//! use measured::{registry::Registry, text::BufferedTextEncoder, Counter, MetricGroup};
+//!
+//! #[derive(MetricGroup, Default)]
+//! struct PluginMetrics {
+//!     /// number of events handled by the plugin
+//!     plugin_events_total: Counter,
+//! }
+//!
+//! let registry: Registry = Registry::new();
+//! registry.register("plugin", Box::new(PluginMetrics::default())).unwrap();
+//!
+//! // the same families cannot be registered twice
+//! assert!(registry.register("plugin_copy", Box::new(PluginMetrics::default())).is_err());
+//!
+//! let mut enc = BufferedTextEncoder::new();
+//! registry.collect_group_into(&mut enc).unwrap();
+//!
+//! registry.unregister("plugin").unwrap();
+//! ```
+//!
+//! By default, the registry holds collectors that can be encoded with [`BufferedTextEncoder`].
+//! To support other encoders, define a trait which combines the encoders you need and use it as the collector type.
+//! Registering a collector also needs [`FamilyNames`], to find which metric families it writes.
+//!
+//! ```
+//! use measured::{
+//!     json::JsonEncoder,
+//!     metric::group::MetricGroup,
+//!     registry::{FamilyNames, Registry},
+//! };
+//!
+//! trait Collector: MetricGroup<FamilyNames> + MetricGroup<JsonEncoder<Vec<u8>>> + Send + Sync {}
+//! impl<T> Collector for T where T: MetricGroup<FamilyNames> + MetricGroup<JsonEncoder<Vec<u8>>> + Send + Sync {}
+//!
+//! let registry = Registry::<dyn Collector>::new();
+//! ```
+
+use std::convert::Infallible;
+
+use parking_lot::RwLock;
+
+use crate::{
+    label::LabelGroup,
+    metric::{
+        group::{Encoding, MetricGroup},
+        name::MetricNameEncoder,
+        MetricEncoding, MetricType,
+    },
+    text::BufferedTextEncoder,
+    validate::name_to_string,
+};
+
+/// A collector which can be registered in a [`Registry`] and encoded as text
+pub trait Collector:
+    MetricGroup<FamilyNames> + MetricGroup<BufferedTextEncoder> + Send + Sync
+{
+}
+
+impl<T> Collector for T where
+    T: MetricGroup<FamilyNames> + MetricGroup<BufferedTextEncoder> + Send + Sync
+{
+}
+
+/// The default collector type of a [`Registry`]
+pub type DynCollector = dyn Collector;
+
+/// An [`Encoding`] which records the names of the metric families a group writes, without encoding any values.
+#[derive(Default)]
+pub struct FamilyNames {
+    names: Vec<String>,
+}
+
+impl FamilyNames {
+    /// Create a new empty set of names
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The recorded metric family names, in the order they were first written
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// Record a metric family name
+    pub fn insert(&mut self, name: impl MetricNameEncoder) {
+        let name = name_to_string(&name);
+        if !self.names.contains(&name) {
+            self.names.push(name);
+        }
+    }
+}
+
+impl Encoding for FamilyNames {
+    type Err = Infallible;
+
+    fn write_help(&mut self, name: impl MetricNameEncoder, _help: &str) -> Result<(), Infallible> {
+        self.insert(name);
+        Ok(())
+    }
+}
+
+impl<M: MetricType> MetricEncoding<FamilyNames> for M {
+    fn write_type(name: impl MetricNameEncoder, enc: &mut FamilyNames) -> Result<(), Infallible> {
+        enc.insert(name);
+        Ok(())
+    }
+    fn collect_into(
+        &self,
+        _metadata: &Self::Metadata,
+        _labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        _enc: &mut FamilyNames,
+    ) -> Result<(), Infallible> {
+        Ok(())
+    }
+}
+
+/// A set of named metric groups, which can be registered and unregistered at runtime.
+///
+/// The registry itself is a [`MetricGroup`], which collects all registered groups in the order they were registered.
+pub struct Registry<C: ?Sized = DynCollector> {
+    entries: RwLock<Vec<Entry<C>>>,
+}
+
+struct Entry<C: ?Sized> {
+    name: String,
+    families: Vec<String>,
+    collector: Box<C>,
+}
+
+/// An error returned when a collector could not be registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegisterError {
+    /// A collector with the same name was already registered
+    DuplicateName(String),
+    /// The collector writes a metric family that an already registered collector also writes
+    DuplicateFamily {
+        /// The name of the metric family
+        family: String,
+        /// The name of the already registered collector
+        registered: String,
+    },
+}
+
+impl core::fmt::Display for RegisterError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            RegisterError::DuplicateName(name) => {
+                write!(f, "a collector named {name:?} is already registered")
+            }
+            RegisterError::DuplicateFamily { family, registered } => write!(
+                f,
+                "metric family {family:?} is already registered by collector {registered:?}"
+            ),
+        }
+    }
+}
+
+impl std::error::Error for RegisterError {}
+
+impl<C: ?Sized> Default for Registry<C> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<C: ?Sized> Registry<C> {
+    /// Create a new empty registry
+    pub fn new() -> Self {
+        Self {
+            entries: RwLock::new(vec![]),
+        }
+    }
+
+    /// Register a collector with the given name.
+    ///
+    /// The collector is visited once with [`FamilyNames`] to find which metric families it writes, without encoding
+    /// any values. Registering fails if the name is already taken, or if any of those families are already written
+    /// by another registered collector.
+    ///
+    /// Metric families are written even if they have no samples yet, so those are found too. A collector which only
+    /// decides at collection time which families to write, such as a
+    /// [`FederatedGroup`](crate::text::federate::FederatedGroup), is only checked against the families it writes now.
+    pub fn register(&self, name: impl Into<String>, collector: Box<C>) -> Result<(), RegisterError>
+    where
+        C: MetricGroup<FamilyNames>,
+    {
+        let name = name.into();
+
+        let mut enc = FamilyNames::new();
+        let Ok(()) = collector.collect_group_into(&mut enc);
+        let families = enc.names;
+
+        let mut entries = self.entries.write();
+        for entry in &*entries {
+            if entry.name == name {
+                return Err(RegisterError::DuplicateName(name));
+            }
+            if let Some(family) = families.iter().find(|f| entry.families.contains(f)) {
+                return Err(RegisterError::DuplicateFamily {
+                    family: family.clone(),
+                    registered: entry.name.clone(),
+                });
+            }
+        }
+
+        entries.push(Entry {
+            name,
+            families,
+            collector,
+        });
+        Ok(())
+    }
+
+    /// Unregister the collector with the given name, returning it if it was registered.
+    pub fn unregister(&self, name: &str) -> Option<Box<C>> {
+        let mut entries = self.entries.write();
+        let index = entries.iter().position(|entry| entry.name == name)?;
+        Some(entries.remove(index).collector)
+    }
+
+    /// Whether a collector with the given name is registered
+    pub fn contains(&self, name: &str) -> bool {
+        self.entries.read().iter().any(|entry| entry.name == name)
+    }
+
+    /// The names of all registered collectors, in the order they were registered
+    pub fn names(&self) -> Vec<String> {
+        self.entries
+            .read()
+            .iter()
+            .map(|entry| entry.name.clone())
+            .collect()
+    }
+}
+
+impl<Enc: Encoding, C: ?Sized + MetricGroup<Enc>> MetricGroup<Enc> for Registry<C> {
+    fn collect_group_into(&self, enc: &mut Enc) -> Result<(), Enc::Err> {
+        for entry in &*self.entries.read() {
+            entry.collector.collect_group_into(enc)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        json::JsonEncoder, label::StaticLabelSet, text::BufferedTextEncoder, Counter, CounterVec,
+        FixedCardinalityLabel, Gauge, LabelGroup, MetricGroup,
+    };
+
+    use super::{FamilyNames, RegisterError, Registry};
+
+    #[derive(MetricGroup, Default)]
+    #[metric(crate = crate)]
+    struct Plugin {
+        /// number of events handled
+        events_total: Counter,
+    }
+
+    #[derive(MetricGroup, Default)]
+    #[metric(crate = crate)]
+    struct Other {
+        /// number of active connections
+        connections: Gauge,
+    }
+
+    trait Collector:
+        MetricGroup<FamilyNames>
+        + MetricGroup<BufferedTextEncoder>
+        + MetricGroup<JsonEncoder<Vec<u8>>>
+        + Send
+        + Sync
+    {
+    }
+    impl<T> Collector for T where
+        T: MetricGroup<FamilyNames>
+            + MetricGroup<BufferedTextEncoder>
+            + MetricGroup<JsonEncoder<Vec<u8>>>
+            + Send
+            + Sync
+    {
+    }
+
+    #[test]
+    fn register_and_unregister() {
+        let registry = Registry::<dyn Collector>::new();
+
+        registry
+            .register("plugin", Box::new(Plugin::default()))
+            .unwrap();
+        registry
+            .register("other", Box::new(Other::default()))
+            .unwrap();
+
+        assert_eq!(
+            registry.register("plugin", Box::new(Other::default())),
+            Err(RegisterError::DuplicateName("plugin".to_owned()))
+        );
+        assert_eq!(
+            registry.register("plugin2", Box::new(Plugin::default())),
+            Err(RegisterError::DuplicateFamily {
+                family: "events_total".to_owned(),
+                registered: "plugin".to_owned()
+            })
+        );
+        assert_eq!(registry.names(), ["plugin", "other"]);
+
+        let mut enc = BufferedTextEncoder::new();
+        registry.collect_group_into(&mut enc).unwrap();
+        assert_eq!(
+            enc.finish(),
+            r#"# HELP events_total number of events handled
+# TYPE events_total counter
+events_total 0
+
+# HELP connections number of active connections
+# TYPE connections gauge
+connections 0
+"#
+        );
+
+        let mut enc = JsonEncoder::new(vec![]);
+        registry.collect_group_into(&mut enc).unwrap();
+        enc.flush().unwrap();
+        assert!(String::from_utf8(enc.writer)
+            .unwrap()
+            .contains("events_total"));
+
+        assert!(registry.unregister("plugin").is_some());
+        assert!(registry.unregister("plugin").is_none());
+        assert!(!registry.contains("plugin"));
+
+        // the families are free to be registered again
+        registry
+            .register("plugin2", Box::new(Plugin::default()))
+            .unwrap();
+    }
+
+    #[derive(FixedCardinalityLabel, Clone, Copy)]
+    #[label(crate = crate)]
+    enum Kind {
+        Read,
+        Write,
+    }
+
+    #[derive(LabelGroup)]
+    #[label(crate = crate, set = OpSet)]
+    struct Op {
+        kind: Kind,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    struct Ops {
+        /// number of events handled, by kind
+        events_total: CounterVec<OpSet>,
+    }
+
+    #[test]
+    fn families_without_samples() {
+        let ops = Ops {
+            events_total: CounterVec::with_label_set(OpSet {
+                kind: StaticLabelSet::new(),
+            }),
+        };
+        let mut names = FamilyNames::new();
+        ops.collect_group_into(&mut names).unwrap();
+        assert_eq!(names.names(), ["events_total"]);
+
+        let registry: Registry = Registry::new();
+        registry.register("ops", Box::new(ops)).unwrap();
+        assert_eq!(
+            registry.register("plugin", Box::new(Plugin::default())),
+            Err(RegisterError::DuplicateFamily {
+                family: "events_total".to_owned(),
+                registered: "ops".to_owned()
+            })
+        );
+    }
+}
@@ -6,25 +6,56 @@ use std::{
 };
 
 use bytes::{BufMut, Bytes, BytesMut};
-use memchr::memchr3_iter;
+use memchr::{memchr2_iter, memchr3_iter};
 
 use crate::{
-    label::{LabelGroup, LabelGroupVisitor, LabelName, LabelValue, LabelVisitor},
+    label::{
+        FixedCardinalityLabel, LabelGroup, LabelGroupVisitor, LabelName, LabelValue, LabelVisitor,
+    },
     metric::{
-        counter::CounterState,
+        counter::{write_counter, CounterState, ExemplarCounterState, ShardedCounterState},
         gauge::{FloatGaugeState, GaugeState},
         group::{Encoding, MetricValue},
-        histogram::{HistogramState, Thresholds},
-        name::{Bucket, Count, MetricNameEncoder, Sum},
+        histogram::{ExemplarHistogramState, HistogramState, Thresholds},
+        info::{InfoLabels, InfoState},
+        name::{Bucket, Count, Info, MetricNameEncoder, Sum},
+        native_histogram::{NativeHistogramConfig, NativeHistogramState},
+        state_set::{state_set_label_name, StateSetLabel, StateSetState},
+        summary::{Quantiles, SummaryState},
+        untyped::UntypedState,
+        windowed_histogram::{Window, WindowedHistogramState},
         MetricEncoding,
     },
 };
 
+use federate::{FederatedCounterState, FederatedHistogramState, FederatedSummaryState};
+
+pub mod compress;
+pub mod federate;
+pub mod openmetrics;
+pub mod parse;
+
+/// The content type of the prometheus text format, to be used in HTTP responses.
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4; charset=utf-8";
+
 /// The prometheus text encoder helper
 pub struct TextEncoder<W> {
     state: State,
     /// The inner writer for this text encoder.
     pub writer: W,
+    help: PendingHelp,
+}
+
+/// A help line which is waiting for its type line.
+///
+/// Some metric types add a suffix to the family name, which the help line has to match.
+/// The buffers are re-used between help lines.
+#[derive(Default)]
+struct PendingHelp {
+    pending: bool,
+    name: Vec<u8>,
+    /// The escaped help text
+    text: Vec<u8>,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -33,8 +64,11 @@ enum State {
     Metrics,
 }
 
-/// Prometheus only supports these 5 types of metrics
-#[derive(Clone, Copy, Debug)]
+/// Prometheus only supports the first 5 types of metrics.
+///
+/// [`Info`](MetricType::Info) and [`StateSet`](MetricType::StateSet) are only supported by OpenMetrics,
+/// and are written as gauges in the prometheus text format. Info metrics keep their `_info` suffix.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum MetricType {
     /// Corresponds to [`Counter`](crate::Counter)
     Counter,
@@ -42,10 +76,14 @@ pub enum MetricType {
     Histogram,
     /// Corresponds to [`Gauge`](crate::Gauge)
     Gauge,
-    /// Not currently supported
+    /// Corresponds to [`Summary`](crate::Summary)
     Summary,
-    /// Not currently supported
+    /// Corresponds to [`Untyped`](crate::Untyped)
     Untyped,
+    /// Corresponds to [`Info`](crate::Info)
+    Info,
+    /// Corresponds to [`StateSet`](crate::StateSet)
+    StateSet,
 }
 
 impl<W: Write> Encoding for TextEncoder<W> {
@@ -57,16 +95,17 @@ impl<W: Write> Encoding for TextEncoder<W> {
         name: impl MetricNameEncoder,
         help: &str,
     ) -> Result<(), std::io::Error> {
+        self.write_pending_help()?;
         if self.state == State::Metrics {
             self.write_line()?;
         }
         self.state = State::Info;
 
-        self.writer.write_all(b"# HELP ")?;
-        name.encode_utf8(&mut self.writer)?;
-        self.writer.write_all(b" ")?;
-        self.writer.write_all(help.as_bytes())?;
-        self.writer.write_all(b"\n")?;
+        self.help.name.clear();
+        self.help.text.clear();
+        name.encode_utf8(&mut self.help.name)?;
+        write_help_str_value(help, &mut self.help.text)?;
+        self.help.pending = true;
         Ok(())
     }
 }
@@ -79,11 +118,13 @@ impl<W: Write> TextEncoder<W> {
         Self {
             state: State::Info,
             writer: w,
+            help: PendingHelp::default(),
         }
     }
 
     /// Finish the text encoding and extract the bytes to send in a HTTP response.
     pub fn flush(&mut self) -> std::io::Result<()> {
+        self.write_pending_help()?;
         self.state = State::Info;
         self.writer.flush()
     }
@@ -92,6 +133,18 @@ impl<W: Write> TextEncoder<W> {
         self.writer.write_all(b"\n")
     }
 
+    /// Write the help line that is waiting for its type line, if there is one
+    fn write_pending_help(&mut self) -> std::io::Result<()> {
+        if !std::mem::take(&mut self.help.pending) {
+            return Ok(());
+        }
+        self.writer.write_all(b"# HELP ")?;
+        self.writer.write_all(&self.help.name)?;
+        self.writer.write_all(b" ")?;
+        self.writer.write_all(&self.help.text)?;
+        self.writer.write_all(b"\n")
+    }
+
     /// Write the type line for a metric
     pub fn write_type(
         &mut self,
@@ -102,13 +155,21 @@ impl<W: Write> TextEncoder<W> {
             self.write_line()?;
         }
         self.state = State::Info;
+        if self.help.pending {
+            // the help line is written with the same family name as the type line
+            self.help.name.clear();
+            name.encode_utf8(&mut self.help.name)?;
+            self.write_pending_help()?;
+        }
 
         self.writer.write_all(b"# TYPE ")?;
         name.encode_utf8(&mut self.writer)?;
         match typ {
             MetricType::Counter => self.writer.write_all(b" counter\n"),
             MetricType::Histogram => self.writer.write_all(b" histogram\n"),
-            MetricType::Gauge => self.writer.write_all(b" gauge\n"),
+            MetricType::Gauge | MetricType::Info | MetricType::StateSet => {
+                self.writer.write_all(b" gauge\n")
+            }
             MetricType::Summary => self.writer.write_all(b" summary\n"),
             MetricType::Untyped => self.writer.write_all(b" untyped\n"),
         }
@@ -121,83 +182,148 @@ impl<W: Write> TextEncoder<W> {
         labels: impl LabelGroup,
         value: MetricValue,
     ) -> Result<(), std::io::Error> {
-        struct Visitor<'a, W> {
-            writer: &'a mut W,
+        self.write_pending_help()?;
+        self.state = State::Metrics;
+        write_metric_value(&mut self.writer, name, labels, value)
+    }
+}
+
+/// Write a single sample line. Shared by the text based encoders
+fn write_metric_value(
+    writer: &mut impl Write,
+    name: impl MetricNameEncoder,
+    labels: impl LabelGroup,
+    value: MetricValue,
+) -> Result<(), std::io::Error> {
+    write_sample(writer, name, labels, value)?;
+    writer.write_all(b"\n")
+}
+
+/// Write the name, labels and value of a sample, without the trailing newline.
+fn write_sample(
+    writer: &mut impl Write,
+    name: impl MetricNameEncoder,
+    labels: impl LabelGroup,
+    value: MetricValue,
+) -> Result<(), std::io::Error> {
+    name.encode_utf8(writer)?;
+    write_labels(writer, labels)?;
+    writer.write_all(b" ")?;
+    match value {
+        MetricValue::Int(x) => writer.write_all(itoa::Buffer::new().format(x).as_bytes()),
+        MetricValue::Float(x) => write_float_value(x, writer),
+    }
+}
+
+/// Write the labels enclosed in braces, if there are any. Returns whether any labels were written.
+fn write_labels(writer: &mut impl Write, labels: impl LabelGroup) -> Result<bool, std::io::Error> {
+    struct Visitor<'a, W> {
+        writer: &'a mut W,
+    }
+    impl<W: Write> LabelVisitor for Visitor<'_, W> {
+        type Output = Result<(), std::io::Error>;
+        fn write_int(self, x: i64) -> Result<(), std::io::Error> {
+            self.write_str(itoa::Buffer::new().format(x))
         }
-        impl<W: Write> LabelVisitor for Visitor<'_, W> {
-            type Output = Result<(), std::io::Error>;
-            fn write_int(self, x: i64) -> Result<(), std::io::Error> {
-                self.write_str(itoa::Buffer::new().format(x))
-            }
 
-            fn write_float(self, x: f64) -> Result<(), std::io::Error> {
-                if x.is_infinite() {
-                    if x.is_sign_positive() {
-                        self.write_str("+Inf")
-                    } else {
-                        self.write_str("-Inf")
-                    }
-                } else if x.is_nan() {
-                    self.write_str("NaN")
+        fn write_float(self, x: f64) -> Result<(), std::io::Error> {
+            if x.is_infinite() {
+                if x.is_sign_positive() {
+                    self.write_str("+Inf")
                 } else {
-                    self.write_str(ryu::Buffer::new().format(x))
+                    self.write_str("-Inf")
                 }
-            }
-
-            fn write_str(self, x: &str) -> Result<(), std::io::Error> {
-                self.writer.write_all(b"=\"")?;
-                write_label_str_value(x, &mut *self.writer)?;
-                self.writer.write_all(b"\"")?;
-                Ok(())
+            } else if x.is_nan() {
+                self.write_str("NaN")
+            } else {
+                self.write_str(ryu::Buffer::new().format(x))
             }
         }
 
-        struct GroupVisitor<'a, W> {
-            first: bool,
-            writer: &'a mut W,
+        fn write_str(self, x: &str) -> Result<(), std::io::Error> {
+            self.writer.write_all(b"=\"")?;
+            write_label_str_value(x, &mut *self.writer)?;
+            self.writer.write_all(b"\"")?;
+            Ok(())
         }
-        impl<W: Write> LabelGroupVisitor for GroupVisitor<'_, W> {
-            type Output = Result<(), std::io::Error>;
-            fn write_value(
-                &mut self,
-                name: &LabelName,
-                x: &impl LabelValue,
-            ) -> Result<(), std::io::Error> {
-                if self.first {
-                    self.first = false;
-                    self.writer.write_all(b"{")?;
-                } else {
-                    self.writer.write_all(b",")?;
-                }
-                self.writer.write_all(name.as_str().as_bytes())?;
-                x.visit(Visitor {
-                    writer: self.writer,
-                })
+    }
+
+    struct GroupVisitor<'a, W> {
+        first: bool,
+        writer: &'a mut W,
+    }
+    impl<W: Write> LabelGroupVisitor for GroupVisitor<'_, W> {
+        type Output = Result<(), std::io::Error>;
+        fn write_value(
+            &mut self,
+            name: &LabelName,
+            x: &impl LabelValue,
+        ) -> Result<(), std::io::Error> {
+            if self.first {
+                self.first = false;
+                self.writer.write_all(b"{")?;
+            } else {
+                self.writer.write_all(b",")?;
             }
+            self.writer.write_all(name.as_str().as_bytes())?;
+            x.visit(Visitor {
+                writer: self.writer,
+            })
         }
+    }
 
-        self.state = State::Metrics;
-        name.encode_utf8(&mut self.writer)?;
+    let mut visitor = GroupVisitor {
+        first: true,
+        writer: &mut *writer,
+    };
+    labels.visit_values(&mut visitor);
+    let any = !visitor.first;
+    if any {
+        writer.write_all(b"}")?;
+    }
+    Ok(any)
+}
 
-        let mut visitor = GroupVisitor {
-            first: true,
-            writer: &mut self.writer,
-        };
-        labels.visit_values(&mut visitor);
-        if !visitor.first {
-            self.writer.write_all(b"}")?;
-        }
-        self.writer.write_all(b" ")?;
-        match value {
-            MetricValue::Int(x) => self
-                .writer
-                .write_all(itoa::Buffer::new().format(x).as_bytes())?,
-            MetricValue::Float(x) => self
-                .writer
-                .write_all(ryu::Buffer::new().format(x).as_bytes())?,
+fn write_float_value(x: f64, writer: &mut impl Write) -> Result<(), std::io::Error> {
+    if x.is_infinite() {
+        if x.is_sign_positive() {
+            writer.write_all(b"+Inf")
+        } else {
+            writer.write_all(b"-Inf")
         }
-        self.writer.write_all(b"\n")?;
-        Ok(())
+    } else if x.is_nan() {
+        writer.write_all(b"NaN")
+    } else {
+        writer.write_all(ryu::Buffer::new().format(x).as_bytes())
+    }
+}
+
+struct F64(f64);
+impl LabelValue for F64 {
+    fn visit<V: LabelVisitor>(&self, v: V) -> V::Output {
+        v.write_float(self.0)
+    }
+}
+
+struct HistogramLabelLe {
+    le: f64,
+}
+
+impl LabelGroup for HistogramLabelLe {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        const LE: &LabelName = LabelName::from_str("le");
+        v.write_value(LE, &F64(self.le));
+    }
+}
+
+struct SummaryLabelQuantile {
+    quantile: f64,
+}
+
+impl LabelGroup for SummaryLabelQuantile {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        const QUANTILE: &LabelName = LabelName::from_str("quantile");
+        v.write_value(QUANTILE, &F64(self.quantile));
     }
 }
 
@@ -215,25 +341,7 @@ impl<W: Write, const N: usize> MetricEncoding<TextEncoder<W>> for HistogramState
         name: impl MetricNameEncoder,
         enc: &mut TextEncoder<W>,
     ) -> Result<(), std::io::Error> {
-        struct F64(f64);
-        impl LabelValue for F64 {
-            fn visit<V: LabelVisitor>(&self, v: V) -> V::Output {
-                v.write_float(self.0)
-            }
-        }
-
-        struct HistogramLabelLe {
-            le: f64,
-        }
-
-        impl LabelGroup for HistogramLabelLe {
-            fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
-                const LE: &LabelName = LabelName::from_str("le");
-                v.write_value(LE, &F64(self.le));
-            }
-        }
-
-        let (buckets, inf, sum) = self.inner.write().sample();
+        let (buckets, inf, sum) = self.sample();
         let mut val = 0;
 
         #[allow(clippy::needless_range_loop)]
@@ -268,6 +376,121 @@ impl<W: Write, const N: usize> MetricEncoding<TextEncoder<W>> for HistogramState
     }
 }
 
+/// The prometheus text format does not support exemplars, so only the histogram is written.
+impl<W: Write, const N: usize> MetricEncoding<TextEncoder<W>> for ExemplarHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        self.histogram.collect_into(metadata, labels, name, enc)
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<TextEncoder<W>> for WindowedHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Window<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        self.histogram
+            .collect_into(metadata.thresholds(), labels, name, enc)
+    }
+}
+
+/// The text format cannot represent the sparse buckets, so only the `+Inf` bucket, sum and count are written.
+impl<W: Write> MetricEncoding<TextEncoder<W>> for NativeHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        _m: &NativeHistogramConfig,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.inner.write().sample();
+
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Bucket),
+            labels
+                .by_ref()
+                .compose_with(HistogramLabelLe { le: f64::INFINITY }),
+            MetricValue::Int(sample.count as i64),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(sample.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(sample.count as i64),
+        )?;
+        Ok(())
+    }
+}
+
+impl<W: Write> MetricEncoding<TextEncoder<W>> for SummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Quantiles,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let (values, count, sum) = self.inner.lock().sample(metadata);
+
+        for (&(quantile, _), value) in metadata.get().iter().zip(values) {
+            enc.write_metric_value(
+                &name,
+                labels
+                    .by_ref()
+                    .compose_with(SummaryLabelQuantile { quantile }),
+                MetricValue::Float(value),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(count as i64),
+        )?;
+        Ok(())
+    }
+}
+
 impl<W: Write> MetricEncoding<TextEncoder<W>> for CounterState {
     fn write_type(
         name: impl MetricNameEncoder,
@@ -290,6 +513,47 @@ impl<W: Write> MetricEncoding<TextEncoder<W>> for CounterState {
     }
 }
 
+impl<W: Write> MetricEncoding<TextEncoder<W>> for ShardedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_counter(enc, name, labels, self.get())
+    }
+}
+
+/// The prometheus text format does not support exemplars, so only the counter value is written.
+impl<W: Write> MetricEncoding<TextEncoder<W>> for ExemplarCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(
+            &name,
+            labels,
+            MetricValue::Int(self.count.load(core::sync::atomic::Ordering::Relaxed) as i64),
+        )
+    }
+}
+
 impl<W: Write> MetricEncoding<TextEncoder<W>> for GaugeState {
     fn write_type(
         name: impl MetricNameEncoder,
@@ -330,6 +594,178 @@ impl<W: Write> MetricEncoding<TextEncoder<W>> for FloatGaugeState {
     }
 }
 
+impl<W: Write> MetricEncoding<TextEncoder<W>> for UntypedState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Untyped)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(&name, labels, MetricValue::Float(self.value.get()))
+    }
+}
+
+/// Counters are written as integers, unless they have a fractional value
+impl<W: Write> MetricEncoding<TextEncoder<W>> for FederatedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(&name, labels, count_value(self.value))
+    }
+}
+
+impl<W: Write> MetricEncoding<TextEncoder<W>> for FederatedHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        for &(le, count) in &self.buckets {
+            enc.write_metric_value(
+                name.by_ref().with_suffix(Bucket),
+                labels.by_ref().compose_with(HistogramLabelLe { le }),
+                MetricValue::Int(count as i64),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(self.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(self.count as i64),
+        )
+    }
+}
+
+impl<W: Write> MetricEncoding<TextEncoder<W>> for FederatedSummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        for &(quantile, value) in &self.quantiles {
+            enc.write_metric_value(
+                &name,
+                labels
+                    .by_ref()
+                    .compose_with(SummaryLabelQuantile { quantile }),
+                MetricValue::Float(value),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(self.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(self.count as i64),
+        )
+    }
+}
+
+/// Write a counter as an integer if it can be, without losing precision
+pub(crate) fn count_value(value: f64) -> MetricValue {
+    if value >= 0.0 && value.fract() == 0.0 && value < i64::MAX as f64 {
+        MetricValue::Int(value as i64)
+    } else {
+        MetricValue::Float(value)
+    }
+}
+
+/// Written with the `_info` suffix, so the series have the same name as in OpenMetrics
+impl<W: Write> MetricEncoding<TextEncoder<W>> for InfoState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name.with_suffix(Info), MetricType::Info)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let info = self.labels.read();
+        enc.write_metric_value(
+            name.with_suffix(Info),
+            labels.compose_with(InfoLabels(&info)),
+            MetricValue::Int(1),
+        )
+    }
+}
+
+impl<T: FixedCardinalityLabel, W: Write> MetricEncoding<TextEncoder<W>> for StateSetState<T> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::StateSet)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut TextEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let label_name = state_set_label_name(&name);
+        let label_name = LabelName::from_str(&label_name);
+        let state = self.state.load(core::sync::atomic::Ordering::Relaxed);
+        for i in 0..T::cardinality() {
+            let label = StateSetLabel {
+                name: label_name,
+                state: T::decode(i),
+            };
+            enc.write_metric_value(
+                &name,
+                labels.by_ref().compose_with(label),
+                MetricValue::Int((i == state) as i64),
+            )?;
+        }
+        Ok(())
+    }
+}
+
 /// The prometheus text encoder helper
 pub struct BufferedTextEncoder {
     inner: TextEncoder<BytesWriter>,
@@ -416,6 +852,21 @@ pub(crate) fn write_label_str_value(s: &str, b: &mut impl Write) -> io::Result<(
     b.write_all(&s.as_bytes()[i..])
 }
 
+/// Help text escapes backslashes and newlines, but not double quotes
+pub(crate) fn write_help_str_value(s: &str, b: &mut impl Write) -> io::Result<()> {
+    let mut i = 0;
+    for j in memchr2_iter(b'\\', b'\n', s.as_bytes()) {
+        b.write_all(&s.as_bytes()[i..j])?;
+        match s.as_bytes()[j] {
+            b'\\' => b.write_all(b"\\\\")?,
+            b'\n' => b.write_all(b"\\n")?,
+            _ => unreachable!(),
+        }
+        i = j + 1;
+    }
+    b.write_all(&s.as_bytes()[i..])
+}
+
 struct BytesWriter {
     buf: BytesMut,
 }
@@ -449,9 +900,10 @@ mod tests {
             group::Encoding,
             histogram::Thresholds,
             name::{MetricName, Total},
+            summary::Quantiles,
             MetricFamilyEncoding,
         },
-        CounterVec, Histogram,
+        Counter, CounterVec, Histogram, MetricGroup, Summary,
     };
 
     use super::{write_label_str_value, BufferedTextEncoder};
@@ -565,6 +1017,73 @@ http_request_duration_seconds_bucket{le="12.8"} 4
 http_request_duration_seconds_bucket{le="+Inf"} 4
 http_request_duration_seconds_sum 12.4
 http_request_duration_seconds_count 4
+"#
+        );
+    }
+
+    #[test]
+    fn text_summary() {
+        let summary = Summary::with_metadata(Quantiles::new([(0.5, 0.05), (0.99, 0.001)]));
+
+        for x in 1..=100 {
+            summary.observe(x as f64 / 100.0);
+        }
+
+        let mut encoder = BufferedTextEncoder::default();
+
+        let name = MetricName::from_str("rpc_duration_seconds");
+        encoder
+            .write_help(name, "A summary of the RPC duration.")
+            .unwrap();
+        summary.collect_family_into(name, &mut encoder).unwrap();
+
+        let s = String::from_utf8(encoder.finish().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# HELP rpc_duration_seconds A summary of the RPC duration.
+# TYPE rpc_duration_seconds summary
+rpc_duration_seconds{quantile="0.5"} 0.5
+rpc_duration_seconds{quantile="0.99"} 0.99
+rpc_duration_seconds_sum 50.5
+rpc_duration_seconds_count 100
+"#
+        );
+    }
+
+    #[derive(MetricGroup, Default)]
+    #[metric(crate = crate)]
+    struct Help {
+        /// The number of requests,
+        /// split over two lines.
+        ///
+        /// Paths are written as `C:\path`.
+        requests: Counter,
+        /// Ignored in favour of the override
+        #[metric(help = "The number of errors")]
+        errors: Counter,
+        /// Not written
+        #[metric(no_help)]
+        retries: Counter,
+    }
+
+    #[test]
+    fn text_help() {
+        let mut encoder = BufferedTextEncoder::default();
+        Help::default().collect_group_into(&mut encoder).unwrap();
+
+        let s = String::from_utf8(encoder.finish().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# HELP requests The number of requests, split over two lines.\nPaths are written as `C:\\path`.
+# TYPE requests counter
+requests 0
+
+# HELP errors The number of errors
+# TYPE errors counter
+errors 0
+
+# TYPE retries counter
+retries 0
 "#
         );
     }
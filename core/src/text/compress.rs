@@ -0,0 +1,640 @@
+//! Compress the text exposition while it is being encoded.
+//!
+//! Large scrape payloads compress very well. Rather than encoding into a buffer and compressing a second
+//! copy of it, a [`CompressedTextEncoder`] streams the text through the compressor as each metric is written.
+//!
+//! Gzip and zstd support are enabled with the `gzip` and `zstd` features.
+//!
+//! ```
+//! use measured::{
+//!     text::compress::{negotiate, CompressedOpenMetricsEncoder, CompressedTextEncoder, Format},
+//!     Counter, MetricGroup,
+//! };
+//!
+//! #[derive(MetricGroup)]
+//! struct Metrics {
+//!     /// total number of requests
+//!     requests: Counter,
+//! }
+//!
+//! let metrics = Metrics {
+//!     requests: Counter::new(),
+//! };
+//!
+//! // taken from the incoming HTTP request
+//! let accept = Some("text/plain;version=0.0.4;q=0.9,*/*;q=0.1");
+//! let accept_encoding = Some("gzip, deflate");
+//!
+//! let negotiated = negotiate(accept, accept_encoding);
+//!
+//! let body = match negotiated.format {
+//!     Format::OpenMetrics => {
+//!         let mut enc = CompressedOpenMetricsEncoder::new(negotiated.compression);
+//!         metrics.collect_group_into(&mut enc).unwrap();
+//!         enc.finish()
+//!     }
+//!     _ => {
+//!         let mut enc = CompressedTextEncoder::new(negotiated.compression);
+//!         metrics.collect_group_into(&mut enc).unwrap();
+//!         enc.finish()
+//!     }
+//! };
+//!
+//! // send the response with these headers
+//! let content_type = negotiated.content_type();
+//! let content_encoding = negotiated.content_encoding();
+//! # let _ = (body, content_type, content_encoding);
+//! ```
+
+use std::{
+    convert::Infallible,
+    io::{self, Write},
+};
+
+use bytes::{Bytes, BytesMut};
+
+use crate::{
+    label::LabelGroup,
+    metric::{
+        group::Encoding,
+        name::{MetricNameEncoder, Unit},
+        MetricEncoding,
+    },
+};
+
+use super::{openmetrics::OpenMetricsEncoder, BytesWriter, TextEncoder, Unreachable};
+
+/// The compression to apply to an encoded response body
+///
+/// The available variants depend on the enabled features, so this cannot be matched exhaustively.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum Compression {
+    /// No compression
+    Identity,
+    /// Gzip compression
+    #[cfg(feature = "gzip")]
+    Gzip,
+    /// Zstandard compression
+    #[cfg(feature = "zstd")]
+    Zstd,
+}
+
+impl Compression {
+    /// The supported compressions, in order of preference
+    const PREFERENCE: &'static [Compression] = &[
+        #[cfg(feature = "zstd")]
+        Compression::Zstd,
+        #[cfg(feature = "gzip")]
+        Compression::Gzip,
+        Compression::Identity,
+    ];
+
+    /// The token for this compression, as used in the `Accept-Encoding` and `Content-Encoding` headers
+    pub fn token(self) -> &'static str {
+        match self {
+            Compression::Identity => "identity",
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => "gzip",
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => "zstd",
+        }
+    }
+
+    /// The value to send in the `Content-Encoding` header, if any.
+    pub fn content_encoding(self) -> Option<&'static str> {
+        (self != Compression::Identity).then(|| self.token())
+    }
+
+    /// Choose a compression from the value of an `Accept-Encoding` header.
+    ///
+    /// Picks the supported compression with the highest quality value, preferring zstd then gzip on ties.
+    /// Falls back to [`Compression::Identity`] if the header is missing or nothing else is acceptable.
+    pub fn negotiate(accept_encoding: Option<&str>) -> Self {
+        let Some(accept_encoding) = accept_encoding else {
+            return Compression::Identity;
+        };
+
+        let mut best = (Compression::Identity, 0.0);
+        for &compression in Self::PREFERENCE {
+            let mut q = None;
+            let mut wildcard = None;
+            for (token, weight) in media_ranges(accept_encoding) {
+                if token.eq_ignore_ascii_case(compression.token()) {
+                    q = Some(weight);
+                } else if token == "*" {
+                    wildcard = Some(weight);
+                }
+            }
+
+            // identity is always acceptable unless explicitly refused
+            let default = match compression {
+                Compression::Identity => f32::MIN_POSITIVE,
+                #[cfg(any(feature = "gzip", feature = "zstd"))]
+                _ => 0.0,
+            };
+            let q = q.or(wildcard).unwrap_or(default);
+            if q > best.1 {
+                best = (compression, q);
+            }
+        }
+        best.0
+    }
+}
+
+/// The exposition format to encode a response body with
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    /// The prometheus text format, written by [`TextEncoder`]
+    Text,
+    /// The OpenMetrics text format, written by [`OpenMetricsEncoder`](super::openmetrics::OpenMetricsEncoder)
+    OpenMetrics,
+    /// The prometheus protobuf format, written by the `measured-prometheus-protobuf` crate
+    Protobuf,
+}
+
+impl Format {
+    /// The value to send in the `Content-Type` header
+    pub const fn content_type(self) -> &'static str {
+        match self {
+            Format::Text => super::CONTENT_TYPE,
+            Format::OpenMetrics => super::openmetrics::CONTENT_TYPE,
+            Format::Protobuf => "application/vnd.google.protobuf; proto=io.prometheus.client.MetricFamily; encoding=delimited",
+        }
+    }
+
+    /// Choose between [`Format::Text`] and [`Format::OpenMetrics`] from the value of an `Accept` header.
+    ///
+    /// See [`Format::negotiate_from`] for details.
+    pub fn negotiate(accept: Option<&str>) -> Self {
+        Self::negotiate_from(accept, &[Format::Text, Format::OpenMetrics])
+    }
+
+    /// Choose one of the supported formats from the value of an `Accept` header.
+    ///
+    /// Picks the format with the highest quality value, preferring whichever was listed first on ties.
+    /// Falls back to [`Format::Text`] if the header is missing or none of the formats are acceptable.
+    pub fn negotiate_from(accept: Option<&str>, supported: &[Format]) -> Self {
+        let Some(accept) = accept else {
+            return Format::Text;
+        };
+
+        let mut best = (Format::Text, 0.0);
+        for (media_type, q) in media_ranges(accept) {
+            let format = if media_type.eq_ignore_ascii_case("application/openmetrics-text") {
+                Format::OpenMetrics
+            } else if media_type.eq_ignore_ascii_case("application/vnd.google.protobuf") {
+                Format::Protobuf
+            } else if media_type.eq_ignore_ascii_case("text/plain")
+                || media_type.eq_ignore_ascii_case("text/*")
+                || media_type == "*/*"
+            {
+                Format::Text
+            } else {
+                continue;
+            };
+            if q > best.1 && supported.contains(&format) {
+                best = (format, q);
+            }
+        }
+        best.0
+    }
+}
+
+/// The result of [`negotiate`]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Negotiated {
+    /// The text format to encode with
+    pub format: Format,
+    /// The compression to apply to the encoded text
+    pub compression: Compression,
+}
+
+impl Negotiated {
+    /// The value to send in the `Content-Type` header
+    pub fn content_type(&self) -> &'static str {
+        self.format.content_type()
+    }
+
+    /// The value to send in the `Content-Encoding` header, if any.
+    pub fn content_encoding(&self) -> Option<&'static str> {
+        self.compression.content_encoding()
+    }
+}
+
+/// Choose the response format and compression from the `Accept` and `Accept-Encoding` request headers.
+///
+/// The format is either [`Format::Text`], to be encoded with a [`CompressedTextEncoder`], or [`Format::OpenMetrics`],
+/// to be encoded with a [`CompressedOpenMetricsEncoder`].
+pub fn negotiate(accept: Option<&str>, accept_encoding: Option<&str>) -> Negotiated {
+    Negotiated {
+        format: Format::negotiate(accept),
+        compression: Compression::negotiate(accept_encoding),
+    }
+}
+
+/// Split a header into its values and their quality, ignoring any other parameters
+fn media_ranges(header: &str) -> impl Iterator<Item = (&str, f32)> {
+    header.split(',').filter_map(|range| {
+        let mut params = range.split(';');
+        let value = params.next()?.trim();
+        if value.is_empty() {
+            return None;
+        }
+        let q = params
+            .filter_map(|param| param.split_once('='))
+            .find(|(name, _)| name.trim().eq_ignore_ascii_case("q"))
+            .map_or(Some(1.0), |(_, q)| q.trim().parse::<f32>().ok())?;
+        Some((value, q))
+    })
+}
+
+enum Stream {
+    Identity(BytesWriter),
+    #[cfg(feature = "gzip")]
+    Gzip(flate2::write::GzEncoder<BytesWriter>),
+    #[cfg(feature = "zstd")]
+    Zstd(zstd::stream::write::Encoder<'static, BytesWriter>),
+}
+
+/// A writer which compresses everything written to it into an in-memory buffer.
+///
+/// This can be used as the writer for a [`TextEncoder`] or an
+/// [`OpenMetricsEncoder`](super::openmetrics::OpenMetricsEncoder).
+pub struct Compressor {
+    compression: Compression,
+    stream: Stream,
+}
+
+impl Compressor {
+    /// Create a new compressor
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            compression,
+            stream: Self::stream(
+                compression,
+                BytesWriter {
+                    buf: BytesMut::new(),
+                },
+            ),
+        }
+    }
+
+    fn stream(compression: Compression, w: BytesWriter) -> Stream {
+        match compression {
+            Compression::Identity => Stream::Identity(w),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => Stream::Gzip(flate2::write::GzEncoder::new(
+                w,
+                flate2::Compression::default(),
+            )),
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => Stream::Zstd(
+                zstd::stream::write::Encoder::new(w, 0).expect("default zstd level is valid"),
+            ),
+        }
+    }
+
+    /// The compression this compressor applies
+    pub fn compression(&self) -> Compression {
+        self.compression
+    }
+
+    /// Finish the compressed stream and extract the bytes to send in a HTTP response.
+    ///
+    /// The compressor can continue to be used and will start a new stream.
+    pub fn finish(&mut self) -> io::Result<Bytes> {
+        let empty = Stream::Identity(BytesWriter {
+            buf: BytesMut::new(),
+        });
+        #[cfg_attr(
+            not(any(feature = "gzip", feature = "zstd")),
+            allow(clippy::infallible_destructuring_match)
+        )]
+        let mut w = match std::mem::replace(&mut self.stream, empty) {
+            Stream::Identity(w) => w,
+            #[cfg(feature = "gzip")]
+            Stream::Gzip(s) => s.finish()?,
+            #[cfg(feature = "zstd")]
+            Stream::Zstd(s) => s.finish()?,
+        };
+        let bytes = w.buf.split().freeze();
+        self.stream = Self::stream(self.compression, w);
+        Ok(bytes)
+    }
+}
+
+impl Write for Compressor {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match &mut self.stream {
+            Stream::Identity(w) => w.write(buf),
+            #[cfg(feature = "gzip")]
+            Stream::Gzip(s) => s.write(buf),
+            #[cfg(feature = "zstd")]
+            Stream::Zstd(s) => s.write(buf),
+        }
+    }
+
+    #[inline]
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        match &mut self.stream {
+            Stream::Identity(w) => w.write_all(buf),
+            #[cfg(feature = "gzip")]
+            Stream::Gzip(s) => s.write_all(buf),
+            #[cfg(feature = "zstd")]
+            Stream::Zstd(s) => s.write_all(buf),
+        }
+    }
+
+    /// Does nothing. Compressed output is only available after [`Compressor::finish`].
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The prometheus text encoder helper, which compresses as it encodes
+pub struct CompressedTextEncoder {
+    inner: TextEncoder<Compressor>,
+}
+
+impl Encoding for CompressedTextEncoder {
+    type Err = Infallible;
+
+    /// Write the help line for a metric
+    fn write_help(&mut self, name: impl MetricNameEncoder, help: &str) -> Result<(), Infallible> {
+        self.inner.write_help(name, help).unreachable()
+    }
+}
+
+impl CompressedTextEncoder {
+    /// Create a new compressing text encoder.
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            inner: TextEncoder::new(Compressor::new(compression)),
+        }
+    }
+
+    /// The compression this encoder applies
+    pub fn compression(&self) -> Compression {
+        self.inner.writer.compression()
+    }
+
+    /// Change the compression this encoder applies.
+    ///
+    /// Any metrics encoded since the last [`finish`](Self::finish) are discarded.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.inner = TextEncoder::new(Compressor::new(compression));
+    }
+
+    /// Finish the text encoding and extract the compressed bytes to send in a HTTP response.
+    pub fn finish(&mut self) -> Bytes {
+        self.inner.flush().unreachable().unwrap();
+        self.inner.writer.finish().unreachable().unwrap()
+    }
+}
+
+impl<T: MetricEncoding<TextEncoder<Compressor>>> MetricEncoding<CompressedTextEncoder> for T {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut CompressedTextEncoder,
+    ) -> Result<(), Infallible> {
+        Self::write_type(name, &mut enc.inner).unreachable()
+    }
+    fn collect_into(
+        &self,
+        metadata: &T::Metadata,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut CompressedTextEncoder,
+    ) -> Result<(), Infallible> {
+        self.collect_into(metadata, labels, name, &mut enc.inner)
+            .unreachable()
+    }
+}
+
+/// The OpenMetrics text encoder helper, which compresses as it encodes
+pub struct CompressedOpenMetricsEncoder {
+    inner: OpenMetricsEncoder<Compressor>,
+}
+
+impl Encoding for CompressedOpenMetricsEncoder {
+    type Err = Infallible;
+
+    /// Write the help line for a metric
+    fn write_help(&mut self, name: impl MetricNameEncoder, help: &str) -> Result<(), Infallible> {
+        self.inner.write_help(name, help).unreachable()
+    }
+
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Infallible> {
+        Encoding::write_unit(&mut self.inner, name, unit).unreachable()
+    }
+}
+
+impl CompressedOpenMetricsEncoder {
+    /// Create a new compressing OpenMetrics encoder.
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(compression: Compression) -> Self {
+        Self {
+            inner: OpenMetricsEncoder::new(Compressor::new(compression)),
+        }
+    }
+
+    /// The compression this encoder applies
+    pub fn compression(&self) -> Compression {
+        self.inner.writer.compression()
+    }
+
+    /// Change the compression this encoder applies.
+    ///
+    /// Any metrics encoded since the last [`finish`](Self::finish) are discarded.
+    pub fn set_compression(&mut self, compression: Compression) {
+        self.inner = OpenMetricsEncoder::new(Compressor::new(compression));
+    }
+
+    /// Finish the encoding with the `# EOF` marker and extract the compressed bytes to send in a HTTP response.
+    pub fn finish(&mut self) -> Bytes {
+        self.inner.finish().unreachable().unwrap();
+        self.inner.writer.finish().unreachable().unwrap()
+    }
+}
+
+impl<T: MetricEncoding<OpenMetricsEncoder<Compressor>>> MetricEncoding<CompressedOpenMetricsEncoder>
+    for T
+{
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut CompressedOpenMetricsEncoder,
+    ) -> Result<(), Infallible> {
+        Self::write_type(name, &mut enc.inner).unreachable()
+    }
+    fn collect_into(
+        &self,
+        metadata: &T::Metadata,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut CompressedOpenMetricsEncoder,
+    ) -> Result<(), Infallible> {
+        self.collect_into(metadata, labels, name, &mut enc.inner)
+            .unreachable()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        text::{openmetrics::OpenMetricsEncoder, BufferedTextEncoder, TextEncoder},
+        Counter, MetricGroup,
+    };
+
+    use super::{
+        negotiate, CompressedOpenMetricsEncoder, CompressedTextEncoder, Compression, Compressor,
+        Format,
+    };
+
+    #[test]
+    fn negotiate_format() {
+        assert_eq!(Format::negotiate(None), Format::Text);
+        assert_eq!(Format::negotiate(Some("application/json")), Format::Text);
+        assert_eq!(
+            Format::negotiate(Some(
+                "application/openmetrics-text;version=1.0.0;q=0.5,text/plain;version=0.0.4;q=0.4,*/*;q=0.1"
+            )),
+            Format::OpenMetrics
+        );
+        assert_eq!(
+            Format::negotiate(Some("application/openmetrics-text;q=0.2, text/plain")),
+            Format::Text
+        );
+        assert_eq!(
+            Format::negotiate(Some("text/plain, application/openmetrics-text")),
+            Format::Text
+        );
+
+        let prometheus = "application/vnd.google.protobuf;proto=io.prometheus.client.MetricFamily;encoding=delimited;q=0.6,application/openmetrics-text;version=1.0.0;q=0.5,text/plain;version=0.0.4;q=0.4,*/*;q=0.1";
+        assert_eq!(Format::negotiate(Some(prometheus)), Format::OpenMetrics);
+        assert_eq!(
+            Format::negotiate_from(Some(prometheus), &[Format::Text, Format::Protobuf]),
+            Format::Protobuf
+        );
+    }
+
+    #[test]
+    fn negotiate_compression() {
+        assert_eq!(Compression::negotiate(None), Compression::Identity);
+        assert_eq!(Compression::negotiate(Some("br")), Compression::Identity);
+        assert_eq!(
+            Compression::negotiate(Some("identity;q=0")),
+            Compression::Identity
+        );
+
+        #[cfg(feature = "gzip")]
+        {
+            assert_eq!(
+                Compression::negotiate(Some("gzip, deflate")),
+                Compression::Gzip
+            );
+            assert_eq!(
+                Compression::negotiate(Some("gzip;q=0.5, zstd;q=0.2")),
+                Compression::Gzip
+            );
+        }
+        #[cfg(feature = "zstd")]
+        {
+            assert_eq!(Compression::negotiate(Some("*")), Compression::Zstd);
+            assert_eq!(
+                Compression::negotiate(Some("gzip, zstd")),
+                Compression::Zstd
+            );
+        }
+
+        let negotiated = negotiate(Some("application/openmetrics-text"), None);
+        assert_eq!(
+            negotiated.content_type(),
+            "application/openmetrics-text; version=1.0.0; charset=utf-8"
+        );
+        assert_eq!(negotiated.content_encoding(), None);
+    }
+
+    fn decompress(compression: Compression, body: &[u8]) -> Vec<u8> {
+        match compression {
+            Compression::Identity => body.to_vec(),
+            #[cfg(feature = "gzip")]
+            Compression::Gzip => {
+                use std::io::Read;
+                let mut out = vec![];
+                flate2::read::GzDecoder::new(body)
+                    .read_to_end(&mut out)
+                    .unwrap();
+                out
+            }
+            #[cfg(feature = "zstd")]
+            Compression::Zstd => zstd::stream::decode_all(body).unwrap(),
+        }
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    struct Metrics {
+        /// total number of requests
+        requests: Counter,
+        /// total number of errors
+        errors: Counter,
+    }
+
+    #[test]
+    fn compressed_matches_buffered() {
+        let metrics = Metrics {
+            requests: Counter::new(),
+            errors: Counter::new(),
+        };
+        metrics.requests.inc_by(3);
+        metrics.errors.inc();
+
+        let mut buffered = BufferedTextEncoder::new();
+        metrics.collect_group_into(&mut buffered).unwrap();
+        let expected = buffered.finish();
+
+        for &compression in Compression::PREFERENCE {
+            let mut enc = CompressedTextEncoder::new(compression);
+            // the encoder can be re-used
+            for _ in 0..2 {
+                metrics.collect_group_into(&mut enc).unwrap();
+                let body = enc.finish();
+                assert_eq!(decompress(compression, &body), expected);
+            }
+        }
+
+        let mut enc = TextEncoder::new(Compressor::new(Compression::Identity));
+        metrics.collect_group_into(&mut enc).unwrap();
+        assert_eq!(enc.writer.finish().unwrap(), expected);
+    }
+
+    #[test]
+    fn compressed_openmetrics() {
+        let metrics = Metrics {
+            requests: Counter::new(),
+            errors: Counter::new(),
+        };
+        metrics.requests.inc_by(3);
+
+        let mut unbuffered = OpenMetricsEncoder::new(vec![]);
+        metrics.collect_group_into(&mut unbuffered).unwrap();
+        unbuffered.finish().unwrap();
+        let expected = unbuffered.writer;
+        assert!(expected.ends_with(b"# EOF\n"));
+
+        for &compression in Compression::PREFERENCE {
+            let mut enc = CompressedOpenMetricsEncoder::new(compression);
+            for _ in 0..2 {
+                metrics.collect_group_into(&mut enc).unwrap();
+                let body = enc.finish();
+                assert_eq!(decompress(compression, &body), expected);
+            }
+        }
+    }
+}
@@ -0,0 +1,598 @@
+//! Re-export metrics scraped from another endpoint.
+//!
+//! A [`FederatedGroup`] holds a parsed snapshot of another exporter's text exposition, such as a
+//! sidecar proxy, and writes it back out as part of your own [`MetricGroup`].
+//!
+//! ```
+//! use measured::{
+//!     label::{LabelGroupVisitor, LabelName},
+//!     text::{federate::FederatedGroup, BufferedTextEncoder},
+//!     LabelGroup, MetricGroup,
+//! };
+//!
+//! struct Sidecar;
+//!
+//! impl LabelGroup for Sidecar {
+//!     fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+//!         const SIDECAR: &LabelName = LabelName::from_str("sidecar");
+//!         v.write_value(SIDECAR, &"envoy");
+//!     }
+//! }
+//!
+//! let group = FederatedGroup::parse(concat!(
+//!     "# TYPE upstream_rq_total counter\n",
+//!     "upstream_rq_total{cluster=\"api\"} 12\n",
+//! ))
+//! .unwrap()
+//! .with_labels(Sidecar);
+//!
+//! let mut enc = BufferedTextEncoder::new();
+//! group.collect_group_into(&mut enc).unwrap();
+//!
+//! assert_eq!(
+//!     enc.finish(),
+//!     concat!(
+//!         "# TYPE upstream_rq_total counter\n",
+//!         "upstream_rq_total{sidecar=\"envoy\",cluster=\"api\"} 12\n",
+//!     )
+//! );
+//! ```
+
+use std::sync::RwLock;
+
+use crate::{
+    label::{ComposedGroup, LabelGroupVisitor, LabelName, LabelValue, NoLabels},
+    metric::{
+        self, gauge::FloatGaugeState, group::Encoding, name::MetricName, untyped::UntypedState,
+        MetricEncoding,
+    },
+    LabelGroup, MetricGroup,
+};
+
+use super::{
+    parse::{
+        is_label_name, parse, parse_bytes, parse_value, MetricFamily, ParseError, ParseErrorKind,
+    },
+    MetricType,
+};
+
+/// A snapshot of metric families scraped from another endpoint.
+///
+/// Each family is rebuilt into metrics of its type, so it can be collected with any encoder that can write
+/// [`FederatedCounterState`], [`FloatGaugeState`], [`FederatedHistogramState`], [`FederatedSummaryState`]
+/// and [`UntypedState`]. Families of any other type are written as untyped. Timestamps are dropped.
+///
+/// Histogram buckets without an `le` label and summary samples without a `quantile` label are dropped.
+///
+/// Every sample can be tagged with some extra labels using [`FederatedGroup::with_labels`]. If a sample already
+/// has a label with the same name, it is renamed to `exported_<name>`, the same as Prometheus federation does.
+pub struct FederatedGroup<L = NoLabels> {
+    families: RwLock<Vec<MetricFamily>>,
+    labels: L,
+}
+
+/// A scraped counter. Unlike [`CounterState`](crate::metric::counter::CounterState), the value can be fractional.
+#[derive(Default, Debug, Clone, Copy, PartialEq)]
+pub struct FederatedCounterState {
+    /// The current value
+    pub value: f64,
+}
+
+/// A scraped histogram
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FederatedHistogramState {
+    /// The upper bound and cumulative count of each bucket, in the order they were scraped
+    pub buckets: Vec<(f64, u64)>,
+    /// The sum of all observations
+    pub sum: f64,
+    /// The number of observations
+    pub count: u64,
+}
+
+/// A scraped summary
+#[derive(Default, Debug, Clone, PartialEq)]
+pub struct FederatedSummaryState {
+    /// The quantile and its value, in the order they were scraped
+    pub quantiles: Vec<(f64, f64)>,
+    /// The sum of all observations
+    pub sum: f64,
+    /// The number of observations
+    pub count: u64,
+}
+
+impl metric::MetricType for FederatedCounterState {
+    type Metadata = ();
+}
+
+impl metric::MetricType for FederatedHistogramState {
+    type Metadata = ();
+}
+
+impl metric::MetricType for FederatedSummaryState {
+    type Metadata = ();
+}
+
+/// An error returned when a [`MetricFamily`] has an invalid metric or label name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct InvalidFamily {
+    /// The name of the metric family
+    pub family: String,
+    /// Either [`ParseErrorKind::InvalidMetricName`] or [`ParseErrorKind::InvalidLabelName`]
+    pub kind: ParseErrorKind,
+}
+
+impl core::fmt::Display for InvalidFamily {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self.kind {
+            ParseErrorKind::InvalidLabelName => "invalid label name",
+            _ => "invalid metric name",
+        };
+        write!(f, "metric family {:?}: {msg}", self.family)
+    }
+}
+
+impl std::error::Error for InvalidFamily {}
+
+/// Check that all the metric and label names can be written
+fn validate(families: &[MetricFamily]) -> Result<(), InvalidFamily> {
+    for family in families {
+        let err = |kind| InvalidFamily {
+            family: family.name.clone(),
+            kind,
+        };
+        let names = std::iter::once(&family.name).chain(family.samples.iter().map(|s| &s.name));
+        for name in names {
+            MetricName::try_from_str(name).map_err(|_| err(ParseErrorKind::InvalidMetricName))?;
+        }
+        for sample in &family.samples {
+            if !sample.labels.iter().all(|(name, _)| is_label_name(name)) {
+                return Err(err(ParseErrorKind::InvalidLabelName));
+            }
+        }
+    }
+    Ok(())
+}
+
+impl FederatedGroup {
+    /// Create a `FederatedGroup` from already parsed metric families
+    ///
+    /// # Errors
+    /// Will error if any metric or label name is invalid
+    pub fn new(families: Vec<MetricFamily>) -> Result<Self, InvalidFamily> {
+        validate(&families)?;
+        Ok(Self::new_unchecked(families))
+    }
+
+    /// The parser only returns valid names
+    fn new_unchecked(families: Vec<MetricFamily>) -> Self {
+        Self {
+            families: RwLock::new(families),
+            labels: NoLabels,
+        }
+    }
+
+    /// Parse the text exposition format into a new `FederatedGroup`
+    pub fn parse(input: &str) -> Result<Self, ParseError> {
+        parse(input).map(Self::new_unchecked)
+    }
+
+    /// Parse the text exposition format from raw bytes into a new `FederatedGroup`
+    pub fn from_bytes(input: &[u8]) -> Result<Self, ParseError> {
+        parse_bytes(input).map(Self::new_unchecked)
+    }
+}
+
+impl<L> FederatedGroup<L> {
+    /// Add extra labels to every sample in this group. These are written before the sample's own labels.
+    pub fn with_labels<L2: LabelGroup>(self, labels: L2) -> FederatedGroup<L2> {
+        FederatedGroup {
+            families: self.families,
+            labels,
+        }
+    }
+
+    /// Replace the snapshot with a newly scraped set of metric families
+    ///
+    /// # Errors
+    /// Will error if any metric or label name is invalid. The snapshot is left unchanged.
+    pub fn replace(&self, families: Vec<MetricFamily>) -> Result<(), InvalidFamily> {
+        validate(&families)?;
+        *self.families.write().unwrap() = families;
+        Ok(())
+    }
+
+    /// Get a copy of the current snapshot
+    pub fn families(&self) -> Vec<MetricFamily> {
+        self.families.read().unwrap().clone()
+    }
+}
+
+struct SampleLabels<'a> {
+    labels: &'a [(String, String)],
+    /// The names of the extra labels, which the sample's own labels must not repeat
+    reserved: &'a [String],
+}
+
+impl LabelGroup for SampleLabels<'_> {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        for (name, value) in self.labels {
+            if self.reserved.contains(name) {
+                v.write_value(LabelName::from_str(&format!("exported_{name}")), value);
+            } else {
+                v.write_value(LabelName::from_str(name), value);
+            }
+        }
+    }
+}
+
+/// The extra labels, followed by the sample's own labels
+fn sample_labels<'a, L>(
+    extra: &'a L,
+    labels: &'a [(String, String)],
+    reserved: &'a [String],
+) -> ComposedGroup<&'a L, SampleLabels<'a>> {
+    ComposedGroup(extra, SampleLabels { labels, reserved })
+}
+
+/// Collects the label names of a label group
+struct LabelNames(Vec<String>);
+
+impl LabelGroupVisitor for LabelNames {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, _x: &impl LabelValue) {
+        self.0.push(name.as_str().to_owned());
+    }
+}
+
+/// Group the samples of a histogram or summary family by their labels, without the `le` or `quantile` label.
+///
+/// `add` is called with each sample's name suffix, the value of the removed label, and the sample value.
+fn group_series<S: Default>(
+    family: &MetricFamily,
+    label: &str,
+    mut add: impl FnMut(&mut S, &str, Option<f64>, f64),
+) -> Vec<(Vec<(String, String)>, S)> {
+    let mut series: Vec<(Vec<(String, String)>, S)> = vec![];
+    for sample in &family.samples {
+        let mut removed = None;
+        let mut labels = Vec::with_capacity(sample.labels.len());
+        for (name, value) in &sample.labels {
+            if name == label {
+                removed = parse_value(value);
+            } else {
+                labels.push((name.clone(), value.clone()));
+            }
+        }
+
+        let i = match series.iter().position(|(l, _)| *l == labels) {
+            Some(i) => i,
+            None => {
+                series.push((labels, S::default()));
+                series.len() - 1
+            }
+        };
+        let suffix = sample.name.strip_prefix(&*family.name).unwrap_or_default();
+        add(&mut series[i].1, suffix, removed, sample.value);
+    }
+    series
+}
+
+fn histograms(family: &MetricFamily) -> Vec<(Vec<(String, String)>, FederatedHistogramState)> {
+    group_series(
+        family,
+        "le",
+        |h: &mut FederatedHistogramState, suffix, le, value| match (suffix, le) {
+            ("_bucket", Some(le)) => h.buckets.push((le, value as u64)),
+            ("_sum", _) => h.sum = value,
+            ("_count", _) => h.count = value as u64,
+            _ => {}
+        },
+    )
+}
+
+fn summaries(family: &MetricFamily) -> Vec<(Vec<(String, String)>, FederatedSummaryState)> {
+    group_series(
+        family,
+        "quantile",
+        |s: &mut FederatedSummaryState, suffix, q, value| match (suffix, q) {
+            ("", Some(q)) => s.quantiles.push((q, value)),
+            ("_sum", _) => s.sum = value,
+            ("_count", _) => s.count = value as u64,
+            _ => {}
+        },
+    )
+}
+
+impl<L: LabelGroup, Enc: Encoding> MetricGroup<Enc> for FederatedGroup<L>
+where
+    FederatedCounterState: MetricEncoding<Enc>,
+    FloatGaugeState: MetricEncoding<Enc>,
+    FederatedHistogramState: MetricEncoding<Enc>,
+    FederatedSummaryState: MetricEncoding<Enc>,
+    UntypedState: MetricEncoding<Enc>,
+{
+    fn collect_group_into(&self, enc: &mut Enc) -> Result<(), Enc::Err> {
+        let mut reserved = LabelNames(vec![]);
+        self.labels.visit_values(&mut reserved);
+
+        let families = self.families.read().unwrap();
+        for family in &*families {
+            let name = MetricName::try_from_str(&family.name)
+                .expect("metric names are validated when the snapshot is set");
+
+            if let Some(help) = &family.help {
+                enc.write_help(name, help)?;
+            }
+            match family.metric_type {
+                MetricType::Counter => {
+                    FederatedCounterState::write_type(name, enc)?;
+                    for sample in &family.samples {
+                        let counter = FederatedCounterState {
+                            value: sample.value,
+                        };
+                        counter.collect_into(
+                            &(),
+                            sample_labels(&self.labels, &sample.labels, &reserved.0),
+                            name,
+                            enc,
+                        )?;
+                    }
+                }
+                MetricType::Gauge => {
+                    FloatGaugeState::write_type(name, enc)?;
+                    for sample in &family.samples {
+                        let gauge = FloatGaugeState::new(sample.value);
+                        gauge.collect_into(
+                            &(),
+                            sample_labels(&self.labels, &sample.labels, &reserved.0),
+                            name,
+                            enc,
+                        )?;
+                    }
+                }
+                MetricType::Histogram => {
+                    FederatedHistogramState::write_type(name, enc)?;
+                    for (series, histogram) in histograms(family) {
+                        histogram.collect_into(
+                            &(),
+                            sample_labels(&self.labels, &series, &reserved.0),
+                            name,
+                            enc,
+                        )?;
+                    }
+                }
+                MetricType::Summary => {
+                    FederatedSummaryState::write_type(name, enc)?;
+                    for (series, summary) in summaries(family) {
+                        summary.collect_into(
+                            &(),
+                            sample_labels(&self.labels, &series, &reserved.0),
+                            name,
+                            enc,
+                        )?;
+                    }
+                }
+                MetricType::Untyped | MetricType::Info | MetricType::StateSet => {
+                    UntypedState::write_type(name, enc)?;
+                    for sample in &family.samples {
+                        let untyped = UntypedState::new(sample.value);
+                        untyped.collect_into(
+                            &(),
+                            sample_labels(&self.labels, &sample.labels, &reserved.0),
+                            name,
+                            enc,
+                        )?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        json::JsonEncoder,
+        label::{LabelGroupVisitor, LabelName},
+        text::{
+            openmetrics::OpenMetricsEncoder,
+            parse::{MetricFamily, ParseErrorKind, Sample},
+            BufferedTextEncoder, MetricType,
+        },
+        LabelGroup, MetricGroup,
+    };
+
+    use super::{FederatedGroup, InvalidFamily};
+
+    struct Sidecar;
+
+    impl LabelGroup for Sidecar {
+        fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+            const SIDECAR: &LabelName = LabelName::from_str("sidecar");
+            v.write_value(SIDECAR, &"envoy");
+        }
+    }
+
+    #[test]
+    fn federate() {
+        let input = concat!(
+            "# HELP upstream_rq_total total upstream requests\n",
+            "# TYPE upstream_rq_total counter\n",
+            "upstream_rq_total{cluster=\"api\"} 12\n",
+            "upstream_rq_total{cluster=\"web\"} 3 1700000000000\n",
+            "# TYPE connections gauge\n",
+            "connections 4\n",
+            "# TYPE rq_time histogram\n",
+            "rq_time_bucket{le=\"0.5\"} 2\n",
+            "rq_time_bucket{le=\"+Inf\"} 3\n",
+            "rq_time_sum 1.25\n",
+            "rq_time_count 3\n",
+            "# TYPE rq_size summary\n",
+            "rq_size{quantile=\"0.5\"} 120\n",
+            "rq_size_sum 360\n",
+            "rq_size_count 3\n",
+            "untyped_metric -1.5\n",
+        );
+
+        let group = FederatedGroup::from_bytes(input.as_bytes())
+            .unwrap()
+            .with_labels(Sidecar);
+
+        let mut enc = BufferedTextEncoder::new();
+        group.collect_group_into(&mut enc).unwrap();
+        let output = enc.finish();
+
+        assert_eq!(
+            output,
+            concat!(
+                "# HELP upstream_rq_total total upstream requests\n",
+                "# TYPE upstream_rq_total counter\n",
+                "upstream_rq_total{sidecar=\"envoy\",cluster=\"api\"} 12\n",
+                "upstream_rq_total{sidecar=\"envoy\",cluster=\"web\"} 3\n",
+                "\n",
+                "# TYPE connections gauge\n",
+                "connections{sidecar=\"envoy\"} 4.0\n",
+                "\n",
+                "# TYPE rq_time histogram\n",
+                "rq_time_bucket{sidecar=\"envoy\",le=\"0.5\"} 2\n",
+                "rq_time_bucket{sidecar=\"envoy\",le=\"+Inf\"} 3\n",
+                "rq_time_sum{sidecar=\"envoy\"} 1.25\n",
+                "rq_time_count{sidecar=\"envoy\"} 3\n",
+                "\n",
+                "# TYPE rq_size summary\n",
+                "rq_size{sidecar=\"envoy\",quantile=\"0.5\"} 120.0\n",
+                "rq_size_sum{sidecar=\"envoy\"} 360.0\n",
+                "rq_size_count{sidecar=\"envoy\"} 3\n",
+                "\n",
+                "# TYPE untyped_metric untyped\n",
+                "untyped_metric{sidecar=\"envoy\"} -1.5\n",
+            )
+        );
+
+        group.replace(vec![]).unwrap();
+        let mut enc = BufferedTextEncoder::new();
+        group.collect_group_into(&mut enc).unwrap();
+        assert!(enc.finish().is_empty());
+    }
+
+    #[test]
+    fn other_encoders() {
+        let input = concat!(
+            "# HELP upstream_rq_total total upstream requests\n",
+            "# TYPE upstream_rq_total counter\n",
+            "upstream_rq_total 12\n",
+            "# TYPE cpu_seconds_total counter\n",
+            "cpu_seconds_total 0.5\n",
+            "# TYPE rq_time histogram\n",
+            "rq_time_bucket{le=\"0.5\"} 2\n",
+            "rq_time_sum 1.25\n",
+            "rq_time_bucket{le=\"+Inf\"} 3\n",
+            "rq_time_count 3\n",
+            "# TYPE rq_size summary\n",
+            "rq_size{quantile=\"0.5\"} 120\n",
+            "rq_size_sum 360\n",
+            "rq_size_count 3\n",
+            "untyped_metric -1.5\n",
+        );
+        let group = FederatedGroup::parse(input).unwrap();
+
+        let mut enc = OpenMetricsEncoder::new(vec![]);
+        group.collect_group_into(&mut enc).unwrap();
+        enc.finish().unwrap();
+        assert_eq!(
+            String::from_utf8(enc.writer).unwrap(),
+            concat!(
+                "# HELP upstream_rq total upstream requests\n",
+                "# TYPE upstream_rq counter\n",
+                "upstream_rq_total 12\n",
+                "# TYPE cpu_seconds counter\n",
+                "cpu_seconds_total 0.5\n",
+                "# TYPE rq_time histogram\n",
+                "rq_time_bucket{le=\"0.5\"} 2\n",
+                "rq_time_bucket{le=\"+Inf\"} 3\n",
+                "rq_time_sum 1.25\n",
+                "rq_time_count 3\n",
+                "# TYPE rq_size summary\n",
+                "rq_size{quantile=\"0.5\"} 120.0\n",
+                "rq_size_sum 360.0\n",
+                "rq_size_count 3\n",
+                "# TYPE untyped_metric unknown\n",
+                "untyped_metric -1.5\n",
+                "# EOF\n",
+            )
+        );
+
+        let mut enc = JsonEncoder::new(vec![]);
+        group.collect_group_into(&mut enc).unwrap();
+        enc.flush().unwrap();
+        assert_eq!(
+            String::from_utf8(enc.writer).unwrap(),
+            concat!(
+                r#"{"families":["#,
+                r#"{"name":"upstream_rq_total","help":"total upstream requests","type":"counter","samples":["#,
+                r#"{"labels":{},"value":12}]},"#,
+                r#"{"name":"cpu_seconds_total","type":"counter","samples":["#,
+                r#"{"labels":{},"value":0.5}]},"#,
+                r#"{"name":"rq_time","type":"histogram","samples":["#,
+                r#"{"labels":{},"buckets":[{"le":0.5,"count":2},{"le":"+Inf","count":3}],"sum":1.25,"count":3}]},"#,
+                r#"{"name":"rq_size","type":"summary","samples":["#,
+                r#"{"labels":{},"quantiles":[{"quantile":0.5,"value":120.0}],"sum":360.0,"count":3}]},"#,
+                r#"{"name":"untyped_metric","type":"untyped","samples":["#,
+                r#"{"labels":{},"value":-1.5}]}"#,
+                "]}",
+            )
+        );
+    }
+
+    #[test]
+    fn conflicting_labels() {
+        let group = FederatedGroup::parse("connections{sidecar=\"istio\",pod=\"a\"} 4\n")
+            .unwrap()
+            .with_labels(Sidecar);
+
+        let mut enc = BufferedTextEncoder::new();
+        group.collect_group_into(&mut enc).unwrap();
+        assert_eq!(
+            enc.finish(),
+            concat!(
+                "# TYPE connections untyped\n",
+                "connections{sidecar=\"envoy\",exported_sidecar=\"istio\",pod=\"a\"} 4.0\n",
+            )
+        );
+    }
+
+    #[test]
+    fn invalid_names() {
+        let family = |name: &str, label: &str| MetricFamily {
+            name: "connections".to_owned(),
+            help: None,
+            metric_type: MetricType::Gauge,
+            samples: vec![Sample {
+                name: name.to_owned(),
+                labels: vec![(label.to_owned(), "a".to_owned())],
+                value: 1.0,
+                timestamp: None,
+            }],
+        };
+
+        assert!(FederatedGroup::new(vec![family("connections", "pod")]).is_ok());
+        assert_eq!(
+            FederatedGroup::new(vec![family("connections total", "pod")]).err(),
+            Some(InvalidFamily {
+                family: "connections".to_owned(),
+                kind: ParseErrorKind::InvalidMetricName
+            })
+        );
+
+        let group = FederatedGroup::new(vec![]).unwrap();
+        assert_eq!(
+            group.replace(vec![family("connections", "pod-name")]),
+            Err(InvalidFamily {
+                family: "connections".to_owned(),
+                kind: ParseErrorKind::InvalidLabelName
+            })
+        );
+        assert!(group.families().is_empty());
+    }
+}
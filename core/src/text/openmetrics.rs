@@ -0,0 +1,1069 @@
+//! OpenMetrics Text based exporter
+//!
+//! See <https://github.com/OpenObservability/OpenMetrics/blob/main/specification/OpenMetrics.md>
+
+use std::{io::Write, time::SystemTime};
+
+use crate::{
+    label::{FixedCardinalityLabel, LabelGroup, LabelName},
+    metric::{
+        counter::{write_counter, CounterState, ExemplarCounterState, ShardedCounterState},
+        exemplar::Exemplar,
+        gauge::{FloatGaugeState, GaugeState},
+        group::{Encoding, MetricValue},
+        histogram::{ExemplarHistogramState, HistogramState, Thresholds},
+        info::{InfoLabels, InfoState},
+        name::{Bucket, Count, Created, Info, MetricName, MetricNameEncoder, Sum, Total, Unit},
+        native_histogram::{NativeHistogramConfig, NativeHistogramState},
+        state_set::{state_set_label_name, StateSetLabel, StateSetState},
+        summary::{Quantiles, SummaryState},
+        untyped::UntypedState,
+        windowed_histogram::{Window, WindowedHistogramState},
+        MetricEncoding,
+    },
+};
+
+use super::{
+    count_value,
+    federate::{FederatedCounterState, FederatedHistogramState, FederatedSummaryState},
+    write_float_value, write_label_str_value, write_labels, write_metric_value, write_sample,
+    HistogramLabelLe, MetricType, SummaryLabelQuantile,
+};
+
+/// The content type of the OpenMetrics text format, to be used in HTTP responses.
+pub const CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The OpenMetrics text encoder helper
+///
+/// Unlike the prometheus text format, counter families in OpenMetrics are named without their `_total` suffix.
+/// A counter named `http_requests_total` is written as the `http_requests` family,
+/// with the `http_requests_total` and `http_requests_created` samples.
+pub struct OpenMetricsEncoder<W> {
+    /// The inner writer for this text encoder.
+    pub writer: W,
+    created: Option<f64>,
+    pending: PendingMetadata,
+    family: Family,
+}
+
+/// The help and unit lines which are waiting for their type line.
+///
+/// Counter families drop the `_total` suffix from their name, which the help and unit lines have to match.
+/// The buffers are re-used between families.
+#[derive(Default)]
+struct PendingMetadata {
+    help_pending: bool,
+    unit_pending: bool,
+    name: Vec<u8>,
+    /// The escaped help text
+    help: Vec<u8>,
+    unit: Vec<u8>,
+}
+
+/// The family that was last written in a type line.
+#[derive(Default)]
+struct Family {
+    /// The name that was given to the type line
+    name: Vec<u8>,
+    /// The length of the family name. For counters, this excludes the `_total` suffix of the name.
+    len: usize,
+}
+
+impl Family {
+    /// Whether the name is the name of a counter family which ends with `_total`
+    fn is_counter_total(&self, name: &impl MetricNameEncoder) -> bool {
+        self.len < self.name.len() && name.encode_len() == self.name.len() && {
+            let mut eq = NameEq {
+                expected: &self.name,
+            };
+            name.encode_utf8(&mut eq).is_ok() && eq.expected.is_empty()
+        }
+    }
+
+    /// The family name, without the `_total` suffix of a counter
+    fn base(&self) -> &MetricName {
+        let name = std::str::from_utf8(&self.name[..self.len])
+            .expect("metric names are always valid utf8");
+        MetricName::from_str_unchecked(name)
+    }
+}
+
+/// Compares an encoded name against the expected bytes, without allocating.
+struct NameEq<'a> {
+    expected: &'a [u8],
+}
+
+impl Write for NameEq<'_> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self.expected.strip_prefix(buf) {
+            Some(rest) => {
+                self.expected = rest;
+                Ok(buf.len())
+            }
+            None => Err(std::io::ErrorKind::InvalidData.into()),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<W: Write> Encoding for OpenMetricsEncoder<W> {
+    type Err = std::io::Error;
+
+    /// Write the help line for a metric
+    fn write_help(
+        &mut self,
+        name: impl MetricNameEncoder,
+        help: &str,
+    ) -> Result<(), std::io::Error> {
+        self.write_pending_metadata()?;
+        self.pending.name.clear();
+        self.pending.help.clear();
+        name.encode_utf8(&mut self.pending.name)?;
+        write_label_str_value(help, &mut self.pending.help)?;
+        self.pending.help_pending = true;
+        Ok(())
+    }
+
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Self::Err> {
+        OpenMetricsEncoder::write_unit(self, name, unit.as_str())
+    }
+}
+
+impl<W: Write> OpenMetricsEncoder<W> {
+    /// Create a new OpenMetrics encoder.
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(w: W) -> Self {
+        Self {
+            writer: w,
+            created: None,
+            pending: PendingMetadata::default(),
+            family: Family::default(),
+        }
+    }
+
+    /// Write a `_created` sample for all counters and histograms, set to the given time.
+    ///
+    /// Measured does not track when each individual metric was created,
+    /// so this would usually be the time the metrics were first registered.
+    pub fn with_created_timestamp(self, created: SystemTime) -> Self {
+        let created = created
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            created: Some(created.as_secs_f64()),
+            ..self
+        }
+    }
+
+    /// Finish the text encoding with the `# EOF` marker and extract the bytes to send in a HTTP response.
+    pub fn finish(&mut self) -> std::io::Result<()> {
+        self.write_pending_metadata()?;
+        self.family.name.clear();
+        self.family.len = 0;
+        self.writer.write_all(b"# EOF\n")?;
+        self.writer.flush()
+    }
+
+    /// Write the type line for a metric, preceded by its help and unit lines.
+    ///
+    /// Counter families are written without the `_total` suffix of their name.
+    pub fn write_type(
+        &mut self,
+        name: &impl MetricNameEncoder,
+        typ: MetricType,
+    ) -> Result<(), std::io::Error> {
+        self.family.name.clear();
+        name.encode_utf8(&mut self.family.name)?;
+        self.family.len = match typ {
+            MetricType::Counter => self
+                .family
+                .name
+                .strip_suffix(b"_total")
+                .unwrap_or(&self.family.name)
+                .len(),
+            _ => self.family.name.len(),
+        };
+        let family = &self.family.name[..self.family.len];
+
+        // the help and unit lines are written with the same family name as the type line
+        if self.pending.help_pending || self.pending.unit_pending {
+            self.pending.name.clear();
+            self.pending.name.extend_from_slice(family);
+            self.write_pending_metadata()?;
+        }
+
+        self.writer.write_all(b"# TYPE ")?;
+        self.writer
+            .write_all(&self.family.name[..self.family.len])?;
+        match typ {
+            MetricType::Counter => self.writer.write_all(b" counter\n"),
+            MetricType::Histogram => self.writer.write_all(b" histogram\n"),
+            MetricType::Gauge => self.writer.write_all(b" gauge\n"),
+            MetricType::Summary => self.writer.write_all(b" summary\n"),
+            MetricType::Untyped => self.writer.write_all(b" unknown\n"),
+            MetricType::Info => self.writer.write_all(b" info\n"),
+            MetricType::StateSet => self.writer.write_all(b" stateset\n"),
+        }
+    }
+
+    /// Write the unit line for a metric. The metric name must end with the unit.
+    ///
+    /// The unit line is written together with the type line that follows it.
+    pub fn write_unit(
+        &mut self,
+        name: impl MetricNameEncoder,
+        unit: &str,
+    ) -> Result<(), std::io::Error> {
+        if !self.pending.help_pending {
+            self.write_pending_metadata()?;
+            self.pending.name.clear();
+            name.encode_utf8(&mut self.pending.name)?;
+        }
+        self.pending.unit.clear();
+        self.pending.unit.extend_from_slice(unit.as_bytes());
+        self.pending.unit_pending = true;
+        Ok(())
+    }
+
+    /// Write the help and unit lines that are waiting for their type line, if there are any
+    fn write_pending_metadata(&mut self) -> std::io::Result<()> {
+        if std::mem::take(&mut self.pending.help_pending) {
+            self.writer.write_all(b"# HELP ")?;
+            self.writer.write_all(&self.pending.name)?;
+            self.writer.write_all(b" ")?;
+            self.writer.write_all(&self.pending.help)?;
+            self.writer.write_all(b"\n")?;
+        }
+        if std::mem::take(&mut self.pending.unit_pending) {
+            self.writer.write_all(b"# UNIT ")?;
+            self.writer.write_all(&self.pending.name)?;
+            self.writer.write_all(b" ")?;
+            self.writer.write_all(&self.pending.unit)?;
+            self.writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+
+    /// Write the metric data
+    fn write_metric_value(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        value: MetricValue,
+    ) -> Result<(), std::io::Error> {
+        self.write_pending_metadata()?;
+        write_metric_value(&mut self.writer, name, labels, value)
+    }
+
+    /// Write the metric data, followed by the exemplar if there is one
+    fn write_metric_value_with_exemplar(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        value: MetricValue,
+        exemplar: Option<&Exemplar>,
+    ) -> Result<(), std::io::Error> {
+        self.write_pending_metadata()?;
+        write_sample(&mut self.writer, name, labels, value)?;
+        if let Some(exemplar) = exemplar {
+            self.writer.write_all(b" # ")?;
+            if !write_labels(&mut self.writer, exemplar)? {
+                self.writer.write_all(b"{}")?;
+            }
+            self.writer.write_all(b" ")?;
+            write_float_value(exemplar.value(), &mut self.writer)?;
+            self.writer.write_all(b" ")?;
+            let timestamp = exemplar
+                .timestamp()
+                .duration_since(SystemTime::UNIX_EPOCH)
+                .unwrap_or_default();
+            write_float_value(timestamp.as_secs_f64(), &mut self.writer)?;
+        }
+        self.writer.write_all(b"\n")
+    }
+
+    fn write_created(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+    ) -> Result<(), std::io::Error> {
+        match self.created {
+            Some(created) => self.write_metric_value(
+                name.with_suffix(Created),
+                labels,
+                MetricValue::Float(created),
+            ),
+            None => Ok(()),
+        }
+    }
+
+    /// Write the `_total` sample of a counter, and the `_created` sample if there is a created timestamp.
+    fn write_counter(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        count: u64,
+        exemplar: Option<&Exemplar>,
+    ) -> Result<(), std::io::Error> {
+        let value = MetricValue::Int(count as i64);
+        self.write_counter_total(&name, labels.by_ref(), value, exemplar)?;
+        if !self.family.is_counter_total(&name) {
+            return self.write_created(name, labels);
+        }
+        if let Some(created) = self.created {
+            let name = self.family.base().with_suffix(Created);
+            write_metric_value(&mut self.writer, name, labels, MetricValue::Float(created))?;
+        }
+        Ok(())
+    }
+
+    /// Write the `_total` sample of a counter.
+    ///
+    /// Counter names conventionally already end with `_total`, which must not be written twice.
+    /// If the name is the counter family written in the last type line, the sample is named after that family.
+    fn write_counter_total(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        value: MetricValue,
+        exemplar: Option<&Exemplar>,
+    ) -> Result<(), std::io::Error> {
+        if self.family.is_counter_total(&name) {
+            self.write_metric_value_with_exemplar(name, labels, value, exemplar)
+        } else {
+            self.write_metric_value_with_exemplar(name.with_suffix(Total), labels, value, exemplar)
+        }
+    }
+}
+
+/// Write the cumulative buckets, sum, count and created samples of a histogram,
+/// with the exemplar of each bucket if there is one.
+fn write_histogram<W: Write, const N: usize>(
+    enc: &mut OpenMetricsEncoder<W>,
+    metadata: &Thresholds<N>,
+    labels: impl LabelGroup,
+    name: impl MetricNameEncoder,
+    (buckets, inf, sum): ([u64; N], u64, f64),
+    exemplars: &[Option<Exemplar>],
+) -> Result<(), std::io::Error> {
+    let exemplar = |i: usize| exemplars.get(i).and_then(Option::as_ref);
+    let mut val = 0;
+
+    for (i, (&le, b)) in metadata.get().iter().zip(buckets).enumerate() {
+        val += b;
+        enc.write_metric_value_with_exemplar(
+            name.by_ref().with_suffix(Bucket),
+            labels.by_ref().compose_with(HistogramLabelLe { le }),
+            MetricValue::Int(val as i64),
+            exemplar(i),
+        )?;
+    }
+    let count = val + inf;
+    enc.write_metric_value_with_exemplar(
+        name.by_ref().with_suffix(Bucket),
+        labels
+            .by_ref()
+            .compose_with(HistogramLabelLe { le: f64::INFINITY }),
+        MetricValue::Int(count as i64),
+        exemplar(N),
+    )?;
+    enc.write_metric_value(
+        name.by_ref().with_suffix(Sum),
+        labels.by_ref(),
+        MetricValue::Float(sum),
+    )?;
+    enc.write_metric_value(
+        name.by_ref().with_suffix(Count),
+        labels.by_ref(),
+        MetricValue::Int(count as i64),
+    )?;
+    enc.write_created(name, labels)
+}
+
+impl<W: Write, const N: usize> MetricEncoding<OpenMetricsEncoder<W>> for HistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_histogram(enc, metadata, labels, name, self.sample(), &[])
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<OpenMetricsEncoder<W>> for ExemplarHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.sample();
+        let exemplars = self.exemplars.lock();
+        write_histogram(enc, metadata, labels, name, sample, &exemplars)
+    }
+}
+
+/// Only the cumulative histogram is written, with all of its buckets. The recent window is not exported.
+impl<W: Write, const N: usize> MetricEncoding<OpenMetricsEncoder<W>> for WindowedHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Window<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        self.histogram
+            .collect_into(metadata.thresholds(), labels, name, enc)
+    }
+}
+
+/// The text format cannot represent the sparse buckets, so only the `+Inf` bucket, sum and count are written.
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for NativeHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        _m: &NativeHistogramConfig,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.inner.write().sample();
+
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Bucket),
+            labels
+                .by_ref()
+                .compose_with(HistogramLabelLe { le: f64::INFINITY }),
+            MetricValue::Int(sample.count as i64),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(sample.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels.by_ref(),
+            MetricValue::Int(sample.count as i64),
+        )?;
+        enc.write_created(name, labels)
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for SummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        metadata: &Quantiles,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let (values, count, sum) = self.inner.lock().sample(metadata);
+
+        for (&(quantile, _), value) in metadata.get().iter().zip(values) {
+            enc.write_metric_value(
+                &name,
+                labels
+                    .by_ref()
+                    .compose_with(SummaryLabelQuantile { quantile }),
+                MetricValue::Float(value),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels.by_ref(),
+            MetricValue::Int(count as i64),
+        )?;
+        enc.write_created(name, labels)
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for CounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(core::sync::atomic::Ordering::Relaxed);
+        enc.write_counter(name, labels, count, None)
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for ShardedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_counter(enc, name, labels, self.get())
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for ExemplarCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(core::sync::atomic::Ordering::Relaxed);
+        enc.write_counter(name, labels, count, self.exemplar.lock().as_ref())
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for GaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Gauge)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(
+            &name,
+            labels,
+            MetricValue::Int(self.count.load(core::sync::atomic::Ordering::Relaxed)),
+        )
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for FloatGaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Gauge)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(&name, labels, MetricValue::Float(self.count.get()))
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for UntypedState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Untyped)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metric_value(&name, labels, MetricValue::Float(self.value.get()))
+    }
+}
+
+/// The `_created` sample is not written, as the scraped endpoint's created timestamp is not known.
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for FederatedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Counter)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_counter_total(name, labels, count_value(self.value), None)
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for FederatedHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Histogram)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        for &(le, count) in &self.buckets {
+            enc.write_metric_value(
+                name.by_ref().with_suffix(Bucket),
+                labels.by_ref().compose_with(HistogramLabelLe { le }),
+                MetricValue::Int(count as i64),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(self.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(self.count as i64),
+        )
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for FederatedSummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Summary)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        for &(quantile, value) in &self.quantiles {
+            enc.write_metric_value(
+                &name,
+                labels
+                    .by_ref()
+                    .compose_with(SummaryLabelQuantile { quantile }),
+                MetricValue::Float(value),
+            )?;
+        }
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Sum),
+            labels.by_ref(),
+            MetricValue::Float(self.sum),
+        )?;
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Count),
+            labels,
+            MetricValue::Int(self.count as i64),
+        )
+    }
+}
+
+impl<W: Write> MetricEncoding<OpenMetricsEncoder<W>> for InfoState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::Info)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let info = self.labels.read();
+        enc.write_metric_value(
+            name.by_ref().with_suffix(Info),
+            labels.compose_with(InfoLabels(&info)),
+            MetricValue::Int(1),
+        )
+    }
+}
+
+impl<T: FixedCardinalityLabel, W: Write> MetricEncoding<OpenMetricsEncoder<W>>
+    for StateSetState<T>
+{
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.write_type(&name, MetricType::StateSet)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut OpenMetricsEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let label_name = state_set_label_name(&name);
+        let label_name = LabelName::from_str(&label_name);
+        let state = self.state.load(core::sync::atomic::Ordering::Relaxed);
+        for i in 0..T::cardinality() {
+            let label = StateSetLabel {
+                name: label_name,
+                state: T::decode(i),
+            };
+            enc.write_metric_value(
+                &name,
+                labels.by_ref().compose_with(label),
+                MetricValue::Int((i == state) as i64),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use bytes::{BufMut, BytesMut};
+
+    use crate::{
+        label::StaticLabelSet,
+        metric::{
+            exemplar::Exemplar, group::Encoding, histogram::Thresholds, name::MetricName,
+            MetricFamilyEncoding,
+        },
+        CounterVec, ExemplarCounter, ExemplarHistogram, FloatGauge, Histogram, Info, StateSet,
+        Untyped,
+    };
+
+    use super::OpenMetricsEncoder;
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured_derive::LabelGroup)]
+    #[label(crate = crate, set = RequestLabelSet)]
+    struct RequestLabels {
+        method: Method,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured_derive::FixedCardinalityLabel)]
+    #[label(crate = crate, rename_all = "snake_case")]
+    enum Method {
+        Post,
+        Get,
+    }
+
+    #[test]
+    fn openmetrics_encoding() {
+        let requests = CounterVec::with_label_set(RequestLabelSet {
+            method: StaticLabelSet::new(),
+        });
+        requests.inc_by(
+            RequestLabels {
+                method: Method::Post,
+            },
+            1027,
+        );
+        requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            3,
+        );
+
+        let histogram = Histogram::with_metadata(Thresholds::<2>::exponential_buckets(0.5, 2.0));
+        histogram.observe(0.7);
+        histogram.observe(2.5);
+
+        let temperature = FloatGauge::new();
+        temperature.set(f64::NEG_INFINITY);
+
+        let created = SystemTime::UNIX_EPOCH + Duration::from_millis(1520430000123);
+        let mut encoder =
+            OpenMetricsEncoder::new(BytesMut::new().writer()).with_created_timestamp(created);
+
+        let name = MetricName::from_str("http_request");
+        encoder
+            .write_help(name, "The total number of \"HTTP\" requests.")
+            .unwrap();
+        requests.collect_family_into(name, &mut encoder).unwrap();
+
+        let name = MetricName::from_str("http_request_duration_seconds");
+        encoder.write_unit(name, "seconds").unwrap();
+        histogram.collect_family_into(name, &mut encoder).unwrap();
+
+        let name = MetricName::from_str("temperature_celsius");
+        temperature.collect_family_into(name, &mut encoder).unwrap();
+
+        encoder.finish().unwrap();
+
+        let s = String::from_utf8(encoder.writer.into_inner().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# HELP http_request The total number of \"HTTP\" requests.
+# TYPE http_request counter
+http_request_total{method="post"} 1027
+http_request_created{method="post"} 1520430000.123
+http_request_total{method="get"} 3
+http_request_created{method="get"} 1520430000.123
+# UNIT http_request_duration_seconds seconds
+# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{le="0.5"} 0
+http_request_duration_seconds_bucket{le="1.0"} 1
+http_request_duration_seconds_bucket{le="+Inf"} 2
+http_request_duration_seconds_sum 3.2
+http_request_duration_seconds_count 2
+http_request_duration_seconds_created 1520430000.123
+# TYPE temperature_celsius gauge
+temperature_celsius -Inf
+# EOF
+"#
+        );
+    }
+
+    #[test]
+    fn counter_total_suffix() {
+        let requests = CounterVec::with_label_set(RequestLabelSet {
+            method: StaticLabelSet::new(),
+        });
+        requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            3,
+        );
+
+        let created = SystemTime::UNIX_EPOCH + Duration::from_millis(1520430000123);
+        let mut encoder =
+            OpenMetricsEncoder::new(BytesMut::new().writer()).with_created_timestamp(created);
+
+        let name = MetricName::from_str("http_requests_total");
+        encoder.write_help(name, "number of requests").unwrap();
+        requests.collect_family_into(name, &mut encoder).unwrap();
+        encoder.finish().unwrap();
+
+        let s = String::from_utf8(encoder.writer.into_inner().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# HELP http_requests number of requests
+# TYPE http_requests counter
+http_requests_total{method="get"} 3
+http_requests_created{method="get"} 1520430000.123
+# EOF
+"#
+        );
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured_derive::FixedCardinalityLabel)]
+    #[label(crate = crate, singleton = "trace_id")]
+    enum Trace {
+        #[label(rename = "4bf92f3577b34da6")]
+        A,
+        #[label(rename = "00f067aa0ba902b7")]
+        B,
+    }
+
+    #[test]
+    fn exemplars() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(1520879607789);
+        let fix_timestamp =
+            |e: &mut Exemplar| *e = Exemplar::with_timestamp(&*e, e.value(), timestamp);
+
+        let counter = ExemplarCounter::new();
+        counter.inc_by_with_exemplar(2, Trace::A);
+        fix_timestamp(counter.get_metric().exemplar.lock().as_mut().unwrap());
+
+        let histogram =
+            ExemplarHistogram::with_metadata(Thresholds::<2>::exponential_buckets(0.5, 2.0));
+        histogram.observe(0.3);
+        histogram.observe_with_exemplar(0.7, Trace::A);
+        histogram.observe_with_exemplar(0.8, Trace::B);
+        histogram.observe_with_exemplar(2.5, Trace::A);
+        histogram
+            .get_metric()
+            .exemplars
+            .lock()
+            .iter_mut()
+            .flatten()
+            .for_each(fix_timestamp);
+
+        let mut encoder = OpenMetricsEncoder::new(BytesMut::new().writer());
+
+        let name = MetricName::from_str("http_request");
+        counter.collect_family_into(name, &mut encoder).unwrap();
+        let name = MetricName::from_str("http_request_duration_seconds");
+        histogram.collect_family_into(name, &mut encoder).unwrap();
+        encoder.finish().unwrap();
+
+        let s = String::from_utf8(encoder.writer.into_inner().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# TYPE http_request counter
+http_request_total 2 # {trace_id="4bf92f3577b34da6"} 2.0 1520879607.789
+# TYPE http_request_duration_seconds histogram
+http_request_duration_seconds_bucket{le="0.5"} 1
+http_request_duration_seconds_bucket{le="1.0"} 3 # {trace_id="00f067aa0ba902b7"} 0.8 1520879607.789
+http_request_duration_seconds_bucket{le="+Inf"} 4 # {trace_id="4bf92f3577b34da6"} 2.5 1520879607.789
+http_request_duration_seconds_sum 4.3
+http_request_duration_seconds_count 4
+# EOF
+"#
+        );
+    }
+
+    #[test]
+    fn info_and_state_set() {
+        let info = Info::new();
+        info.set_info(RequestLabels {
+            method: Method::Get,
+        });
+        let state = StateSet::new();
+        state.set(Method::Post);
+        let untyped = Untyped::new();
+        untyped.set(-1.5);
+
+        let mut encoder = OpenMetricsEncoder::new(BytesMut::new().writer());
+
+        let name = MetricName::from_str("build");
+        info.collect_family_into(name, &mut encoder).unwrap();
+        let name = MetricName::from_str("http:method");
+        state.collect_family_into(name, &mut encoder).unwrap();
+        let name = MetricName::from_str("forwarded");
+        untyped.collect_family_into(name, &mut encoder).unwrap();
+        encoder.finish().unwrap();
+
+        let s = String::from_utf8(encoder.writer.into_inner().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# TYPE build info
+build_info{method="get"} 1
+# TYPE http:method stateset
+http:method{http_method="post"} 1
+http:method{http_method="get"} 0
+# TYPE forwarded unknown
+forwarded -1.5
+# EOF
+"#
+        );
+    }
+
+    #[derive(crate::MetricGroup)]
+    #[metric(crate = crate, new())]
+    struct Units {
+        /// time spent handling requests
+        #[metric(unit = "seconds")]
+        #[metric(metadata = Thresholds::with_buckets([0.1, 1.0]))]
+        request_duration: Histogram<2>,
+        /// time spent waiting for a connection
+        #[metric(unit = "milliseconds")]
+        #[metric(metadata = Thresholds::with_buckets([100.0, 1000.0]))]
+        pool_wait_milliseconds: Histogram<2>,
+        #[metric(unit = "bytes")]
+        response_size: FloatGauge,
+    }
+
+    #[test]
+    fn units() {
+        let units = Units::new();
+        units
+            .request_duration
+            .observe_duration(Duration::from_millis(500));
+        units
+            .pool_wait_milliseconds
+            .observe_duration(Duration::from_millis(500));
+        units.response_size.set(512.0);
+
+        let mut encoder = OpenMetricsEncoder::new(BytesMut::new().writer());
+        crate::MetricGroup::collect_group_into(&units, &mut encoder).unwrap();
+        encoder.finish().unwrap();
+
+        let s = String::from_utf8(encoder.writer.into_inner().to_vec()).unwrap();
+        assert_eq!(
+            s,
+            r#"# HELP request_duration_seconds time spent handling requests
+# UNIT request_duration_seconds seconds
+# TYPE request_duration_seconds histogram
+request_duration_seconds_bucket{le="0.1"} 0
+request_duration_seconds_bucket{le="1.0"} 1
+request_duration_seconds_bucket{le="+Inf"} 1
+request_duration_seconds_sum 0.5
+request_duration_seconds_count 1
+# HELP pool_wait_milliseconds time spent waiting for a connection
+# UNIT pool_wait_milliseconds milliseconds
+# TYPE pool_wait_milliseconds histogram
+pool_wait_milliseconds_bucket{le="100.0"} 0
+pool_wait_milliseconds_bucket{le="1000.0"} 1
+pool_wait_milliseconds_bucket{le="+Inf"} 1
+pool_wait_milliseconds_sum 500.0
+pool_wait_milliseconds_count 1
+# UNIT response_size_bytes bytes
+# TYPE response_size_bytes gauge
+response_size_bytes 512.0
+# EOF
+"#
+        );
+    }
+
+    #[test]
+    #[should_panic = "cannot observe durations in \"bytes\""]
+    fn duration_in_non_time_unit() {
+        Thresholds::with_buckets([1.0]).with_unit(crate::metric::name::Unit::BYTES);
+    }
+}
@@ -0,0 +1,496 @@
+//! Parse the prometheus text exposition format (version 0.0.4).
+//!
+//! This is the format written by [`TextEncoder`](super::TextEncoder). It can be used to
+//! round-trip test the encoder output, or to ingest metrics from other exporters.
+//!
+//! ```
+//! use measured::text::{parse::parse, MetricType};
+//!
+//! let families = parse(concat!(
+//!     "# HELP http_requests_total The total number of HTTP requests.\n",
+//!     "# TYPE http_requests_total counter\n",
+//!     "http_requests_total{method=\"post\",code=\"200\"} 1027\n",
+//! ))
+//! .unwrap();
+//!
+//! assert_eq!(families[0].name, "http_requests_total");
+//! assert_eq!(families[0].metric_type, MetricType::Counter);
+//! assert_eq!(families[0].samples[0].label("method"), Some("post"));
+//! assert_eq!(families[0].samples[0].value, 1027.0);
+//! ```
+
+use super::MetricType;
+
+/// A parsed metric family
+#[derive(Clone, Debug, PartialEq)]
+pub struct MetricFamily {
+    /// The name of the metric family
+    pub name: String,
+    /// The help text, if any was given
+    pub help: Option<String>,
+    /// The metric type. Families without a `TYPE` line are [`MetricType::Untyped`]
+    pub metric_type: MetricType,
+    /// All samples within this family, in the order they were written.
+    ///
+    /// For histograms and summaries, this includes the `_bucket`, `_sum` and `_count` samples.
+    pub samples: Vec<Sample>,
+}
+
+/// A parsed sample line
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sample {
+    /// The name of the sample, including any suffix such as `_bucket`
+    pub name: String,
+    /// The labels of the sample, in the order they were written
+    pub labels: Vec<(String, String)>,
+    /// The sample value
+    pub value: f64,
+    /// The optional timestamp, in milliseconds since the unix epoch
+    pub timestamp: Option<i64>,
+}
+
+impl Sample {
+    /// Get the value of the label with the given name
+    pub fn label(&self, name: &str) -> Option<&str> {
+        self.labels
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| &**v)
+    }
+}
+
+impl MetricFamily {
+    fn new(name: &str) -> Self {
+        Self {
+            name: name.to_owned(),
+            help: None,
+            metric_type: MetricType::Untyped,
+            samples: vec![],
+        }
+    }
+
+    /// Whether a sample with the given name belongs to this family
+    fn contains(&self, sample: &str) -> bool {
+        let Some(suffix) = sample.strip_prefix(&*self.name) else {
+            return false;
+        };
+        match self.metric_type {
+            _ if suffix.is_empty() => true,
+            MetricType::Histogram => matches!(suffix, "_bucket" | "_sum" | "_count"),
+            MetricType::Summary => matches!(suffix, "_sum" | "_count"),
+            _ => false,
+        }
+    }
+}
+
+/// An error returned when the text could not be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// The line number the error occurred on, starting from 1
+    pub line: usize,
+    /// What went wrong
+    pub kind: ParseErrorKind,
+}
+
+/// What went wrong when parsing the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParseErrorKind {
+    /// A metric name was missing or contained invalid characters
+    InvalidMetricName,
+    /// A label name was missing or contained invalid characters
+    InvalidLabelName,
+    /// The label set was not correctly formatted
+    InvalidLabels,
+    /// A label value contained an unknown escape sequence
+    InvalidEscape,
+    /// The `TYPE` line named an unknown type
+    InvalidType,
+    /// The `TYPE` line was given after samples for that metric were already written
+    UnexpectedType,
+    /// The sample value could not be parsed
+    InvalidValue,
+    /// The sample timestamp could not be parsed
+    InvalidTimestamp,
+    /// The input was not valid UTF-8
+    InvalidUtf8,
+}
+
+impl core::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let msg = match self.kind {
+            ParseErrorKind::InvalidMetricName => "invalid metric name",
+            ParseErrorKind::InvalidLabelName => "invalid label name",
+            ParseErrorKind::InvalidLabels => "invalid label set",
+            ParseErrorKind::InvalidEscape => "invalid escape sequence",
+            ParseErrorKind::InvalidType => "invalid metric type",
+            ParseErrorKind::UnexpectedType => "type given after samples",
+            ParseErrorKind::InvalidValue => "invalid sample value",
+            ParseErrorKind::InvalidTimestamp => "invalid sample timestamp",
+            ParseErrorKind::InvalidUtf8 => "invalid utf-8",
+        };
+        write!(f, "line {}: {msg}", self.line)
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// Parse the text exposition format from raw bytes, such as an HTTP response body.
+///
+/// See [`parse`] for details.
+pub fn parse_bytes(input: &[u8]) -> Result<Vec<MetricFamily>, ParseError> {
+    match core::str::from_utf8(input) {
+        Ok(input) => parse(input),
+        Err(e) => Err(ParseError {
+            line: memchr::memchr_iter(b'\n', &input[..e.valid_up_to()]).count() + 1,
+            kind: ParseErrorKind::InvalidUtf8,
+        }),
+    }
+}
+
+/// Parse the text exposition format into metric families.
+///
+/// Samples that are not preceded by a `HELP` or `TYPE` line for their family are grouped into untyped families.
+pub fn parse(input: &str) -> Result<Vec<MetricFamily>, ParseError> {
+    let mut families: Vec<MetricFamily> = vec![];
+
+    for (i, line) in input.lines().enumerate() {
+        let err = |kind| ParseError { line: i + 1, kind };
+        let line = line.trim();
+
+        if let Some(comment) = line.strip_prefix('#') {
+            let comment = comment.trim_start();
+            let (keyword, rest) = comment.split_once([' ', '\t']).unwrap_or((comment, ""));
+            if keyword != "HELP" && keyword != "TYPE" {
+                continue;
+            }
+
+            let rest = rest.trim_start();
+            let (name, rest) = rest.split_once([' ', '\t']).unwrap_or((rest, ""));
+            if !is_metric_name(name) {
+                return Err(err(ParseErrorKind::InvalidMetricName));
+            }
+
+            let family = match families.last_mut() {
+                Some(family) if family.name == name => family,
+                _ => {
+                    families.push(MetricFamily::new(name));
+                    families.last_mut().unwrap()
+                }
+            };
+
+            if keyword == "HELP" {
+                family.help = Some(unescape(rest.trim_start(), false).map_err(err)?);
+            } else {
+                if !family.samples.is_empty() {
+                    return Err(err(ParseErrorKind::UnexpectedType));
+                }
+                family.metric_type = match rest.trim() {
+                    "counter" => MetricType::Counter,
+                    "gauge" => MetricType::Gauge,
+                    "histogram" => MetricType::Histogram,
+                    "summary" => MetricType::Summary,
+                    "untyped" => MetricType::Untyped,
+                    _ => return Err(err(ParseErrorKind::InvalidType)),
+                };
+            }
+            continue;
+        }
+
+        if line.is_empty() {
+            continue;
+        }
+
+        let sample = parse_sample(line).map_err(err)?;
+        match families.last_mut() {
+            Some(family) if family.contains(&sample.name) => family.samples.push(sample),
+            _ => {
+                let mut family = MetricFamily::new(&sample.name);
+                family.samples.push(sample);
+                families.push(family);
+            }
+        }
+    }
+
+    Ok(families)
+}
+
+fn is_metric_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.as_bytes()[0].is_ascii_digit()
+        && name
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || b == b'_' || b == b':')
+}
+
+pub(super) fn is_label_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.as_bytes()[0].is_ascii_digit()
+        && name.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'_')
+}
+
+fn parse_sample(line: &str) -> Result<Sample, ParseErrorKind> {
+    let name_end = line
+        .find(|c: char| c == '{' || c.is_ascii_whitespace())
+        .unwrap_or(line.len());
+    let (name, mut rest) = line.split_at(name_end);
+    if !is_metric_name(name) {
+        return Err(ParseErrorKind::InvalidMetricName);
+    }
+
+    let mut labels = vec![];
+    if let Some(label_set) = rest.trim_start().strip_prefix('{') {
+        rest = parse_labels(label_set, &mut labels)?;
+    }
+
+    let mut parts = rest.split_ascii_whitespace();
+    let value = parts.next().ok_or(ParseErrorKind::InvalidValue)?;
+    let value = parse_value(value).ok_or(ParseErrorKind::InvalidValue)?;
+    let timestamp = parts
+        .next()
+        .map(|t| t.parse().map_err(|_| ParseErrorKind::InvalidTimestamp))
+        .transpose()?;
+    if parts.next().is_some() {
+        return Err(ParseErrorKind::InvalidTimestamp);
+    }
+
+    Ok(Sample {
+        name: name.to_owned(),
+        labels,
+        value,
+        timestamp,
+    })
+}
+
+/// Parse the labels following the opening brace. Returns the remainder of the line after the closing brace.
+fn parse_labels<'a>(
+    mut s: &'a str,
+    labels: &mut Vec<(String, String)>,
+) -> Result<&'a str, ParseErrorKind> {
+    loop {
+        s = s.trim_start();
+        if let Some(rest) = s.strip_prefix('}') {
+            return Ok(rest);
+        }
+
+        let (name, rest) = s.split_once('=').ok_or(ParseErrorKind::InvalidLabels)?;
+        let name = name.trim_end();
+        if !is_label_name(name) {
+            return Err(ParseErrorKind::InvalidLabelName);
+        }
+
+        let rest = rest
+            .trim_start()
+            .strip_prefix('"')
+            .ok_or(ParseErrorKind::InvalidLabels)?;
+        let end = find_closing_quote(rest).ok_or(ParseErrorKind::InvalidLabels)?;
+        labels.push((name.to_owned(), unescape(&rest[..end], true)?));
+
+        s = rest[end + 1..].trim_start();
+        if let Some(rest) = s.strip_prefix(',') {
+            s = rest;
+        } else if !s.starts_with('}') {
+            return Err(ParseErrorKind::InvalidLabels);
+        }
+    }
+}
+
+/// Find the first quote that is not escaped
+fn find_closing_quote(s: &str) -> Option<usize> {
+    let mut escaped = false;
+    for (i, b) in s.bytes().enumerate() {
+        match b {
+            _ if escaped => escaped = false,
+            b'\\' => escaped = true,
+            b'"' => return Some(i),
+            _ => {}
+        }
+    }
+    None
+}
+
+/// Reverse the escaping of label values and help text.
+/// Label values can additionally escape double quotes.
+fn unescape(s: &str, quotes: bool) -> Result<String, ParseErrorKind> {
+    let mut out = String::with_capacity(s.len());
+    let mut chars = s.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('\\') => out.push('\\'),
+            Some('n') => out.push('\n'),
+            Some('"') if quotes => out.push('"'),
+            _ => return Err(ParseErrorKind::InvalidEscape),
+        }
+    }
+    Ok(out)
+}
+
+pub(super) fn parse_value(s: &str) -> Option<f64> {
+    match s {
+        "+Inf" => Some(f64::INFINITY),
+        "-Inf" => Some(f64::NEG_INFINITY),
+        "NaN" => Some(f64::NAN),
+        // rust would accept other spellings of these, such as "inf"
+        _ if s
+            .bytes()
+            .any(|b| b.is_ascii_alphabetic() && b != b'e' && b != b'E') =>
+        {
+            None
+        }
+        _ => s.parse().ok(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        label::{LabelGroupVisitor, LabelName},
+        metric::histogram::Thresholds,
+        text::{BufferedTextEncoder, MetricType},
+        Counter, Histogram, Info, LabelGroup, MetricGroup,
+    };
+
+    use super::{parse, parse_bytes, ParseError, ParseErrorKind, Sample};
+
+    struct PathLabels<'a> {
+        path: &'a str,
+    }
+
+    impl LabelGroup for PathLabels<'_> {
+        fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+            const PATH: &LabelName = LabelName::from_str("path");
+            v.write_value(PATH, &self.path);
+        }
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    struct Metrics {
+        /// total number of requests
+        requests: Counter,
+        config: Info,
+        /// request latency
+        latency: Histogram<2>,
+    }
+
+    fn sample(name: &str, labels: &[(&str, &str)], value: f64) -> Sample {
+        Sample {
+            name: name.to_owned(),
+            labels: labels
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            value,
+            timestamp: None,
+        }
+    }
+
+    #[test]
+    fn round_trip() {
+        let metrics = Metrics {
+            requests: Counter::new(),
+            config: Info::new(),
+            latency: Histogram::with_metadata(Thresholds::with_buckets([0.1, 1.0])),
+        };
+        metrics.requests.inc_by(3);
+        metrics.config.set_info(PathLabels {
+            path: "C:\\some \"path\"\nnext line",
+        });
+        metrics.latency.observe(0.5);
+        metrics.latency.observe(5.0);
+
+        let mut enc = BufferedTextEncoder::new();
+        metrics.collect_group_into(&mut enc).unwrap();
+        let text = enc.finish();
+        let families = parse(std::str::from_utf8(&text).unwrap()).unwrap();
+
+        assert_eq!(families.len(), 3);
+
+        assert_eq!(families[0].name, "requests");
+        assert_eq!(
+            families[0].help.as_deref(),
+            Some("total number of requests")
+        );
+        assert_eq!(families[0].metric_type, MetricType::Counter);
+        assert_eq!(families[0].samples, [sample("requests", &[], 3.0)]);
+
+        // info metrics are written as gauges in the text format, with the same `_info` suffix as OpenMetrics
+        assert_eq!(families[1].name, "config_info");
+        assert_eq!(families[1].help, None);
+        assert_eq!(families[1].metric_type, MetricType::Gauge);
+        assert_eq!(
+            families[1].samples,
+            [sample(
+                "config_info",
+                &[("path", "C:\\some \"path\"\nnext line")],
+                1.0
+            )]
+        );
+
+        assert_eq!(families[2].name, "latency");
+        assert_eq!(families[2].metric_type, MetricType::Histogram);
+        assert_eq!(
+            families[2].samples,
+            [
+                sample("latency_bucket", &[("le", "0.1")], 0.0),
+                sample("latency_bucket", &[("le", "1.0")], 1.0),
+                sample("latency_bucket", &[("le", "+Inf")], 2.0),
+                sample("latency_sum", &[], 5.5),
+                sample("latency_count", &[], 2.0),
+            ]
+        );
+    }
+
+    #[test]
+    fn untyped_and_timestamps() {
+        let families = parse(concat!(
+            "# some comment\n",
+            "\n",
+            "up 1 1700000000000\n",
+            "temperature { room = \"kitchen\" , } NaN\n",
+            "temperature{room=\"garage\"} -Inf\n",
+        ))
+        .unwrap();
+
+        assert_eq!(families.len(), 2);
+        assert_eq!(families[0].metric_type, MetricType::Untyped);
+        assert_eq!(families[0].samples[0].timestamp, Some(1_700_000_000_000));
+
+        assert_eq!(families[1].name, "temperature");
+        assert_eq!(families[1].samples.len(), 2);
+        assert_eq!(families[1].samples[0].label("room"), Some("kitchen"));
+        assert!(families[1].samples[0].value.is_nan());
+        assert_eq!(families[1].samples[1].value, f64::NEG_INFINITY);
+    }
+
+    #[test]
+    fn errors() {
+        let err = |line, kind| Err(ParseError { line, kind });
+
+        assert_eq!(
+            parse("ok 1\n1bad 1\n"),
+            err(2, ParseErrorKind::InvalidMetricName)
+        );
+        assert_eq!(parse("m{a=\"b} 1"), err(1, ParseErrorKind::InvalidLabels));
+        assert_eq!(
+            parse("m{a=\"\\t\"} 1"),
+            err(1, ParseErrorKind::InvalidEscape)
+        );
+        assert_eq!(parse("m inf"), err(1, ParseErrorKind::InvalidValue));
+        assert_eq!(
+            parse("# TYPE m counter\nm 1\n# TYPE m gauge\n"),
+            err(3, ParseErrorKind::UnexpectedType)
+        );
+        assert_eq!(
+            parse("# TYPE m info\n"),
+            err(1, ParseErrorKind::InvalidType)
+        );
+        assert_eq!(
+            parse_bytes(b"ok 1\nbad{a=\"\xff\"} 1\n"),
+            err(2, ParseErrorKind::InvalidUtf8)
+        );
+    }
+}
@@ -0,0 +1,514 @@
+//! Check that a metric group produces a well formed exposition.
+//!
+//! Nothing stops two fields in nested groups from writing the same metric family, or a label group from using a label
+//! name that the metric type already uses itself. Prometheus rejects such an exposition when it is scraped.
+//!
+//! A [`ValidatingEncoder`] wraps another encoder and records every metric family written during a
+//! [`MetricGroup::collect_group_into`] pass. In tests, [`assert_valid`] is a convenient shorthand.
+//!
+//! ```
+//! use measured::{validate::validate, Counter, Gauge, MetricGroup};
+//!
+//! #[derive(MetricGroup, Default)]
+//! struct Http {
+//!     /// number of requests
+//!     requests_total: Counter,
+//! }
+//!
+//! #[derive(MetricGroup, Default)]
+//! struct App {
+//!     #[metric(namespace = "http")]
+//!     http: Http,
+//!
+//!     /// conflicts with the `http_requests_total` counter above
+//!     http_requests_total: Gauge,
+//! }
+//!
+//! let err = validate(&App::default()).unwrap_err();
+//! assert_eq!(
+//!     err.to_string(),
+//!     "metric family \"http_requests_total\" is written as both a counter and a gauge"
+//! );
+//! ```
+
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    label::{LabelGroupVisitor, LabelName, LabelValue},
+    metric::{
+        describe::MetricTypeDescribe,
+        group::{Encoding, MetricGroup},
+        name::{MetricNameEncoder, Unit},
+        MetricEncoding,
+    },
+    text::{BufferedTextEncoder, MetricType},
+    LabelGroup,
+};
+
+/// An [`Encoding`] wrapper which checks for duplicate and conflicting metric families.
+///
+/// All writes are forwarded to the inner encoder. Any problems found are returned from
+/// [`ValidatingEncoder::finish`].
+pub struct ValidatingEncoder<E> {
+    inner: E,
+    types: HashMap<String, MetricType>,
+    helps: HashSet<String>,
+    problems: Vec<Problem>,
+}
+
+/// A problem found by a [`ValidatingEncoder`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Problem {
+    /// The metric family was written more than once
+    DuplicateFamily {
+        /// The name of the metric family
+        family: String,
+    },
+    /// The metric family was written more than once, by different metric types
+    ConflictingType {
+        /// The name of the metric family
+        family: String,
+        /// The metric type that was written first
+        first: MetricType,
+        /// The metric type that was written second
+        second: MetricType,
+    },
+    /// The metric family has the same name as one of the samples of a histogram or summary, such as
+    /// a `foo_count` counter alongside a `foo` histogram
+    SampleCollision {
+        /// The name of the metric family
+        family: String,
+        /// The name of the histogram or summary family whose samples it collides with
+        other: String,
+    },
+    /// A sample has a label which the metric type reserves for itself, such as `le` for histograms
+    ReservedLabel {
+        /// The name of the metric family
+        family: String,
+        /// The reserved label name
+        label: &'static str,
+    },
+    /// A sample has the same label name more than once
+    DuplicateLabel {
+        /// The name of the metric family
+        family: String,
+        /// The repeated label name
+        label: String,
+    },
+}
+
+impl core::fmt::Display for Problem {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match self {
+            Problem::DuplicateFamily { family } => {
+                write!(f, "metric family {family:?} is written more than once")
+            }
+            Problem::ConflictingType {
+                family,
+                first,
+                second,
+            } => write!(
+                f,
+                "metric family {family:?} is written as both {} and {}",
+                type_name(*first),
+                type_name(*second)
+            ),
+            Problem::SampleCollision { family, other } => write!(
+                f,
+                "metric family {family:?} has the same name as a sample of metric family {other:?}"
+            ),
+            Problem::ReservedLabel { family, label } => write!(
+                f,
+                "metric family {family:?} uses the reserved label name {label:?}"
+            ),
+            Problem::DuplicateLabel { family, label } => write!(
+                f,
+                "metric family {family:?} has the label name {label:?} more than once"
+            ),
+        }
+    }
+}
+
+/// The problems found while validating a metric group
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// The problems, in the order they were found
+    pub problems: Vec<Problem>,
+}
+
+impl core::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        for (i, problem) in self.problems.iter().enumerate() {
+            if i > 0 {
+                f.write_str("\n")?;
+            }
+            write!(f, "{problem}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+impl<E> ValidatingEncoder<E> {
+    /// Wrap the given encoder
+    pub fn new(inner: E) -> Self {
+        Self {
+            inner,
+            types: HashMap::new(),
+            helps: HashSet::new(),
+            problems: vec![],
+        }
+    }
+
+    /// Get a reference to the inner encoder
+    pub fn inner(&self) -> &E {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the inner encoder
+    pub fn inner_mut(&mut self) -> &mut E {
+        &mut self.inner
+    }
+
+    /// Unwrap the inner encoder, discarding any recorded problems
+    pub fn into_inner(self) -> E {
+        self.inner
+    }
+
+    /// Report the problems found since the last call to `finish`, and reset the recorded metric families.
+    pub fn finish(&mut self) -> Result<(), ValidationError> {
+        self.types.clear();
+        self.helps.clear();
+        let problems = core::mem::take(&mut self.problems);
+        if problems.is_empty() {
+            Ok(())
+        } else {
+            Err(ValidationError { problems })
+        }
+    }
+
+    fn report(&mut self, problem: Problem) {
+        if self.problems.contains(&problem) {
+            return;
+        }
+        // a conflicting type is more specific than a duplicate family, so it replaces it
+        let family = match &problem {
+            Problem::DuplicateFamily { family } | Problem::ConflictingType { family, .. } => family,
+            _ => return self.problems.push(problem),
+        };
+        let existing = self.problems.iter().position(|p| match p {
+            Problem::DuplicateFamily { family: f } | Problem::ConflictingType { family: f, .. } => {
+                f == family
+            }
+            _ => false,
+        });
+        match existing {
+            None => self.problems.push(problem),
+            Some(i) if matches!(problem, Problem::ConflictingType { .. }) => {
+                self.problems[i] = problem
+            }
+            Some(_) => {}
+        }
+    }
+}
+
+impl<E: Encoding> Encoding for ValidatingEncoder<E> {
+    type Err = E::Err;
+
+    fn write_help(&mut self, name: impl MetricNameEncoder, help: &str) -> Result<(), Self::Err> {
+        let family = name_to_string(&name);
+        if !self.helps.insert(family.clone()) {
+            self.report(Problem::DuplicateFamily { family });
+        }
+        self.inner.write_help(name, help)
+    }
+
+    fn write_unit(&mut self, name: impl MetricNameEncoder, unit: Unit) -> Result<(), Self::Err> {
+        self.inner.write_unit(name, unit)
+    }
+}
+
+impl<M: MetricEncoding<E> + MetricTypeDescribe, E: Encoding> MetricEncoding<ValidatingEncoder<E>>
+    for M
+{
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ValidatingEncoder<E>,
+    ) -> Result<(), E::Err> {
+        let family = name_to_string(&name);
+        let kind = M::METRIC_TYPE;
+
+        // the samples of this family must not share a name with another family, or the other way around
+        for &suffix in sample_suffixes(kind) {
+            let sample = format!("{family}{suffix}");
+            if enc.types.contains_key(&sample) {
+                enc.report(Problem::SampleCollision {
+                    family: sample,
+                    other: family.clone(),
+                });
+            }
+        }
+        let others: Vec<String> = enc
+            .types
+            .iter()
+            .filter(|&(other, &other_kind)| {
+                family
+                    .strip_prefix(other.as_str())
+                    .is_some_and(|suffix| sample_suffixes(other_kind).contains(&suffix))
+            })
+            .map(|(other, _)| other.clone())
+            .collect();
+        for other in others {
+            enc.report(Problem::SampleCollision {
+                family: family.clone(),
+                other,
+            });
+        }
+
+        match enc.types.get(&family) {
+            Some(&first) if first == kind => enc.report(Problem::DuplicateFamily { family }),
+            Some(&first) => enc.report(Problem::ConflictingType {
+                family,
+                first,
+                second: kind,
+            }),
+            None => {
+                enc.types.insert(family, kind);
+            }
+        }
+        M::write_type(name, &mut enc.inner)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &M::Metadata,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut ValidatingEncoder<E>,
+    ) -> Result<(), E::Err> {
+        let mut names = LabelNames(vec![]);
+        labels.visit_values(&mut names);
+
+        let reserved = reserved_label(M::METRIC_TYPE);
+        for (i, label) in names.0.iter().enumerate() {
+            if let Some(reserved) = reserved.filter(|&r| r == label) {
+                enc.report(Problem::ReservedLabel {
+                    family: name_to_string(&name),
+                    label: reserved,
+                });
+            }
+            if names.0[..i].contains(label) {
+                enc.report(Problem::DuplicateLabel {
+                    family: name_to_string(&name),
+                    label: label.clone(),
+                });
+            }
+        }
+
+        self.collect_into(metadata, labels, name, &mut enc.inner)
+    }
+}
+
+/// Collects the label names of a label group
+struct LabelNames(Vec<String>);
+
+impl LabelGroupVisitor for LabelNames {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, _x: &impl LabelValue) {
+        self.0.push(name.as_str().to_owned());
+    }
+}
+
+pub(crate) fn name_to_string(name: &impl MetricNameEncoder) -> String {
+    let mut b = Vec::with_capacity(name.encode_len());
+    name.encode_utf8(&mut b)
+        .expect("writing to a vec should not fail");
+    String::from_utf8(b).expect("metric names should be valid utf-8")
+}
+
+fn type_name(kind: MetricType) -> &'static str {
+    match kind {
+        MetricType::Counter => "a counter",
+        MetricType::Histogram => "a histogram",
+        MetricType::Gauge => "a gauge",
+        MetricType::Summary => "a summary",
+        MetricType::Untyped => "an untyped metric",
+        MetricType::Info => "an info metric",
+        MetricType::StateSet => "a state set",
+    }
+}
+
+/// The suffixes of the extra samples that the metric type writes
+fn sample_suffixes(kind: MetricType) -> &'static [&'static str] {
+    match kind {
+        MetricType::Histogram => &["_bucket", "_sum", "_count"],
+        MetricType::Summary => &["_sum", "_count"],
+        _ => &[],
+    }
+}
+
+/// The label name that the metric type writes itself
+fn reserved_label(kind: MetricType) -> Option<&'static str> {
+    match kind {
+        MetricType::Histogram => Some("le"),
+        MetricType::Summary => Some("quantile"),
+        _ => None,
+    }
+}
+
+/// Collect the metric group once and report any problems with the metric families it writes.
+pub fn validate<G>(group: &G) -> Result<(), ValidationError>
+where
+    G: MetricGroup<ValidatingEncoder<BufferedTextEncoder>> + ?Sized,
+{
+    let mut enc = ValidatingEncoder::new(BufferedTextEncoder::new());
+    let Ok(()) = group.collect_group_into(&mut enc);
+    enc.finish()
+}
+
+/// Assert that the metric group writes no duplicate or conflicting metric families.
+///
+/// # Panics
+///
+/// Panics with a description of every problem found by [`validate`].
+#[track_caller]
+pub fn assert_valid<G>(group: &G)
+where
+    G: MetricGroup<ValidatingEncoder<BufferedTextEncoder>> + ?Sized,
+{
+    if let Err(err) = validate(group) {
+        panic!("metric group is not valid:\n{err}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        label::StaticLabelSet, metric::histogram::Thresholds, text::MetricType, Counter,
+        FixedCardinalityLabel, Gauge, Histogram, HistogramVec, LabelGroup, MetricGroup,
+        NativeHistogramVec,
+    };
+
+    use super::{assert_valid, validate, Problem};
+
+    #[derive(FixedCardinalityLabel, Clone, Copy)]
+    #[label(crate = crate)]
+    enum Kind {
+        Read,
+        Write,
+    }
+
+    #[derive(LabelGroup)]
+    #[label(crate = crate, set = BucketSet)]
+    struct Bucket {
+        le: Kind,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate, new())]
+    struct Inner {
+        /// bytes read
+        bytes: Gauge,
+        /// request latency
+        #[metric(metadata = Thresholds::linear_buckets(0.0, 1.0))]
+        latency: Histogram<4>,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    struct Outer {
+        #[metric(namespace = "io")]
+        io: Inner,
+        #[metric(namespace = "io")]
+        io2: Inner,
+        /// conflicts with the `io_latency` histogram
+        io_latency: Gauge,
+        /// uses the histogram `le` label name for something else
+        ops: HistogramVec<BucketSet, 4>,
+    }
+
+    #[test]
+    fn problems() {
+        assert_valid(&Inner::new());
+
+        let outer = Outer {
+            io: Inner::new(),
+            io2: Inner::new(),
+            io_latency: Gauge::new(),
+            ops: HistogramVec::with_label_set_and_metadata(
+                BucketSet::new(),
+                Thresholds::linear_buckets(0.0, 1.0),
+            ),
+        };
+        outer
+            .ops
+            .get_metric(outer.ops.with_labels(Bucket { le: Kind::Write }));
+
+        let err = validate(&outer).unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                Problem::DuplicateFamily {
+                    family: "io_bytes".to_owned()
+                },
+                Problem::ConflictingType {
+                    family: "io_latency".to_owned(),
+                    first: MetricType::Histogram,
+                    second: MetricType::Gauge,
+                },
+                Problem::ReservedLabel {
+                    family: "ops".to_owned(),
+                    label: "le",
+                },
+            ]
+        );
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(crate = crate)]
+    struct Samples {
+        /// collides with the `ops` histogram written after it
+        ops_count: Counter,
+        /// histogram of operation sizes
+        ops: Histogram<4>,
+        /// collides with the `ops` histogram written before it
+        ops_bucket: Gauge,
+        /// native histograms also write the `le` label
+        sizes: NativeHistogramVec<BucketSet>,
+    }
+
+    #[test]
+    fn sample_collisions() {
+        let samples = Samples {
+            ops_count: Counter::new(),
+            ops: Histogram::with_metadata(Thresholds::linear_buckets(0.0, 1.0)),
+            ops_bucket: Gauge::new(),
+            sizes: NativeHistogramVec::with_label_set(BucketSet {
+                le: StaticLabelSet::new(),
+            }),
+        };
+        samples
+            .sizes
+            .get_metric(samples.sizes.with_labels(Bucket { le: Kind::Read }));
+
+        let err = validate(&samples).unwrap_err();
+        assert_eq!(
+            err.problems,
+            [
+                Problem::SampleCollision {
+                    family: "ops_count".to_owned(),
+                    other: "ops".to_owned(),
+                },
+                Problem::SampleCollision {
+                    family: "ops_bucket".to_owned(),
+                    other: "ops".to_owned(),
+                },
+                Problem::ReservedLabel {
+                    family: "sizes".to_owned(),
+                    label: "le",
+                },
+            ]
+        );
+    }
+}
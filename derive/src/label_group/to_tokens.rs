@@ -27,6 +27,17 @@ impl ToTokens for LabelGroup {
             }
         });
 
+        let names = fields.iter().map(|x| {
+            let LabelGroupField { name, attrs, .. } = x;
+            let name_string = attrs.rename.as_ref().map_or_else(|| name.to_string(), |r| r.value());
+            let ident = format_ident!("{}", name_string.to_shouty_snake_case(), span = x.span);
+            quote_spanned! { x.span =>
+                const #ident: &#krate::label::LabelName = #krate::label::LabelName::from_str(#name_string);
+                v(#ident);
+            }
+        });
+
+        let set_ident = &self.set_ident;
         let label_group_set = Set(self);
 
         tokens.extend(quote! {
@@ -38,6 +49,13 @@ impl ToTokens for LabelGroup {
             }
 
             #label_group_set
+
+            #[automatically_derived]
+            impl #krate::label::LabelGroupNames for #set_ident {
+                fn visit_names(&self, v: &mut impl FnMut(&#krate::label::LabelName)) {
+                    #(#names)*
+                }
+            }
         });
     }
 }
@@ -50,8 +50,9 @@ impl ContainerAttrs {
 #[derive(Clone)]
 pub struct MetricGroupFieldAttrs {
     pub kind: MetricGroupFieldAttrsKind,
-    pub docs: Option<String>,
+    pub help: Option<String>,
     pub init: Option<MetricGroupFieldAttrsInit>,
+    pub unit: Option<LitStr>,
 }
 
 #[derive(Clone)]
@@ -72,8 +73,11 @@ pub enum MetricGroupFieldAttrsInit {
 impl MetricGroupFieldAttrs {
     pub fn parse_attrs(attrs: &[Attribute]) -> syn::Result<Self> {
         let mut args = None;
-        let mut docs = None;
+        let mut docs = vec![];
+        let mut help: Option<LitStr> = None;
+        let mut no_help = false;
         let mut init = None;
+        let mut unit: Option<LitStr> = None;
 
         for attr in attrs {
             if attr.path().is_ident(LABEL_ATTR) {
@@ -101,6 +105,21 @@ impl MetricGroupFieldAttrs {
                                 return Err(meta.error("duplicate `metric(flatten)` attr"));
                             }
                         }
+                        () if meta.path.is_ident("unit") => {
+                            if unit.replace(meta.value()?.parse()?).is_some() {
+                                return Err(meta.error("duplicate `metric(unit)` attr"));
+                            }
+                        }
+                        () if meta.path.is_ident("help") => {
+                            if help.replace(meta.value()?.parse()?).is_some() {
+                                return Err(meta.error("duplicate `metric(help)` attr"));
+                            }
+                        }
+                        () if meta.path.is_ident("no_help") => {
+                            if std::mem::replace(&mut no_help, true) {
+                                return Err(meta.error("duplicate `metric(no_help)` attr"));
+                            }
+                        }
                         () if meta.path.is_ident("init") => {
                             if init
                                 .replace(MetricGroupFieldAttrsInit::Raw(meta.value()?.parse()?))
@@ -168,13 +187,68 @@ impl MetricGroupFieldAttrs {
                     }) => s,
                     _ => return Err(syn::Error::new(attr.span(), "invalid doc comment")),
                 };
-                docs.get_or_insert_with(String::new).push_str(&s.value());
+                docs.push(s.value());
             }
         }
+        let kind = args.unwrap_or(MetricGroupFieldAttrsKind::Metric { rename: None });
+        if let (Some(unit), MetricGroupFieldAttrsKind::Group { .. }) = (&unit, &kind) {
+            return Err(syn::Error::new(
+                unit.span(),
+                "`metric(unit)` can only be used on metrics, not on metric groups",
+            ));
+        }
+        if let (Some(unit), Some(MetricGroupFieldAttrsInit::Raw(_))) = (&unit, &init) {
+            return Err(syn::Error::new(
+                unit.span(),
+                "`metric(unit)` and `metric(init)` attributes are not compatible, use `metric(metadata)` instead",
+            ));
+        }
+        if let (Some(help), true) = (&help, no_help) {
+            return Err(syn::Error::new(
+                help.span(),
+                "`metric(help)` and `metric(no_help)` attributes are not compatible",
+            ));
+        }
+        if let (Some(help), MetricGroupFieldAttrsKind::Group { .. }) = (&help, &kind) {
+            return Err(syn::Error::new(
+                help.span(),
+                "`metric(help)` can only be used on metrics, not on metric groups",
+            ));
+        }
+
+        let help = match (help, no_help) {
+            (_, true) => None,
+            (Some(help), false) => Some(help.value()),
+            (None, false) => join_docs(&docs),
+        };
+
         Ok(Self {
-            kind: args.unwrap_or(MetricGroupFieldAttrsKind::Metric { rename: None }),
-            docs,
+            kind,
+            help,
             init,
+            unit,
         })
     }
 }
+
+/// Join the lines of a doc comment into help text.
+///
+/// Lines within a paragraph are joined with a space, and paragraphs are separated by a newline.
+fn join_docs(docs: &[String]) -> Option<String> {
+    let mut help = String::new();
+    let mut paragraph_break = false;
+    for line in docs.iter().flat_map(|doc| doc.split('\n')).map(str::trim) {
+        if line.is_empty() {
+            paragraph_break = !help.is_empty();
+            continue;
+        }
+        if paragraph_break {
+            help.push('\n');
+        } else if !help.is_empty() {
+            help.push(' ');
+        }
+        paragraph_break = false;
+        help.push_str(line);
+    }
+    (!help.is_empty()).then_some(help)
+}
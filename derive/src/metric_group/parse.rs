@@ -49,6 +49,15 @@ impl TryFrom<DeriveInput> for MetricGroup {
             },
         };
 
+        if args.inputs.is_none() {
+            if let Some(unit) = fields.iter().find_map(|f| f.attrs.unit.as_ref()) {
+                return Err(syn::Error::new(
+                    unit.span(),
+                    "`metric(unit)` requires the `metric(new(...))` attribute, which sets the unit on the metric metadata",
+                ));
+            }
+        }
+
         Ok(Self {
             krate,
             ident,
@@ -55,17 +55,40 @@ impl ToTokens for MetricGroup {
                     let name_string = rename.as_ref().map_or_else(|| name.to_string(), |l| l.value());
                     let ident = format_ident!("{}", name_string.to_shouty_snake_case(), span = x.span);
 
-                    let help = attrs.docs.as_deref().map(|doc|{
-                        let doc = doc.trim();
+                    let metric_name = match attrs.unit {
+                        None => quote!(#ident),
+                        Some(_) => quote!(__name),
+                    };
+                    let help = attrs.help.as_deref().map(|help| {
                         quote_spanned!(x.span => {
-                            <#enc as #krate::metric::group::Encoding>::write_help(enc, #ident, #doc)?;
+                            <#enc as #krate::metric::group::Encoding>::write_help(enc, #metric_name, #help)?;
                         })
                     });
 
-                    quote_spanned! { x.span =>
-                        const #ident: &#krate::metric::name::MetricName = #krate::metric::name::MetricName::from_str(#name_string);
-                        #help
-                        <#ty as #krate::metric::MetricFamilyEncoding<#enc>>::collect_family_into(&self.#name, #ident, enc)?;
+                    match &attrs.unit {
+                        None => quote_spanned! { x.span =>
+                            const #ident: &#krate::metric::name::MetricName = #krate::metric::name::MetricName::from_str(#name_string);
+                            #help
+                            <#ty as #krate::metric::MetricFamilyEncoding<#enc>>::collect_family_into(&self.#name, #ident, enc)?;
+                        },
+                        Some(unit) => {
+                            let unit_ident = format_ident!("{}_UNIT", name_string.to_shouty_snake_case(), span = x.span);
+                            let full_name = if has_unit_suffix(&name_string, unit) {
+                                quote_spanned!(x.span => #ident)
+                            } else {
+                                quote_spanned!(x.span => #ident.with_suffix(#unit_ident))
+                            };
+                            quote_spanned! { x.span =>
+                                {
+                                    const #ident: &#krate::metric::name::MetricName = #krate::metric::name::MetricName::from_str(#name_string);
+                                    const #unit_ident: #krate::metric::name::Unit = #krate::metric::name::Unit::new(#unit);
+                                    let __name = &#full_name;
+                                    #help
+                                    <#enc as #krate::metric::group::Encoding>::write_unit(enc, __name, #unit_ident)?;
+                                    <#ty as #krate::metric::MetricFamilyEncoding<#enc>>::collect_family_into(&self.#name, __name, enc)?;
+                                }
+                            }
+                        }
                     }
                 },
                 MetricGroupFieldAttrsKind::Group { namespace: None } => {
@@ -84,7 +107,80 @@ impl ToTokens for MetricGroup {
             }
         });
 
+        // the bounds are higher-ranked so that they are not checked eagerly. A group with a field that
+        // cannot be described still compiles, it just doesn't implement `MetricGroupDescribe`.
+        let mut describe_generics = generics.clone();
+        let wc = describe_generics.make_where_clause();
+        for field in fields {
+            let MetricGroupField { ty, attrs, .. } = field;
+            match attrs.kind {
+                MetricGroupFieldAttrsKind::Metric { .. } => {
+                    wc.predicates.push(parse_quote_spanned!(field.span => for<'__describe_lt> #ty: #krate::metric::describe::MetricFamilyDescribe ));
+                }
+                MetricGroupFieldAttrsKind::Group { .. } => {
+                    wc.predicates.push(parse_quote_spanned!(field.span => for<'__describe_lt> #ty: #krate::metric::describe::MetricGroupDescribe ));
+                }
+            }
+        }
+        let (_, _, describe_where_clause) = describe_generics.split_for_impl();
+
+        let describes = fields.iter().map(|x| {
+            let MetricGroupField { name, ty, attrs, .. } = x;
+            match &attrs.kind {
+                MetricGroupFieldAttrsKind::Metric { rename } => {
+                    let mut name_string = rename.as_ref().map_or_else(|| name.to_string(), |l| l.value());
+                    let unit = match &attrs.unit {
+                        Some(unit) => {
+                            if !has_unit_suffix(&name_string, unit) {
+                                name_string = format!("{name_string}_{}", unit.value());
+                            }
+                            quote_spanned!(x.span => ::core::option::Option::Some(#krate::metric::name::Unit::new(#unit)))
+                        }
+                        None => quote_spanned!(x.span => ::core::option::Option::None),
+                    };
+                    let help = match attrs.help.as_deref() {
+                        Some(help) => quote!(::core::option::Option::Some(#help)),
+                        None => quote!(::core::option::Option::None),
+                    };
+                    quote_spanned! { x.span =>
+                        <#ty as #krate::metric::describe::MetricFamilyDescribe>::describe_family_into(
+                            &self.#name,
+                            #krate::metric::describe::with_namespace(namespace, #name_string),
+                            #help,
+                            #unit,
+                            out,
+                        );
+                    }
+                },
+                MetricGroupFieldAttrsKind::Group { namespace: None } => {
+                    quote_spanned! { x.span =>
+                        <#ty as #krate::metric::describe::MetricGroupDescribe>::describe_group_into(&self.#name, namespace, out);
+                    }
+                },
+                MetricGroupFieldAttrsKind::Group { namespace: Some(ns) } => {
+                    quote_spanned! { x.span =>
+                        <#ty as #krate::metric::describe::MetricGroupDescribe>::describe_group_into(
+                            &self.#name,
+                            ::core::option::Option::Some(&#krate::metric::describe::with_namespace(namespace, #ns)),
+                            out,
+                        );
+                    }
+                },
+            }
+        });
+
         tokens.extend(quote! {
+            #[automatically_derived]
+            impl #impl_generics #krate::metric::describe::MetricGroupDescribe for #ident #ty_generics #describe_where_clause {
+                fn describe_group_into(
+                    &self,
+                    namespace: ::core::option::Option<&str>,
+                    out: &mut ::std::vec::Vec<#krate::metric::describe::MetricDescription>,
+                ) {
+                    #(#describes)*
+                }
+            }
+
             #[automatically_derived]
             impl #group_impl_generics #krate::metric::group::MetricGroup<#enc> for #ident #ty_generics #group_where_clause {
                 fn collect_group_into(&self, enc: &mut #enc) -> Result<(), #enc::Err>{
@@ -97,11 +193,28 @@ impl ToTokens for MetricGroup {
         if let Some(inputs) = inputs {
             let inits = fields.iter().map(|x| {
                 let MetricGroupField { name,ty, attrs, .. } = x;
-                match &attrs.init {
+                let default_init = MetricGroupFieldAttrsInit::Metric { metadata: None, label_set: None };
+                let init = match (&attrs.init, &attrs.unit) {
+                    // the unit is set on the metadata, so the metric needs to be constructed with metadata.
+                    // a raw `init` together with a unit is rejected when parsing the attributes.
+                    (None, Some(_)) => Some(&default_init),
+                    (init, _) => init.as_ref(),
+                };
+                match init {
                     Some(MetricGroupFieldAttrsInit::Raw(init)) => quote_spanned!(x.span => #name: #init,),
                     Some(MetricGroupFieldAttrsInit::Metric { metadata, label_set }) => {
                         let default: syn::Expr = parse_quote!{::core::default::Default::default()};
+                        let with_unit: syn::Expr;
                         let metadata = metadata.as_ref().unwrap_or(&default);
+                        let metadata = match &attrs.unit {
+                            Some(unit) => {
+                                with_unit = parse_quote_spanned!(x.span =>
+                                    #krate::metric::name::UnitMetadata::with_unit(#metadata, #krate::metric::name::Unit::new(#unit))
+                                );
+                                &with_unit
+                            }
+                            None => metadata,
+                        };
                         if let Some(ls) = label_set {
                             quote_spanned!(x.span => #name: <#ty>::with_label_set_and_metadata(#ls, #metadata),)
                         } else {
@@ -112,15 +225,50 @@ impl ToTokens for MetricGroup {
                 }
             });
 
+            // the metadata records the unit, so that durations are converted into it.
+            // this also fails to compile if the metric has no metadata that can record a unit.
+            let unit_checks: Vec<_> = fields.iter().filter_map(|x| {
+                let MetricGroupField { name, ty, attrs, .. } = x;
+                let unit = attrs.unit.as_ref()?;
+                let name_string = match &attrs.kind {
+                    MetricGroupFieldAttrsKind::Metric { rename: Some(rename) } => rename.value(),
+                    _ => name.to_string(),
+                };
+                Some(quote_spanned! { x.span =>
+                    assert!(
+                        <#ty as #krate::metric::name::MetricFamilyUnit>::matches_unit(&__group.#name, #krate::metric::name::Unit::new(#unit)),
+                        "metric {:?} does not convert durations into {:?}",
+                        #name_string,
+                        #unit,
+                    );
+                })
+            }).collect();
+
+            let body = if unit_checks.is_empty() {
+                quote!(Self { #(#inits)* })
+            } else {
+                quote! {
+                    let __group = Self { #(#inits)* };
+                    #(#unit_checks)*
+                    __group
+                }
+            };
+
             tokens.extend(quote! {
                 impl #impl_generics #ident #ty_generics #where_clause {
+                    // metrics without metadata are constructed with `()` metadata
+                    #[allow(clippy::unit_arg)]
                     pub fn new(#inputs) -> Self {
-                        Self {
-                            #(#inits)*
-                        }
+                        #body
                     }
                 }
             });
         }
     }
 }
+
+/// Whether the metric name already ends with the unit, in which case it is not added again
+fn has_unit_suffix(name: &str, unit: &syn::LitStr) -> bool {
+    name.strip_suffix(&unit.value())
+        .is_some_and(|name| name.ends_with('_'))
+}
@@ -2,6 +2,7 @@ use std::sync::Arc;
 
 use axum::{
     extract::{MatchedPath, Request, State},
+    http::{header, HeaderMap, HeaderValue},
     middleware::Next,
     response::Response,
     RequestExt,
@@ -9,7 +10,10 @@ use axum::{
 use measured::{
     label::{self, LabelValue},
     metric::histogram::Thresholds,
-    text::BufferedTextEncoder,
+    text::{
+        compress::{CompressedTextEncoder, Compression},
+        CONTENT_TYPE,
+    },
     CounterVec, FixedCardinalityLabel, HistogramVec, LabelGroup, MetricGroup,
 };
 use tokio::sync::Mutex;
@@ -17,7 +21,7 @@ use tokio::sync::Mutex;
 /// Defines both the metrics and the metrics encoder.
 /// Will be stored in the axum state.
 pub struct AppMetricsEncoder {
-    encoder: Mutex<BufferedTextEncoder>,
+    encoder: Mutex<CompressedTextEncoder>,
     pub metrics: AppMetrics,
 }
 
@@ -45,7 +49,7 @@ pub struct AppMetrics {
 impl AppMetricsEncoder {
     pub fn new(metrics: AppMetrics) -> Self {
         Self {
-            encoder: Mutex::default(),
+            encoder: Mutex::new(CompressedTextEncoder::new(Compression::Identity)),
             metrics,
         }
     }
@@ -93,13 +97,28 @@ pub async fn middleware(
     response
 }
 
-/// sample and export the metrics
-pub async fn handler(s: State<Arc<AppMetricsEncoder>>) -> Response {
+/// sample and export the metrics, compressed if the scraper supports it
+pub async fn handler(s: State<Arc<AppMetricsEncoder>>, headers: HeaderMap) -> Response {
     let AppMetricsEncoder { encoder, metrics } = &*s.0;
 
+    let accept_encoding = headers
+        .get(header::ACCEPT_ENCODING)
+        .and_then(|v| v.to_str().ok());
+    let compression = Compression::negotiate(accept_encoding);
+
     let mut encoder = encoder.lock().await;
+    if encoder.compression() != compression {
+        encoder.set_compression(compression);
+    }
     metrics.collect_group_into(&mut *encoder).unwrap();
-    Response::new(encoder.finish().into())
+
+    let mut response = Response::new(encoder.finish().into());
+    let headers = response.headers_mut();
+    headers.insert(header::CONTENT_TYPE, HeaderValue::from_static(CONTENT_TYPE));
+    if let Some(encoding) = compression.content_encoding() {
+        headers.insert(header::CONTENT_ENCODING, HeaderValue::from_static(encoding));
+    }
+    response
 }
 
 #[derive(LabelGroup)]
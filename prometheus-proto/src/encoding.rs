@@ -73,6 +73,27 @@ pub mod int32 {
         encode_key(tag, WireType::Varint, buf);
         encode_varint(*value as u64, buf);
     }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &i32) -> usize {
+        key_len(tag) + encoded_len_varint(*value as u64)
+    }
+}
+
+pub mod int64 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &i64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(*value as u64, buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &i64) -> usize {
+        key_len(tag) + encoded_len_varint(*value as u64)
+    }
 }
 
 pub mod double {
@@ -108,3 +129,115 @@ pub mod string {
         key_len(tag) + encoded_len_varint(value.len() as u64) + value.len()
     }
 }
+
+pub mod uint64 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &u64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(*value, buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &u64) -> usize {
+        key_len(tag) + encoded_len_varint(*value)
+    }
+}
+
+pub mod uint32 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &u32, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(u64::from(*value), buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &u32) -> usize {
+        key_len(tag) + encoded_len_varint(u64::from(*value))
+    }
+}
+
+pub mod sint32 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &i32, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(((value << 1) ^ (value >> 31)) as u32 as u64, buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &i32) -> usize {
+        key_len(tag) + encoded_len_varint(((value << 1) ^ (value >> 31)) as u32 as u64)
+    }
+}
+
+pub mod sint64 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &i64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(((value << 1) ^ (value >> 63)) as u64, buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, value: &i64) -> usize {
+        key_len(tag) + encoded_len_varint(((value << 1) ^ (value >> 63)) as u64)
+    }
+}
+
+pub mod fixed64 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &u64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::SixtyFourBit, buf);
+        buf.put_u64_le(*value);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, _: &u64) -> usize {
+        key_len(tag) + 8
+    }
+}
+
+pub mod sfixed64 {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &i64, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::SixtyFourBit, buf);
+        buf.put_i64_le(*value);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, _: &i64) -> usize {
+        key_len(tag) + 8
+    }
+}
+
+pub mod bool {
+    use crate::encoding::*;
+    pub fn encode<B>(tag: u32, value: &bool, buf: &mut B)
+    where
+        B: BufMut,
+    {
+        encode_key(tag, WireType::Varint, buf);
+        encode_varint(u64::from(*value), buf);
+    }
+
+    #[inline]
+    pub fn encoded_len(tag: u32, _: &bool) -> usize {
+        key_len(tag) + 1
+    }
+}
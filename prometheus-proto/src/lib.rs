@@ -1,19 +1,43 @@
-use std::io::Write;
+use std::{io::Write, time::SystemTime};
 
 use encoding::{encode_key, encode_varint, encoded_len_varint, key_len, WireType::LengthDelimited};
 use measured::{
     label::{LabelGroupVisitor, LabelName, LabelValue, LabelVisitor},
     metric::{
-        counter::CounterState,
+        counter::{write_counter, CounterState, ExemplarCounterState, ShardedCounterState},
+        exemplar::Exemplar,
         gauge::{FloatGaugeState, GaugeState},
         group::Encoding,
+        histogram::{ExemplarHistogramState, HistogramState, Thresholds},
         name::MetricNameEncoder,
+        native_histogram::{NativeHistogramConfig, NativeHistogramState},
+        untyped::UntypedState,
+        windowed_histogram::{Window, WindowedHistogramState},
         MetricEncoding,
     },
-    LabelGroup,
+    text::federate::{FederatedCounterState, FederatedHistogramState, FederatedSummaryState},
+    LabelGroup, MetricGroup,
 };
 
 mod encoding;
+pub mod otlp;
+pub mod remote_write;
+
+/// The content type of the prometheus protobuf format, to be used in HTTP responses.
+pub const CONTENT_TYPE: &str = measured::text::compress::Format::Protobuf.content_type();
+
+/// A collector which can be encoded as both text and protobuf.
+///
+/// Use `Registry<dyn Collector>` for a [`Registry`](measured::registry::Registry) that can be encoded with a [`ProtoEncoder`].
+pub trait Collector<W: Write = Vec<u8>>:
+    measured::registry::Collector + MetricGroup<ProtoEncoder<W>>
+{
+}
+
+impl<W: Write, T> Collector<W> for T where
+    T: measured::registry::Collector + MetricGroup<ProtoEncoder<W>>
+{
+}
 
 /// The prometheus text encoder helper
 pub struct ProtoEncoder<W> {
@@ -216,6 +240,68 @@ impl LabelGroupVisitor for GroupVisitor<'_> {
     }
 }
 
+#[derive(Clone, Copy)]
+struct ExemplarMessage<'a> {
+    exemplar: &'a Exemplar,
+    seconds: i64,
+    nanos: i32,
+}
+
+impl<'a> ExemplarMessage<'a> {
+    fn new(exemplar: &'a Exemplar) -> Self {
+        let timestamp = exemplar
+            .timestamp()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default();
+        Self {
+            exemplar,
+            seconds: timestamp.as_secs() as i64,
+            nanos: timestamp.subsec_nanos() as i32,
+        }
+    }
+
+    fn timestamp_len(&self) -> usize {
+        // google.protobuf.Timestamp is a proto3 message, so default values are not encoded.
+        let mut len = 0;
+        if self.seconds != 0 {
+            len += encoding::int64::encoded_len(1, &self.seconds);
+        }
+        if self.nanos != 0 {
+            len += encoding::int32::encoded_len(2, &self.nanos);
+        }
+        len
+    }
+
+    fn encoded_len(&self) -> usize {
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        self.exemplar.visit_values(&mut label_pairs_len);
+
+        label_pairs_len.len
+            + encoding::double::encoded_len(2, &self.exemplar.value())
+            + message_len(3, self.timestamp_len())
+    }
+
+    fn encode(&self, tag: u32, buf: &mut Vec<u8>) {
+        encode_message(tag, self.encoded_len(), buf, |buf| {
+            // repeated LabelPair label = 1;
+            self.exemplar.visit_values(&mut GroupVisitor { buf });
+            // optional double value = 2;
+            encoding::double::encode(2, &self.exemplar.value(), buf);
+            // optional google.protobuf.Timestamp timestamp = 3;
+            encode_message(3, self.timestamp_len(), buf, |buf| {
+                if self.seconds != 0 {
+                    // int64 seconds = 1;
+                    encoding::int64::encode(1, &self.seconds, buf);
+                }
+                if self.nanos != 0 {
+                    // int32 nanos = 2;
+                    encoding::int32::encode(2, &self.nanos, buf);
+                }
+            });
+        });
+    }
+}
+
 impl<W: Write> MetricEncoding<ProtoEncoder<W>> for CounterState {
     fn write_type(
         name: impl MetricNameEncoder,
@@ -271,6 +357,117 @@ impl<W: Write> MetricEncoding<ProtoEncoder<W>> for CounterState {
     }
 }
 
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for FederatedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.state = State::Metrics;
+
+        let mut metric_len = 0;
+
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        labels.visit_values(&mut label_pairs_len);
+        metric_len += label_pairs_len.len;
+
+        let count_len = encoding::double::encoded_len(1, &self.value);
+        metric_len += message_len(3, count_len);
+
+        // repeated Metric     metric = 4;
+        encode_message(4, metric_len, &mut enc.buf, |buf| {
+            labels.visit_values(&mut GroupVisitor { buf });
+
+            // optional Counter   counter      = 3;
+            encode_message(3, count_len, buf, |buf| {
+                // optional double   value    = 1;
+                encoding::double::encode(1, &self.value, buf);
+            });
+        });
+
+        Ok(())
+    }
+}
+
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for ShardedCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_counter(enc, name, labels, self.get())
+    }
+}
+
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for ExemplarCounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        CounterState::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.state = State::Metrics;
+
+        let exemplar = self.exemplar.lock();
+        let exemplar = exemplar.as_ref().map(ExemplarMessage::new);
+
+        let mut metric_len = 0;
+
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        labels.visit_values(&mut label_pairs_len);
+        metric_len += label_pairs_len.len;
+
+        let count = self.count.load(std::sync::atomic::Ordering::Relaxed) as f64;
+        let mut counter_len = encoding::double::encoded_len(1, &count);
+        if let Some(exemplar) = &exemplar {
+            counter_len += message_len(2, exemplar.encoded_len());
+        }
+        metric_len += message_len(3, counter_len);
+
+        // repeated Metric     metric = 4;
+        encode_message(4, metric_len, &mut enc.buf, |buf| {
+            labels.visit_values(&mut GroupVisitor { buf });
+
+            // optional Counter   counter      = 3;
+            encode_message(3, counter_len, buf, |buf| {
+                // optional double   value    = 1;
+                encoding::double::encode(1, &count, buf);
+                // optional Exemplar exemplar = 2;
+                if let Some(exemplar) = &exemplar {
+                    exemplar.encode(2, buf);
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
+
 impl<W: Write> MetricEncoding<ProtoEncoder<W>> for GaugeState {
     fn write_type(
         name: impl MetricNameEncoder,
@@ -381,26 +578,517 @@ impl<W: Write> MetricEncoding<ProtoEncoder<W>> for FloatGaugeState {
     }
 }
 
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for UntypedState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.flush_buf()?;
+
+        if enc.state == State::Init {
+            // optional string     name   = 1;
+            encode_key(1, LengthDelimited, &mut enc.buf);
+            encode_varint(name.encode_len() as u64, &mut enc.buf);
+            name.encode_utf8(&mut enc.buf)?;
+        }
+
+        // optional MetricType type   = 3;
+        // UNTYPED = 3;
+        encoding::int32::encode(3, &3, &mut enc.buf);
+
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.state = State::Metrics;
+
+        let mut metric_len = 0;
+
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        labels.visit_values(&mut label_pairs_len);
+        metric_len += label_pairs_len.len;
+
+        let value = self.value.get();
+        let untyped_len = encoding::double::encoded_len(1, &value);
+        metric_len += message_len(5, untyped_len);
+
+        // repeated Metric     metric = 4;
+        encode_message(4, metric_len, &mut enc.buf, |buf| {
+            labels.visit_values(&mut GroupVisitor { buf });
+
+            // optional Untyped untyped      = 5;
+            encode_message(5, untyped_len, buf, |buf| {
+                // optional double   value    = 1;
+                encoding::double::encode(1, &value, buf);
+            });
+        });
+
+        Ok(())
+    }
+}
+
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for FederatedSummaryState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.flush_buf()?;
+
+        if enc.state == State::Init {
+            // optional string     name   = 1;
+            encode_key(1, LengthDelimited, &mut enc.buf);
+            encode_varint(name.encode_len() as u64, &mut enc.buf);
+            name.encode_utf8(&mut enc.buf)?;
+        }
+
+        // optional MetricType type   = 3;
+        // SUMMARY = 2;
+        encoding::int32::encode(3, &2, &mut enc.buf);
+
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.state = State::Metrics;
+
+        let quantile_len = |quantile: &f64, value: &f64| {
+            encoding::double::encoded_len(1, quantile) + encoding::double::encoded_len(2, value)
+        };
+
+        let mut summary_len = 0;
+        summary_len += encoding::uint64::encoded_len(1, &self.count);
+        summary_len += encoding::double::encoded_len(2, &self.sum);
+        for (q, v) in &self.quantiles {
+            summary_len += message_len(3, quantile_len(q, v));
+        }
+
+        let mut metric_len = 0;
+
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        labels.visit_values(&mut label_pairs_len);
+        metric_len += label_pairs_len.len;
+        metric_len += message_len(4, summary_len);
+
+        // repeated Metric     metric = 4;
+        encode_message(4, metric_len, &mut enc.buf, |buf| {
+            labels.visit_values(&mut GroupVisitor { buf });
+
+            // optional Summary   summary      = 4;
+            encode_message(4, summary_len, buf, |buf| {
+                // optional uint64 sample_count = 1;
+                encoding::uint64::encode(1, &self.count, buf);
+                // optional double sample_sum   = 2;
+                encoding::double::encode(2, &self.sum, buf);
+
+                for (q, v) in &self.quantiles {
+                    // repeated Quantile quantile = 3;
+                    encode_message(3, quantile_len(q, v), buf, |buf| {
+                        // optional double quantile = 1;
+                        encoding::double::encode(1, q, buf);
+                        // optional double value    = 2;
+                        encoding::double::encode(2, v, buf);
+                    });
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
+
+/// Write the buckets, sum and count of a histogram, with the exemplar of each bucket if there is one.
+fn write_histogram<W: Write, const N: usize>(
+    enc: &mut ProtoEncoder<W>,
+    metadata: &Thresholds<N>,
+    labels: impl LabelGroup,
+    (buckets, inf, sum): ([u64; N], u64, f64),
+    exemplars: &[Option<Exemplar>],
+) -> Result<(), std::io::Error> {
+    let exemplar = |i: usize| {
+        exemplars
+            .get(i)
+            .and_then(Option::as_ref)
+            .map(ExemplarMessage::new)
+    };
+
+    let mut cumulative = [0; N];
+    let mut count = 0;
+    for (c, b) in cumulative.iter_mut().zip(buckets) {
+        count += b;
+        *c = count;
+    }
+    count += inf;
+
+    // The +Inf bucket is optional, and implied by `sample_count`.
+    // It is only needed to hold an exemplar.
+    let inf_bucket = exemplar(N).map(|e| (count, f64::INFINITY, Some(e)));
+    let buckets = || {
+        cumulative
+            .iter()
+            .zip(metadata.get())
+            .enumerate()
+            .map(|(i, (&c, &le))| (c, le, exemplar(i)))
+            .chain(inf_bucket)
+    };
+
+    encode_histogram(enc, labels, count, sum, buckets)
+}
+
+/// Write a histogram with the given cumulative count, upper bound and exemplar of each bucket.
+fn encode_histogram<'a, W: Write, I>(
+    enc: &mut ProtoEncoder<W>,
+    labels: impl LabelGroup,
+    count: u64,
+    sum: f64,
+    buckets: impl Fn() -> I,
+) -> Result<(), std::io::Error>
+where
+    I: Iterator<Item = (u64, f64, Option<ExemplarMessage<'a>>)>,
+{
+    enc.state = State::Metrics;
+
+    let bucket_len =
+        |cumulative_count: &u64, upper_bound: &f64, exemplar: &Option<ExemplarMessage>| {
+            encoding::uint64::encoded_len(1, cumulative_count)
+                + encoding::double::encoded_len(2, upper_bound)
+                + exemplar
+                    .as_ref()
+                    .map_or(0, |e| message_len(3, e.encoded_len()))
+        };
+
+    let mut histogram_len = 0;
+    histogram_len += encoding::uint64::encoded_len(1, &count);
+    histogram_len += encoding::double::encoded_len(2, &sum);
+    for (c, le, e) in buckets() {
+        histogram_len += message_len(3, bucket_len(&c, &le, &e));
+    }
+
+    let mut metric_len = 0;
+
+    let mut label_pairs_len = GroupLenVisitor { len: 0 };
+    labels.visit_values(&mut label_pairs_len);
+    metric_len += label_pairs_len.len;
+    metric_len += message_len(7, histogram_len);
+
+    // repeated Metric     metric = 4;
+    encode_message(4, metric_len, &mut enc.buf, |buf| {
+        labels.visit_values(&mut GroupVisitor { buf });
+
+        // optional Histogram histogram    = 7;
+        encode_message(7, histogram_len, buf, |buf| {
+            // optional uint64 sample_count = 1;
+            encoding::uint64::encode(1, &count, buf);
+            // optional double sample_sum   = 2;
+            encoding::double::encode(2, &sum, buf);
+
+            for (c, le, e) in buckets() {
+                // repeated Bucket bucket = 3;
+                encode_message(3, bucket_len(&c, &le, &e), buf, |buf| {
+                    // optional uint64 cumulative_count = 1;
+                    encoding::uint64::encode(1, &c, buf);
+                    // optional double upper_bound = 2;
+                    encoding::double::encode(2, &le, buf);
+                    // optional Exemplar exemplar = 3;
+                    if let Some(e) = &e {
+                        e.encode(3, buf);
+                    }
+                });
+            }
+        });
+    });
+
+    Ok(())
+}
+
+/// The `+Inf` bucket is left out, as it is implied by `sample_count`.
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for FederatedHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<0>::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let buckets = || {
+            self.buckets
+                .iter()
+                .filter(|(le, _)| *le != f64::INFINITY)
+                .map(|&(le, c)| (c, le, None))
+        };
+        encode_histogram(enc, labels, self.count, self.sum, buckets)
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<ProtoEncoder<W>> for HistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.flush_buf()?;
+
+        if enc.state == State::Init {
+            // optional string     name   = 1;
+            encode_key(1, LengthDelimited, &mut enc.buf);
+            encode_varint(name.encode_len() as u64, &mut enc.buf);
+            name.encode_utf8(&mut enc.buf)?;
+        }
+
+        // optional MetricType type   = 3;
+        // HISTOGRAM = 4;
+        encoding::int32::encode(3, &4, &mut enc.buf);
+
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        write_histogram(enc, metadata, labels, self.sample(), &[])
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<ProtoEncoder<W>> for ExemplarHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        let sample = self.sample();
+        let exemplars = self.exemplars.lock();
+        write_histogram(enc, metadata, labels, sample, &exemplars)
+    }
+}
+
+impl<W: Write, const N: usize> MetricEncoding<ProtoEncoder<W>> for WindowedHistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<N>::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Window<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        self.histogram
+            .collect_into(metadata.thresholds(), labels, name, enc)
+    }
+}
+
+impl<W: Write> MetricEncoding<ProtoEncoder<W>> for NativeHistogramState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        HistogramState::<0>::write_type(name, enc)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &NativeHistogramConfig,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut ProtoEncoder<W>,
+    ) -> Result<(), std::io::Error> {
+        enc.state = State::Metrics;
+
+        let sample = self.inner.write().sample();
+        let schema = metadata.schema();
+        let zero_threshold = metadata.zero_threshold();
+
+        let negative = BucketSpans::new(&sample.negative);
+        let mut positive = BucketSpans::new(&sample.positive);
+        if positive.spans.is_empty()
+            && negative.spans.is_empty()
+            && zero_threshold == 0.0
+            && sample.zero == 0
+        {
+            // a no-op span distinguishes an empty native histogram from a classic histogram
+            positive.spans.push((0, 0));
+        }
+
+        let mut histogram_len = 0;
+        histogram_len += encoding::uint64::encoded_len(1, &sample.count);
+        histogram_len += encoding::double::encoded_len(2, &sample.sum);
+        histogram_len += encoding::sint32::encoded_len(5, &schema);
+        histogram_len += encoding::double::encoded_len(6, &zero_threshold);
+        histogram_len += encoding::uint64::encoded_len(7, &sample.zero);
+        histogram_len += negative.encoded_len(9, 10);
+        histogram_len += positive.encoded_len(12, 13);
+
+        let mut metric_len = 0;
+
+        let mut label_pairs_len = GroupLenVisitor { len: 0 };
+        labels.visit_values(&mut label_pairs_len);
+        metric_len += label_pairs_len.len;
+        metric_len += message_len(7, histogram_len);
+
+        // repeated Metric     metric = 4;
+        encode_message(4, metric_len, &mut enc.buf, |buf| {
+            labels.visit_values(&mut GroupVisitor { buf });
+
+            // optional Histogram histogram    = 7;
+            encode_message(7, histogram_len, buf, |buf| {
+                // optional uint64 sample_count = 1;
+                encoding::uint64::encode(1, &sample.count, buf);
+                // optional double sample_sum   = 2;
+                encoding::double::encode(2, &sample.sum, buf);
+                // optional sint32 schema = 5;
+                encoding::sint32::encode(5, &schema, buf);
+                // optional double zero_threshold = 6;
+                encoding::double::encode(6, &zero_threshold, buf);
+                // optional uint64 zero_count = 7;
+                encoding::uint64::encode(7, &sample.zero, buf);
+                // repeated BucketSpan negative_span = 9;
+                // repeated sint64 negative_delta = 10;
+                negative.encode(9, 10, buf);
+                // repeated BucketSpan positive_span = 12;
+                // repeated sint64 positive_delta = 13;
+                positive.encode(12, 13, buf);
+            });
+        });
+
+        Ok(())
+    }
+}
+
+/// The sparse buckets of a native histogram, as spans of consecutive bucket indices
+/// and the count deltas between each bucket.
+struct BucketSpans {
+    spans: Vec<(i32, u32)>,
+    deltas: Vec<i64>,
+}
+
+impl BucketSpans {
+    fn new(buckets: &[(i32, u64)]) -> Self {
+        let mut spans = Vec::<(i32, u32)>::new();
+        let mut deltas = Vec::with_capacity(buckets.len());
+
+        let mut prev_count = 0;
+        let mut next_index = 0;
+        for &(index, count) in buckets {
+            let gap = index - next_index;
+            match spans.last_mut() {
+                // small gaps are cheaper to encode as empty buckets than as a new span
+                Some((_, length)) if gap <= 2 => {
+                    for _ in 0..gap {
+                        deltas.push(-(prev_count as i64));
+                        prev_count = 0;
+                    }
+                    *length += gap as u32 + 1;
+                }
+                Some(_) => spans.push((gap, 1)),
+                None => spans.push((index, 1)),
+            }
+            deltas.push(count as i64 - prev_count as i64);
+            prev_count = count;
+            next_index = index + 1;
+        }
+
+        Self { spans, deltas }
+    }
+
+    fn span_len(&(offset, length): &(i32, u32)) -> usize {
+        encoding::sint32::encoded_len(1, &offset) + encoding::uint32::encoded_len(2, &length)
+    }
+
+    fn encoded_len(&self, span_tag: u32, delta_tag: u32) -> usize {
+        let spans: usize = self
+            .spans
+            .iter()
+            .map(|span| message_len(span_tag, Self::span_len(span)))
+            .sum();
+        let deltas: usize = self
+            .deltas
+            .iter()
+            .map(|delta| encoding::sint64::encoded_len(delta_tag, delta))
+            .sum();
+        spans + deltas
+    }
+
+    fn encode(&self, span_tag: u32, delta_tag: u32, buf: &mut Vec<u8>) {
+        for span in &self.spans {
+            encode_message(span_tag, Self::span_len(span), buf, |buf| {
+                // optional sint32 offset = 1;
+                encoding::sint32::encode(1, &span.0, buf);
+                // optional uint32 length = 2;
+                encoding::uint32::encode(2, &span.1, buf);
+            });
+        }
+        for delta in &self.deltas {
+            encoding::sint64::encode(delta_tag, delta, buf);
+        }
+    }
+}
+
 #[cfg(test)]
 mod generated;
 
 #[cfg(test)]
 mod tests {
-    use std::vec;
+    use std::{
+        time::{Duration, SystemTime},
+        vec,
+    };
 
     use bytes::{BufMut, BytesMut};
     use measured::{
         metric::{
+            exemplar::Exemplar,
             group::Encoding,
+            histogram::Thresholds,
             name::{MetricName, Total},
+            native_histogram::NativeHistogramConfig,
             MetricFamilyEncoding,
         },
-        CounterVec, GaugeVec,
+        CounterVec, ExemplarCounter, ExemplarHistogram, GaugeVec, Histogram, HistogramVec,
+        NativeHistogram,
     };
+    use prometheus::Encoder;
     use prost::Message;
 
     use crate::{
-        generated::{Counter, Gauge, LabelPair, Metric, MetricFamily, MetricType},
+        generated::{
+            Bucket, BucketSpan, Counter, Exemplar as ProtoExemplar, Gauge,
+            Histogram as ProtoHistogram, LabelPair, Metric, MetricFamily, MetricType, Quantile,
+            Summary, Untyped,
+        },
         ProtoEncoder,
     };
 
@@ -589,4 +1277,466 @@ mod tests {
         let actual = MetricFamily::decode_length_delimited(actual_msg).unwrap();
         assert_eq!(actual, expected);
     }
+
+    #[test]
+    fn histogram() {
+        let durations = HistogramVec::<RequestLabelSet, 4>::with_metadata(
+            Thresholds::exponential_buckets(0.1, 2.0),
+        );
+
+        let labels = RequestLabels {
+            method: Method::Post,
+            code: StatusCode::Ok,
+        };
+        durations.observe(labels, 0.05);
+        durations.observe(labels, 0.3);
+        durations.observe(labels, 0.7);
+        durations.observe(labels, 2.5);
+
+        let mut enc = ProtoEncoder::new(BytesMut::new().writer());
+
+        let name = MetricName::from_str("http_request_duration_seconds");
+        enc.write_help(name, "The duration of HTTP requests.")
+            .unwrap();
+        durations.collect_family_into(name, &mut enc).unwrap();
+        enc.flush().unwrap();
+        let actual_msg = enc.writer.into_inner();
+
+        let bucket = |cumulative_count, upper_bound| Bucket {
+            cumulative_count: Some(cumulative_count),
+            cumulative_count_float: None,
+            upper_bound: Some(upper_bound),
+            exemplar: None,
+        };
+
+        let expected = MetricFamily {
+            name: Some("http_request_duration_seconds".to_string()),
+            help: Some("The duration of HTTP requests.".to_string()),
+            r#type: Some(MetricType::Histogram as i32),
+            metric: vec![Metric {
+                label: vec![
+                    LabelPair {
+                        name: Some("method".to_owned()),
+                        value: Some("post".to_owned()),
+                    },
+                    LabelPair {
+                        name: Some("code".to_owned()),
+                        value: Some("200".to_owned()),
+                    },
+                ],
+                gauge: None,
+                counter: None,
+                summary: None,
+                untyped: None,
+                histogram: Some(ProtoHistogram {
+                    sample_count: Some(4),
+                    sample_sum: Some(3.55),
+                    bucket: vec![
+                        bucket(1, 0.1),
+                        bucket(1, 0.2),
+                        bucket(2, 0.4),
+                        bucket(3, 0.8),
+                    ],
+                    ..Default::default()
+                }),
+                timestamp_ms: None,
+            }],
+            unit: None,
+        };
+        let mut expected_msg = BytesMut::new();
+        expected.encode_length_delimited(&mut expected_msg).unwrap();
+
+        assert_eq!(actual_msg, expected_msg);
+
+        let actual = MetricFamily::decode_length_delimited(actual_msg).unwrap();
+        assert_eq!(actual, expected);
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::FixedCardinalityLabel)]
+    #[label(singleton = "trace_id")]
+    enum Trace {
+        #[label(rename = "4bf92f3577b34da6")]
+        A,
+        #[label(rename = "00f067aa0ba902b7")]
+        B,
+    }
+
+    #[test]
+    fn exemplars() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(1520879607789);
+        let fix_timestamp =
+            |e: &mut Exemplar| *e = Exemplar::with_timestamp(&*e, e.value(), timestamp);
+
+        let counter = ExemplarCounter::new();
+        counter.inc_by_with_exemplar(2, Trace::A);
+        fix_timestamp(counter.get_metric().exemplar.lock().as_mut().unwrap());
+
+        let histogram =
+            ExemplarHistogram::with_metadata(Thresholds::<2>::exponential_buckets(0.5, 2.0));
+        histogram.observe(0.3);
+        histogram.observe_with_exemplar(0.8, Trace::B);
+        histogram.observe_with_exemplar(2.5, Trace::A);
+        histogram
+            .get_metric()
+            .exemplars
+            .lock()
+            .iter_mut()
+            .flatten()
+            .for_each(fix_timestamp);
+
+        let mut enc = ProtoEncoder::new(BytesMut::new().writer());
+
+        let name = MetricName::from_str("http_request");
+        counter.collect_family_into(name, &mut enc).unwrap();
+        let name = MetricName::from_str("http_request_duration_seconds");
+        histogram.collect_family_into(name, &mut enc).unwrap();
+        enc.flush().unwrap();
+        let mut actual_msg = enc.writer.into_inner();
+
+        let exemplar = |trace_id: &str, value| ProtoExemplar {
+            label: vec![LabelPair {
+                name: Some("trace_id".to_owned()),
+                value: Some(trace_id.to_owned()),
+            }],
+            value: Some(value),
+            timestamp: Some(prost_types::Timestamp {
+                seconds: 1520879607,
+                nanos: 789_000_000,
+            }),
+        };
+        let bucket = |cumulative_count, upper_bound, exemplar| Bucket {
+            cumulative_count: Some(cumulative_count),
+            cumulative_count_float: None,
+            upper_bound: Some(upper_bound),
+            exemplar,
+        };
+
+        let expected_counter = MetricFamily {
+            name: Some("http_request".to_string()),
+            help: None,
+            r#type: Some(MetricType::Counter as i32),
+            metric: vec![Metric {
+                label: vec![],
+                gauge: None,
+                counter: Some(Counter {
+                    value: Some(2.0),
+                    exemplar: Some(exemplar("4bf92f3577b34da6", 2.0)),
+                    created_timestamp: None,
+                }),
+                summary: None,
+                untyped: None,
+                histogram: None,
+                timestamp_ms: None,
+            }],
+            unit: None,
+        };
+        let expected_histogram = MetricFamily {
+            name: Some("http_request_duration_seconds".to_string()),
+            help: None,
+            r#type: Some(MetricType::Histogram as i32),
+            metric: vec![Metric {
+                label: vec![],
+                gauge: None,
+                counter: None,
+                summary: None,
+                untyped: None,
+                histogram: Some(ProtoHistogram {
+                    sample_count: Some(3),
+                    sample_sum: Some(3.6),
+                    bucket: vec![
+                        bucket(1, 0.5, None),
+                        bucket(2, 1.0, Some(exemplar("00f067aa0ba902b7", 0.8))),
+                        // the +Inf bucket is only included to hold its exemplar
+                        bucket(3, f64::INFINITY, Some(exemplar("4bf92f3577b34da6", 2.5))),
+                    ],
+                    ..Default::default()
+                }),
+                timestamp_ms: None,
+            }],
+            unit: None,
+        };
+
+        let mut expected_msg = BytesMut::new();
+        expected_counter
+            .encode_length_delimited(&mut expected_msg)
+            .unwrap();
+        expected_histogram
+            .encode_length_delimited(&mut expected_msg)
+            .unwrap();
+
+        assert_eq!(actual_msg, expected_msg);
+
+        let actual = MetricFamily::decode_length_delimited(&mut actual_msg).unwrap();
+        assert_eq!(actual, expected_counter);
+        let actual = MetricFamily::decode_length_delimited(&mut actual_msg).unwrap();
+        assert_eq!(actual, expected_histogram);
+    }
+
+    #[test]
+    fn native_histogram() {
+        let h = NativeHistogram::with_metadata(NativeHistogramConfig::new(0));
+        for x in [1.0, 1.5, 3.0, 16.0, 100.0, 10000.0, -3.0, 0.0] {
+            h.observe(x);
+        }
+
+        let empty =
+            NativeHistogram::with_metadata(NativeHistogramConfig::new(0).with_zero_threshold(0.0));
+
+        let mut enc = ProtoEncoder::new(BytesMut::new().writer());
+
+        let name = MetricName::from_str("native");
+        h.collect_family_into(name, &mut enc).unwrap();
+        let name = MetricName::from_str("empty");
+        empty.collect_family_into(name, &mut enc).unwrap();
+        enc.flush().unwrap();
+        let mut actual_msg = enc.writer.into_inner();
+
+        let span = |offset, length| BucketSpan {
+            offset: Some(offset),
+            length: Some(length),
+        };
+        let family = |name: &str, histogram| MetricFamily {
+            name: Some(name.to_string()),
+            help: None,
+            r#type: Some(MetricType::Histogram as i32),
+            metric: vec![Metric {
+                label: vec![],
+                gauge: None,
+                counter: None,
+                summary: None,
+                untyped: None,
+                histogram: Some(histogram),
+                timestamp_ms: None,
+            }],
+            unit: None,
+        };
+
+        let expected = family(
+            "native",
+            ProtoHistogram {
+                sample_count: Some(8),
+                sample_sum: Some(10118.5),
+                schema: Some(0),
+                zero_threshold: Some(NativeHistogramConfig::DEFAULT_ZERO_THRESHOLD),
+                zero_count: Some(1),
+                negative_span: vec![span(2, 1)],
+                negative_delta: vec![1],
+                // buckets 0..=7 with the gaps filled in, then a new span for bucket 14
+                positive_span: vec![span(0, 8), span(6, 1)],
+                positive_delta: vec![1, 0, 0, -1, 1, -1, 0, 1, 0],
+                ..Default::default()
+            },
+        );
+        let expected_empty = family(
+            "empty",
+            ProtoHistogram {
+                sample_count: Some(0),
+                sample_sum: Some(0.0),
+                schema: Some(0),
+                zero_threshold: Some(0.0),
+                zero_count: Some(0),
+                positive_span: vec![span(0, 0)],
+                ..Default::default()
+            },
+        );
+
+        let mut expected_msg = BytesMut::new();
+        expected.encode_length_delimited(&mut expected_msg).unwrap();
+        expected_empty
+            .encode_length_delimited(&mut expected_msg)
+            .unwrap();
+
+        assert_eq!(actual_msg, expected_msg);
+
+        let actual = MetricFamily::decode_length_delimited(&mut actual_msg).unwrap();
+        assert_eq!(actual, expected);
+        let actual = MetricFamily::decode_length_delimited(&mut actual_msg).unwrap();
+        assert_eq!(actual, expected_empty);
+    }
+
+    #[test]
+    fn histogram_prometheus_compat() {
+        let observations = [0.01, 0.15, 0.15, 0.5, 1.2, 3.0, 6.4, 100.0];
+
+        let h = Histogram::<8>::with_metadata(Thresholds::exponential_buckets(0.1, 2.0));
+        for x in observations {
+            h.observe(x);
+        }
+
+        let mut enc = ProtoEncoder::new(BytesMut::new().writer());
+        let name = MetricName::from_str("http_request_duration_seconds");
+        enc.write_help(name, "help text").unwrap();
+        h.collect_family_into(name, &mut enc).unwrap();
+        enc.flush().unwrap();
+        let actual_msg = enc.writer.into_inner();
+
+        let registry = prometheus::Registry::new();
+        let ph = prometheus::register_histogram_with_registry!(
+            "http_request_duration_seconds",
+            "help text",
+            prometheus::exponential_buckets(0.1, 2.0, 8).unwrap(),
+            registry
+        )
+        .unwrap();
+        for x in observations {
+            ph.observe(x);
+        }
+        let mut expected_msg = vec![];
+        prometheus::ProtobufEncoder::new()
+            .encode(&registry.gather(), &mut expected_msg)
+            .unwrap();
+
+        assert_eq!(actual_msg, expected_msg);
+
+        let decoded: prometheus::proto::MetricFamily =
+            protobuf::CodedInputStream::from_bytes(&actual_msg)
+                .read_message()
+                .unwrap();
+        assert_eq!(decoded.get_name(), "http_request_duration_seconds");
+        assert_eq!(
+            decoded.get_field_type(),
+            prometheus::proto::MetricType::HISTOGRAM
+        );
+
+        let histogram = decoded.get_metric()[0].get_histogram();
+        assert_eq!(histogram.get_sample_count(), 8);
+        assert_eq!(histogram.get_sample_sum(), observations.iter().sum::<f64>());
+        let buckets: Vec<(u64, f64)> = histogram
+            .get_bucket()
+            .iter()
+            .map(|b| (b.get_cumulative_count(), b.get_upper_bound()))
+            .collect();
+        assert_eq!(
+            buckets,
+            [
+                (1, 0.1),
+                (3, 0.2),
+                (3, 0.4),
+                (4, 0.8),
+                (5, 1.6),
+                (6, 3.2),
+                (7, 6.4),
+                (7, 12.8),
+            ]
+        );
+    }
+
+    #[test]
+    fn federated() {
+        use measured::{text::federate::FederatedGroup, MetricGroup};
+
+        let group = FederatedGroup::parse(concat!(
+            "# TYPE cpu_seconds_total counter\n",
+            "cpu_seconds_total 0.5\n",
+            "# TYPE rq_time histogram\n",
+            "rq_time_bucket{le=\"0.5\"} 2\n",
+            "rq_time_bucket{le=\"+Inf\"} 3\n",
+            "rq_time_sum 1.25\n",
+            "rq_time_count 3\n",
+            "# TYPE rq_size summary\n",
+            "rq_size{quantile=\"0.5\"} 120\n",
+            "rq_size_sum 360\n",
+            "rq_size_count 3\n",
+            "untyped_metric -1.5\n",
+        ))
+        .unwrap();
+
+        let mut enc = ProtoEncoder::new(BytesMut::new().writer());
+        group.collect_group_into(&mut enc).unwrap();
+        enc.flush().unwrap();
+        let mut actual_msg = enc.writer.into_inner();
+
+        let family = |name: &str, metric_type: MetricType, metric: Metric| MetricFamily {
+            name: Some(name.to_owned()),
+            help: None,
+            r#type: Some(metric_type as i32),
+            metric: vec![metric],
+            unit: None,
+        };
+        let expected = [
+            family(
+                "cpu_seconds_total",
+                MetricType::Counter,
+                Metric {
+                    counter: Some(Counter {
+                        value: Some(0.5),
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            family(
+                "rq_time",
+                MetricType::Histogram,
+                Metric {
+                    histogram: Some(ProtoHistogram {
+                        sample_count: Some(3),
+                        sample_sum: Some(1.25),
+                        bucket: vec![Bucket {
+                            cumulative_count: Some(2),
+                            upper_bound: Some(0.5),
+                            ..Default::default()
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            family(
+                "rq_size",
+                MetricType::Summary,
+                Metric {
+                    summary: Some(Summary {
+                        sample_count: Some(3),
+                        sample_sum: Some(360.0),
+                        quantile: vec![Quantile {
+                            quantile: Some(0.5),
+                            value: Some(120.0),
+                        }],
+                        ..Default::default()
+                    }),
+                    ..Default::default()
+                },
+            ),
+            family(
+                "untyped_metric",
+                MetricType::Untyped,
+                Metric {
+                    untyped: Some(Untyped { value: Some(-1.5) }),
+                    ..Default::default()
+                },
+            ),
+        ];
+
+        for expected in expected {
+            let actual = MetricFamily::decode_length_delimited(&mut actual_msg).unwrap();
+            assert_eq!(actual, expected);
+        }
+        assert!(actual_msg.is_empty());
+    }
+
+    #[test]
+    fn registry() {
+        use measured::MetricGroup;
+
+        #[derive(measured::MetricGroup, Default)]
+        struct Plugin {
+            /// number of events handled
+            events_total: measured::Counter,
+        }
+
+        let registry = measured::registry::Registry::<dyn super::Collector>::new();
+        registry
+            .register("plugin", Box::new(Plugin::default()))
+            .unwrap();
+
+        let mut enc = ProtoEncoder::new(vec![]);
+        registry.collect_group_into(&mut enc).unwrap();
+        enc.flush().unwrap();
+
+        let decoded: prometheus::proto::MetricFamily =
+            protobuf::Message::parse_from_bytes(&enc.writer[1..]).unwrap();
+        assert_eq!(decoded.get_name(), "events_total");
+        assert_eq!(decoded.get_help(), "number of events handled");
+    }
 }
@@ -0,0 +1,790 @@
+//! The [OpenTelemetry protocol](https://opentelemetry.io/docs/specs/otlp/) for metrics.
+//!
+//! Metrics are encoded as an `ExportMetricsServiceRequest`, which can be sent to an OpenTelemetry
+//! collector's `/v1/metrics` endpoint.
+//!
+//! * Counters are encoded as cumulative, monotonic sums.
+//! * Gauges are encoded as gauges.
+//! * Histograms are encoded as cumulative, explicit-bucket histograms.
+//!
+//! Label groups are encoded as data point attributes, and help text is encoded as the metric description.
+
+use std::time::SystemTime;
+
+use measured::{
+    label::{LabelGroupVisitor, LabelName, LabelValue, LabelVisitor},
+    metric::{
+        counter::CounterState,
+        gauge::{FloatGaugeState, GaugeState},
+        group::Encoding,
+        histogram::{HistogramState, Thresholds},
+        name::MetricNameEncoder,
+        MetricEncoding,
+    },
+    LabelGroup,
+};
+
+use crate::{
+    encode_message,
+    encoding::{self, encode_key, encode_varint, WireType::LengthDelimited},
+    message_len,
+};
+
+/// The content type of an OTLP/protobuf request body.
+pub const CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// `AGGREGATION_TEMPORALITY_CUMULATIVE`
+const CUMULATIVE: i32 = 2;
+
+/// The OTLP/protobuf metrics encoder.
+///
+/// ```
+/// use std::time::SystemTime;
+///
+/// use measured::{metric::name::MetricName, metric::MetricFamilyEncoding, Counter};
+/// use measured_prometheus_protobuf::otlp::OtlpEncoder;
+///
+/// let process_start = SystemTime::now();
+///
+/// let requests = Counter::new();
+/// requests.inc();
+///
+/// let mut enc = OtlpEncoder::new(process_start).with_resource("service.name", "my-app");
+/// requests
+///     .collect_family_into(MetricName::from_str("requests"), &mut enc)
+///     .unwrap();
+///
+/// // POST this body to the collector's `/v1/metrics` endpoint
+/// let body = enc.finish();
+/// # drop(body);
+/// ```
+pub struct OtlpEncoder {
+    start_time_unix_nano: u64,
+    time_unix_nano: u64,
+    resource: Vec<(String, String)>,
+    /// The encoded `Metric` messages
+    metrics: Vec<u8>,
+
+    /// The name of the metric currently being encoded
+    name: Vec<u8>,
+    /// The description of the metric currently being encoded
+    help: String,
+    /// The type of the metric currently being encoded
+    kind: Option<Kind>,
+    /// The encoded data points of the metric currently being encoded
+    points: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Kind {
+    Gauge,
+    Sum,
+    Histogram,
+}
+
+impl OtlpEncoder {
+    /// Create a new OTLP encoder.
+    ///
+    /// Counters and histograms are cumulative, so `start_time` should be the time the metrics were created,
+    /// eg when the process started.
+    /// The data points are timestamped with the current time, which can be overridden with [`OtlpEncoder::set_timestamp`].
+    /// The timestamp is refreshed to the current time after every [`OtlpEncoder::finish`].
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(start_time: SystemTime) -> Self {
+        Self {
+            start_time_unix_nano: unix_nanos(start_time),
+            time_unix_nano: unix_nanos(SystemTime::now()),
+            resource: Vec::new(),
+            metrics: Vec::new(),
+            name: Vec::new(),
+            help: String::new(),
+            kind: None,
+            points: Vec::new(),
+        }
+    }
+
+    /// Add an attribute which describes the resource that is producing the metrics, eg `service.name`.
+    pub fn with_resource(mut self, key: &str, value: &str) -> Self {
+        self.resource.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Set the timestamp that all following data points will be recorded at, until the next [`OtlpEncoder::finish`].
+    pub fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.time_unix_nano = unix_nanos(timestamp);
+    }
+
+    /// Finish the encoding and extract the bytes to send in a HTTP request.
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.flush_metric();
+
+        let scope_name = "measured";
+        let scope_version = env!("CARGO_PKG_VERSION");
+        let scope_len = encoding::string::encoded_len(1, scope_name)
+            + encoding::string::encoded_len(2, scope_version);
+        let scope_metrics_len = message_len(1, scope_len) + self.metrics.len();
+
+        let resource_len: usize = self
+            .resource
+            .iter()
+            .map(|(k, v)| message_len(1, key_value_len(k, v)))
+            .sum();
+        let resource_metrics_len = message_len(1, resource_len) + message_len(2, scope_metrics_len);
+
+        let mut body = Vec::with_capacity(message_len(1, resource_metrics_len));
+        // repeated ResourceMetrics resource_metrics = 1;
+        encode_message(1, resource_metrics_len, &mut body, |buf| {
+            // Resource resource = 1;
+            encode_message(1, resource_len, buf, |buf| {
+                for (k, v) in &self.resource {
+                    // repeated KeyValue attributes = 1;
+                    encode_key_value(1, k, v, buf);
+                }
+            });
+            // repeated ScopeMetrics scope_metrics = 2;
+            encode_message(2, scope_metrics_len, buf, |buf| {
+                // InstrumentationScope scope = 1;
+                encode_message(1, scope_len, buf, |buf| {
+                    // string name = 1;
+                    encoding::string::encode(1, scope_name, buf);
+                    // string version = 2;
+                    encoding::string::encode(2, scope_version, buf);
+                });
+                // repeated Metric metrics = 2;
+                buf.extend_from_slice(&self.metrics);
+            });
+        });
+
+        self.metrics.clear();
+        self.time_unix_nano = unix_nanos(SystemTime::now());
+        body
+    }
+
+    /// Start encoding a new metric, finishing the previous one.
+    fn start_metric(
+        &mut self,
+        name: impl MetricNameEncoder,
+        kind: Kind,
+    ) -> Result<(), std::io::Error> {
+        self.flush_metric();
+        name.encode_utf8(&mut self.name)?;
+        self.kind = Some(kind);
+        Ok(())
+    }
+
+    /// Write the metric currently being encoded into the list of metrics.
+    fn flush_metric(&mut self) {
+        let Some(kind) = self.kind.take() else {
+            return;
+        };
+
+        let (data_tag, data_len) = match kind {
+            // Gauge gauge = 5;
+            Kind::Gauge => (5, self.points.len()),
+            // Sum sum = 7;
+            Kind::Sum => (
+                7,
+                self.points.len()
+                    + encoding::int32::encoded_len(2, &CUMULATIVE)
+                    + encoding::bool::encoded_len(3, &true),
+            ),
+            // Histogram histogram = 9;
+            Kind::Histogram => (
+                9,
+                self.points.len() + encoding::int32::encoded_len(2, &CUMULATIVE),
+            ),
+        };
+
+        let mut metric_len = message_len(1, self.name.len()) + message_len(data_tag, data_len);
+        if !self.help.is_empty() {
+            metric_len += encoding::string::encoded_len(2, &self.help);
+        }
+
+        // repeated Metric metrics = 2;
+        encode_message(2, metric_len, &mut self.metrics, |buf| {
+            // string name = 1;
+            encode_key(1, LengthDelimited, buf);
+            encode_varint(self.name.len() as u64, buf);
+            buf.extend_from_slice(&self.name);
+
+            // string description = 2;
+            if !self.help.is_empty() {
+                encoding::string::encode(2, &self.help, buf);
+            }
+
+            encode_message(data_tag, data_len, buf, |buf| {
+                // repeated DataPoint data_points = 1;
+                buf.extend_from_slice(&self.points);
+
+                match kind {
+                    Kind::Gauge => {}
+                    Kind::Sum => {
+                        // AggregationTemporality aggregation_temporality = 2;
+                        encoding::int32::encode(2, &CUMULATIVE, buf);
+                        // bool is_monotonic = 3;
+                        encoding::bool::encode(3, &true, buf);
+                    }
+                    Kind::Histogram => {
+                        // AggregationTemporality aggregation_temporality = 2;
+                        encoding::int32::encode(2, &CUMULATIVE, buf);
+                    }
+                }
+            });
+        });
+
+        self.name.clear();
+        self.help.clear();
+        self.points.clear();
+    }
+
+    /// Write a `NumberDataPoint` for the current metric.
+    fn write_number(&mut self, labels: impl LabelGroup, value: Number, cumulative: bool) {
+        let mut attributes = AttributesLen { tag: 7, len: 0 };
+        labels.visit_values(&mut attributes);
+
+        let mut point_len = attributes.len;
+        if cumulative {
+            point_len += encoding::fixed64::encoded_len(2, &self.start_time_unix_nano);
+        }
+        point_len += encoding::fixed64::encoded_len(3, &self.time_unix_nano);
+        point_len += match value {
+            Number::Int(x) => encoding::sfixed64::encoded_len(6, &x),
+            Number::Double(x) => encoding::double::encoded_len(4, &x),
+        };
+
+        // repeated NumberDataPoint data_points = 1;
+        encode_message(1, point_len, &mut self.points, |buf| {
+            // repeated KeyValue attributes = 7;
+            labels.visit_values(&mut Attributes { tag: 7, buf });
+            if cumulative {
+                // fixed64 start_time_unix_nano = 2;
+                encoding::fixed64::encode(2, &self.start_time_unix_nano, buf);
+            }
+            // fixed64 time_unix_nano = 3;
+            encoding::fixed64::encode(3, &self.time_unix_nano, buf);
+            match value {
+                // sfixed64 as_int = 6;
+                Number::Int(x) => encoding::sfixed64::encode(6, &x, buf),
+                // double as_double = 4;
+                Number::Double(x) => encoding::double::encode(4, &x, buf),
+            }
+        });
+    }
+}
+
+fn unix_nanos(time: SystemTime) -> u64 {
+    time.duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_nanos() as u64
+}
+
+#[derive(Clone, Copy)]
+enum Number {
+    Int(i64),
+    Double(f64),
+}
+
+impl Encoding for OtlpEncoder {
+    type Err = std::io::Error;
+
+    /// Record the help text, which is written as the metric description
+    fn write_help(
+        &mut self,
+        _name: impl MetricNameEncoder,
+        help: &str,
+    ) -> Result<(), std::io::Error> {
+        self.flush_metric();
+        self.help.push_str(help);
+        Ok(())
+    }
+}
+
+/// The length of an encoded `AnyValue`
+struct AnyValueLen;
+
+impl LabelVisitor for AnyValueLen {
+    type Output = usize;
+    fn write_int(self, x: i64) -> usize {
+        // int64 int_value = 3;
+        encoding::int64::encoded_len(3, &x)
+    }
+
+    fn write_float(self, x: f64) -> usize {
+        // double double_value = 4;
+        encoding::double::encoded_len(4, &x)
+    }
+
+    fn write_str(self, x: &str) -> usize {
+        // string string_value = 1;
+        encoding::string::encoded_len(1, x)
+    }
+}
+
+/// Encodes an `AnyValue`
+struct AnyValue<'a> {
+    buf: &'a mut Vec<u8>,
+}
+
+impl LabelVisitor for AnyValue<'_> {
+    type Output = ();
+    fn write_int(self, x: i64) {
+        // int64 int_value = 3;
+        encoding::int64::encode(3, &x, self.buf);
+    }
+
+    fn write_float(self, x: f64) {
+        // double double_value = 4;
+        encoding::double::encode(4, &x, self.buf);
+    }
+
+    fn write_str(self, x: &str) {
+        // string string_value = 1;
+        encoding::string::encode(1, x, self.buf);
+    }
+}
+
+fn key_value_len(key: &str, value: &(impl LabelValue + ?Sized)) -> usize {
+    encoding::string::encoded_len(1, key) + message_len(2, value.visit(AnyValueLen))
+}
+
+fn encode_key_value(tag: u32, key: &str, value: &(impl LabelValue + ?Sized), buf: &mut Vec<u8>) {
+    encode_message(tag, key_value_len(key, value), buf, |buf| {
+        // string key = 1;
+        encoding::string::encode(1, key, buf);
+        // AnyValue value = 2;
+        encode_message(2, value.visit(AnyValueLen), buf, |buf| {
+            value.visit(AnyValue { buf });
+        });
+    });
+}
+
+/// The length of a label group, encoded as repeated `KeyValue`s
+struct AttributesLen {
+    tag: u32,
+    len: usize,
+}
+
+impl LabelGroupVisitor for AttributesLen {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+        self.len += message_len(self.tag, key_value_len(name.as_str(), x));
+    }
+}
+
+/// Encodes a label group as repeated `KeyValue`s
+struct Attributes<'a> {
+    tag: u32,
+    buf: &'a mut Vec<u8>,
+}
+
+impl LabelGroupVisitor for Attributes<'_> {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+        encode_key_value(self.tag, name.as_str(), x, self.buf);
+    }
+}
+
+impl MetricEncoding<OtlpEncoder> for CounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.start_metric(name, Kind::Sum)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(std::sync::atomic::Ordering::Relaxed) as i64;
+        enc.write_number(labels, Number::Int(count), true);
+        Ok(())
+    }
+}
+
+impl MetricEncoding<OtlpEncoder> for GaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.start_metric(name, Kind::Gauge)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        let gauge = self.count.load(std::sync::atomic::Ordering::Relaxed);
+        enc.write_number(labels, Number::Int(gauge), false);
+        Ok(())
+    }
+}
+
+impl MetricEncoding<OtlpEncoder> for FloatGaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.start_metric(name, Kind::Gauge)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_number(labels, Number::Double(self.count.get()), false);
+        Ok(())
+    }
+}
+
+impl<const N: usize> MetricEncoding<OtlpEncoder> for HistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.start_metric(name, Kind::Histogram)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        _name: impl MetricNameEncoder,
+        enc: &mut OtlpEncoder,
+    ) -> Result<(), std::io::Error> {
+        let (buckets, inf, sum) = self.sample();
+        let count = buckets.iter().sum::<u64>() + inf;
+
+        let mut attributes = AttributesLen { tag: 9, len: 0 };
+        labels.visit_values(&mut attributes);
+
+        // packed repeated fields
+        let bucket_counts_len = (N + 1) * 8;
+        let explicit_bounds_len = N * 8;
+
+        let mut point_len = attributes.len;
+        point_len += encoding::fixed64::encoded_len(2, &enc.start_time_unix_nano);
+        point_len += encoding::fixed64::encoded_len(3, &enc.time_unix_nano);
+        point_len += encoding::fixed64::encoded_len(4, &count);
+        point_len += encoding::double::encoded_len(5, &sum);
+        point_len += message_len(6, bucket_counts_len);
+        point_len += message_len(7, explicit_bounds_len);
+
+        let (start, time) = (enc.start_time_unix_nano, enc.time_unix_nano);
+
+        // repeated HistogramDataPoint data_points = 1;
+        encode_message(1, point_len, &mut enc.points, |buf| {
+            // repeated KeyValue attributes = 9;
+            labels.visit_values(&mut Attributes { tag: 9, buf });
+            // fixed64 start_time_unix_nano = 2;
+            encoding::fixed64::encode(2, &start, buf);
+            // fixed64 time_unix_nano = 3;
+            encoding::fixed64::encode(3, &time, buf);
+            // fixed64 count = 4;
+            encoding::fixed64::encode(4, &count, buf);
+            // optional double sum = 5;
+            encoding::double::encode(5, &sum, buf);
+            // repeated fixed64 bucket_counts = 6;
+            encode_message(6, bucket_counts_len, buf, |buf| {
+                for b in buckets.iter().chain([&inf]) {
+                    buf.extend_from_slice(&b.to_le_bytes());
+                }
+            });
+            // repeated double explicit_bounds = 7;
+            encode_message(7, explicit_bounds_len, buf, |buf| {
+                for le in metadata.get() {
+                    buf.extend_from_slice(&le.to_le_bytes());
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use measured::{
+        metric::{group::Encoding, histogram::Thresholds, name::MetricName, MetricFamilyEncoding},
+        Counter, FloatGaugeVec, Histogram,
+    };
+    use prost::Message;
+
+    use super::OtlpEncoder;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ExportMetricsServiceRequest {
+        #[prost(message, repeated, tag = "1")]
+        resource_metrics: Vec<ResourceMetrics>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ResourceMetrics {
+        #[prost(message, optional, tag = "1")]
+        resource: Option<Resource>,
+        #[prost(message, repeated, tag = "2")]
+        scope_metrics: Vec<ScopeMetrics>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Resource {
+        #[prost(message, repeated, tag = "1")]
+        attributes: Vec<KeyValue>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct ScopeMetrics {
+        #[prost(message, optional, tag = "1")]
+        scope: Option<InstrumentationScope>,
+        #[prost(message, repeated, tag = "2")]
+        metrics: Vec<Metric>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct InstrumentationScope {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(string, tag = "2")]
+        version: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct KeyValue {
+        #[prost(string, tag = "1")]
+        key: String,
+        #[prost(message, optional, tag = "2")]
+        value: Option<AnyValue>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct AnyValue {
+        #[prost(string, optional, tag = "1")]
+        string_value: Option<String>,
+        #[prost(int64, optional, tag = "3")]
+        int_value: Option<i64>,
+        #[prost(double, optional, tag = "4")]
+        double_value: Option<f64>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Metric {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(string, tag = "2")]
+        description: String,
+        #[prost(message, optional, tag = "5")]
+        gauge: Option<Gauge>,
+        #[prost(message, optional, tag = "7")]
+        sum: Option<Sum>,
+        #[prost(message, optional, tag = "9")]
+        histogram: Option<OtlpHistogram>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Gauge {
+        #[prost(message, repeated, tag = "1")]
+        data_points: Vec<NumberDataPoint>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Sum {
+        #[prost(message, repeated, tag = "1")]
+        data_points: Vec<NumberDataPoint>,
+        #[prost(int32, tag = "2")]
+        aggregation_temporality: i32,
+        #[prost(bool, tag = "3")]
+        is_monotonic: bool,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct OtlpHistogram {
+        #[prost(message, repeated, tag = "1")]
+        data_points: Vec<HistogramDataPoint>,
+        #[prost(int32, tag = "2")]
+        aggregation_temporality: i32,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct NumberDataPoint {
+        #[prost(message, repeated, tag = "7")]
+        attributes: Vec<KeyValue>,
+        #[prost(fixed64, tag = "2")]
+        start_time_unix_nano: u64,
+        #[prost(fixed64, tag = "3")]
+        time_unix_nano: u64,
+        #[prost(double, optional, tag = "4")]
+        as_double: Option<f64>,
+        #[prost(sfixed64, optional, tag = "6")]
+        as_int: Option<i64>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct HistogramDataPoint {
+        #[prost(message, repeated, tag = "9")]
+        attributes: Vec<KeyValue>,
+        #[prost(fixed64, tag = "2")]
+        start_time_unix_nano: u64,
+        #[prost(fixed64, tag = "3")]
+        time_unix_nano: u64,
+        #[prost(fixed64, tag = "4")]
+        count: u64,
+        #[prost(double, optional, tag = "5")]
+        sum: Option<f64>,
+        #[prost(fixed64, repeated, tag = "6")]
+        bucket_counts: Vec<u64>,
+        #[prost(double, repeated, tag = "7")]
+        explicit_bounds: Vec<f64>,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::LabelGroup)]
+    #[label(set = RoomLabelSet)]
+    struct RoomLabels {
+        room: Room,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::FixedCardinalityLabel)]
+    #[label(rename_all = "snake_case")]
+    enum Room {
+        Kitchen,
+        Garage,
+    }
+
+    fn string_attribute(key: &str, value: &str) -> KeyValue {
+        KeyValue {
+            key: key.to_owned(),
+            value: Some(AnyValue {
+                string_value: Some(value.to_owned()),
+                ..Default::default()
+            }),
+        }
+    }
+
+    #[test]
+    fn export_request() {
+        let (start, now) = (1_700_000_000_000_000_000, 1_700_000_060_000_000_000);
+
+        let mut enc = OtlpEncoder::new(SystemTime::UNIX_EPOCH + Duration::from_nanos(start))
+            .with_resource("service.name", "test");
+        enc.set_timestamp(SystemTime::UNIX_EPOCH + Duration::from_nanos(now));
+
+        let requests = Counter::new();
+        requests.inc_by(5);
+        let name = MetricName::from_str("requests");
+        enc.write_help(name, "number of requests").unwrap();
+        requests.collect_family_into(name, &mut enc).unwrap();
+
+        let temperature = FloatGaugeVec::<RoomLabelSet>::new();
+        temperature.set(RoomLabels { room: Room::Garage }, 12.5);
+        temperature
+            .collect_family_into(MetricName::from_str("temperature"), &mut enc)
+            .unwrap();
+
+        let latency = Histogram::with_metadata(Thresholds::with_buckets([0.1, 1.0]));
+        latency.observe(0.5);
+        latency.observe(2.0);
+        let name = MetricName::from_str("latency");
+        enc.write_help(name, "request latency").unwrap();
+        latency.collect_family_into(name, &mut enc).unwrap();
+
+        let actual = ExportMetricsServiceRequest::decode(&*enc.finish()).unwrap();
+
+        let expected = ExportMetricsServiceRequest {
+            resource_metrics: vec![ResourceMetrics {
+                resource: Some(Resource {
+                    attributes: vec![string_attribute("service.name", "test")],
+                }),
+                scope_metrics: vec![ScopeMetrics {
+                    scope: Some(InstrumentationScope {
+                        name: "measured".to_owned(),
+                        version: env!("CARGO_PKG_VERSION").to_owned(),
+                    }),
+                    metrics: vec![
+                        Metric {
+                            name: "requests".to_owned(),
+                            description: "number of requests".to_owned(),
+                            sum: Some(Sum {
+                                data_points: vec![NumberDataPoint {
+                                    attributes: vec![],
+                                    start_time_unix_nano: start,
+                                    time_unix_nano: now,
+                                    as_double: None,
+                                    as_int: Some(5),
+                                }],
+                                aggregation_temporality: 2,
+                                is_monotonic: true,
+                            }),
+                            ..Default::default()
+                        },
+                        Metric {
+                            name: "temperature".to_owned(),
+                            description: String::new(),
+                            gauge: Some(Gauge {
+                                data_points: vec![NumberDataPoint {
+                                    attributes: vec![string_attribute("room", "garage")],
+                                    start_time_unix_nano: 0,
+                                    time_unix_nano: now,
+                                    as_double: Some(12.5),
+                                    as_int: None,
+                                }],
+                            }),
+                            ..Default::default()
+                        },
+                        Metric {
+                            name: "latency".to_owned(),
+                            description: "request latency".to_owned(),
+                            histogram: Some(OtlpHistogram {
+                                data_points: vec![HistogramDataPoint {
+                                    attributes: vec![],
+                                    start_time_unix_nano: start,
+                                    time_unix_nano: now,
+                                    count: 2,
+                                    sum: Some(2.5),
+                                    bucket_counts: vec![0, 1, 1],
+                                    explicit_bounds: vec![0.1, 1.0],
+                                }],
+                                aggregation_temporality: 2,
+                            }),
+                            ..Default::default()
+                        },
+                    ],
+                }],
+            }],
+        };
+
+        assert_eq!(actual, expected);
+    }
+
+    #[test]
+    fn timestamp_refreshed_after_finish() {
+        let mut enc = OtlpEncoder::new(SystemTime::UNIX_EPOCH);
+        enc.set_timestamp(SystemTime::UNIX_EPOCH + Duration::from_secs(60));
+
+        let requests = Counter::new();
+        let name = MetricName::from_str("requests");
+        requests.collect_family_into(name, &mut enc).unwrap();
+        let before = SystemTime::now()
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap()
+            .as_nanos() as u64;
+        enc.finish();
+
+        requests.collect_family_into(name, &mut enc).unwrap();
+        let actual = ExportMetricsServiceRequest::decode(&*enc.finish()).unwrap();
+
+        let metric = &actual.resource_metrics[0].scope_metrics[0].metrics[0];
+        let point = &metric.sum.as_ref().unwrap().data_points[0];
+        assert!(point.time_unix_nano >= before);
+    }
+}
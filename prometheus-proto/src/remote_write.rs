@@ -0,0 +1,543 @@
+//! The prometheus [remote-write](https://prometheus.io/docs/specs/remote_write_spec/) protocol.
+//!
+//! Metrics are encoded as a snappy compressed `WriteRequest`, which can be sent directly to a
+//! remote-write receiver without needing to be scraped.
+
+use std::time::SystemTime;
+
+use measured::{
+    label::{LabelGroupVisitor, LabelName, LabelValue, LabelVisitor},
+    metric::{
+        counter::CounterState,
+        gauge::{FloatGaugeState, GaugeState},
+        group::Encoding,
+        histogram::{HistogramState, Thresholds},
+        name::{Bucket, Count, MetricNameEncoder, Sum},
+        MetricEncoding,
+    },
+    LabelGroup,
+};
+
+use crate::{
+    encode_message,
+    encoding::{self, encode_key, encode_varint, WireType::LengthDelimited},
+    message_len,
+};
+
+/// The content type of a remote-write request body.
+pub const CONTENT_TYPE: &str = "application/x-protobuf";
+/// The content encoding of a remote-write request body.
+pub const CONTENT_ENCODING: &str = "snappy";
+/// The value of the `X-Prometheus-Remote-Write-Version` header that should be sent with the request.
+pub const REMOTE_WRITE_VERSION: &str = "0.1.0";
+
+/// The prometheus remote-write encoder.
+///
+/// Every sample is written with the same timestamp, which is supplied by the caller.
+///
+/// ```
+/// use std::time::SystemTime;
+///
+/// use measured::{metric::name::MetricName, metric::MetricFamilyEncoding, Counter};
+/// use measured_prometheus_protobuf::remote_write::RemoteWriteEncoder;
+///
+/// let requests = Counter::new();
+/// requests.inc();
+///
+/// let mut enc = RemoteWriteEncoder::new(SystemTime::now());
+/// requests
+///     .collect_family_into(MetricName::from_str("requests_total"), &mut enc)
+///     .unwrap();
+///
+/// // POST this body to the remote-write receiver
+/// let body = enc.finish();
+/// # drop(body);
+/// ```
+pub struct RemoteWriteEncoder {
+    timestamp_ms: i64,
+    /// The encoded `WriteRequest` message
+    buf: Vec<u8>,
+    /// The help text of the metric family currently being encoded
+    help: String,
+    /// The labels of the time series currently being encoded
+    labels: Vec<(String, String)>,
+}
+
+impl RemoteWriteEncoder {
+    /// Create a new remote-write encoder, where all samples will be recorded at `timestamp`.
+    ///
+    /// This should ideally be cached and re-used between collections to reduce re-allocating
+    pub fn new(timestamp: SystemTime) -> Self {
+        let mut enc = Self {
+            timestamp_ms: 0,
+            buf: Vec::new(),
+            help: String::new(),
+            labels: Vec::new(),
+        };
+        enc.set_timestamp(timestamp);
+        enc
+    }
+
+    /// Set the timestamp that all following samples will be recorded at.
+    pub fn set_timestamp(&mut self, timestamp: SystemTime) {
+        self.timestamp_ms = timestamp
+            .duration_since(SystemTime::UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_millis() as i64;
+    }
+
+    /// Finish the encoding and extract the snappy compressed bytes to send in a HTTP request.
+    pub fn finish(&mut self) -> Vec<u8> {
+        let body = snap::raw::Encoder::new()
+            .compress_vec(&self.buf)
+            .expect("the write request should not exceed the snappy size limit");
+        self.buf.clear();
+        body
+    }
+
+    /// Write the metadata of the metric family currently being encoded.
+    fn write_metadata(
+        &mut self,
+        name: impl MetricNameEncoder,
+        metric_type: MetricType,
+    ) -> Result<(), std::io::Error> {
+        let metric_type = metric_type as i32;
+
+        let mut metadata_len = encoding::int32::encoded_len(1, &metric_type);
+        metadata_len += message_len(2, name.encode_len());
+        if !self.help.is_empty() {
+            metadata_len += encoding::string::encoded_len(4, &self.help);
+        }
+
+        // repeated MetricMetadata metadata = 3;
+        encode_key(3, LengthDelimited, &mut self.buf);
+        encode_varint(metadata_len as u64, &mut self.buf);
+
+        // MetricType type = 1;
+        encoding::int32::encode(1, &metric_type, &mut self.buf);
+        // string metric_family_name = 2;
+        encode_key(2, LengthDelimited, &mut self.buf);
+        encode_varint(name.encode_len() as u64, &mut self.buf);
+        name.encode_utf8(&mut self.buf)?;
+        // string help = 4;
+        if !self.help.is_empty() {
+            encoding::string::encode(4, &self.help, &mut self.buf);
+        }
+
+        self.help.clear();
+        Ok(())
+    }
+
+    /// Write a single time series, with a single sample.
+    fn write_series(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        value: f64,
+    ) -> Result<(), std::io::Error> {
+        let mut name_buf = Vec::with_capacity(name.encode_len());
+        name.encode_utf8(&mut name_buf)?;
+        let name = String::from_utf8(name_buf).expect("metric names should be valid utf8");
+
+        self.labels.clear();
+        self.labels.push(("__name__".to_owned(), name));
+        labels.visit_values(&mut SeriesLabels {
+            labels: &mut self.labels,
+        });
+        // receivers expect the labels to be sorted by name
+        self.labels.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+
+        let label_len = |(name, value): &(String, String)| {
+            encoding::string::encoded_len(1, name) + encoding::string::encoded_len(2, value)
+        };
+
+        let mut sample_len = 0;
+        if value != 0.0 {
+            sample_len += encoding::double::encoded_len(1, &value);
+        }
+        if self.timestamp_ms != 0 {
+            sample_len += encoding::int64::encoded_len(2, &self.timestamp_ms);
+        }
+
+        let mut series_len = message_len(2, sample_len);
+        for label in &self.labels {
+            series_len += message_len(1, label_len(label));
+        }
+
+        let timestamp_ms = self.timestamp_ms;
+        let labels = &self.labels;
+
+        // repeated TimeSeries timeseries = 1;
+        encode_message(1, series_len, &mut self.buf, |buf| {
+            for label in labels {
+                // repeated Label labels = 1;
+                encode_message(1, label_len(label), buf, |buf| {
+                    // string name  = 1;
+                    encoding::string::encode(1, &label.0, buf);
+                    // string value = 2;
+                    encoding::string::encode(2, &label.1, buf);
+                });
+            }
+
+            // repeated Sample samples = 2;
+            encode_message(2, sample_len, buf, |buf| {
+                // proto3 does not encode default values
+                if value != 0.0 {
+                    // double value    = 1;
+                    encoding::double::encode(1, &value, buf);
+                }
+                if timestamp_ms != 0 {
+                    // int64 timestamp = 2;
+                    encoding::int64::encode(2, &timestamp_ms, buf);
+                }
+            });
+        });
+
+        Ok(())
+    }
+}
+
+/// The metric types that remote-write metadata can describe
+#[derive(Clone, Copy, Debug)]
+enum MetricType {
+    Counter = 1,
+    Gauge = 2,
+    Histogram = 3,
+}
+
+impl Encoding for RemoteWriteEncoder {
+    type Err = std::io::Error;
+
+    /// Record the help text, which is written with the metric metadata
+    fn write_help(
+        &mut self,
+        _name: impl MetricNameEncoder,
+        help: &str,
+    ) -> Result<(), std::io::Error> {
+        self.help.clear();
+        self.help.push_str(help);
+        Ok(())
+    }
+}
+
+struct SeriesLabels<'a> {
+    labels: &'a mut Vec<(String, String)>,
+}
+
+impl LabelGroupVisitor for SeriesLabels<'_> {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+        self.labels
+            .push((name.as_str().to_owned(), x.visit(ValueVisitor)));
+    }
+}
+
+struct ValueVisitor;
+
+impl LabelVisitor for ValueVisitor {
+    type Output = String;
+    fn write_int(self, x: i64) -> String {
+        itoa::Buffer::new().format(x).to_owned()
+    }
+
+    fn write_float(self, x: f64) -> String {
+        if x.is_infinite() {
+            if x.is_sign_positive() {
+                "+Inf".to_owned()
+            } else {
+                "-Inf".to_owned()
+            }
+        } else if x.is_nan() {
+            "NaN".to_owned()
+        } else {
+            ryu::Buffer::new().format(x).to_owned()
+        }
+    }
+
+    fn write_str(self, x: &str) -> String {
+        x.to_owned()
+    }
+}
+
+struct F64(f64);
+impl LabelValue for F64 {
+    fn visit<V: LabelVisitor>(&self, v: V) -> V::Output {
+        v.write_float(self.0)
+    }
+}
+
+struct HistogramLabelLe {
+    le: f64,
+}
+
+impl LabelGroup for HistogramLabelLe {
+    fn visit_values(&self, v: &mut impl LabelGroupVisitor) {
+        const LE: &LabelName = LabelName::from_str("le");
+        v.write_value(LE, &F64(self.le));
+    }
+}
+
+impl MetricEncoding<RemoteWriteEncoder> for CounterState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metadata(name, MetricType::Counter)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(std::sync::atomic::Ordering::Relaxed) as f64;
+        enc.write_series(name, labels, count)
+    }
+}
+
+impl MetricEncoding<RemoteWriteEncoder> for GaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metadata(name, MetricType::Gauge)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        let gauge = self.count.load(std::sync::atomic::Ordering::Relaxed) as f64;
+        enc.write_series(name, labels, gauge)
+    }
+}
+
+impl MetricEncoding<RemoteWriteEncoder> for FloatGaugeState {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metadata(name, MetricType::Gauge)
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_series(name, labels, self.count.get())
+    }
+}
+
+impl<const N: usize> MetricEncoding<RemoteWriteEncoder> for HistogramState<N> {
+    fn write_type(
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_metadata(name, MetricType::Histogram)
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut RemoteWriteEncoder,
+    ) -> Result<(), std::io::Error> {
+        let (buckets, inf, sum) = self.sample();
+        let mut count = 0;
+
+        for (&le, bucket) in metadata.get().iter().zip(buckets) {
+            count += bucket;
+            enc.write_series(
+                name.by_ref().with_suffix(Bucket),
+                labels.by_ref().compose_with(HistogramLabelLe { le }),
+                count as f64,
+            )?;
+        }
+        count += inf;
+        enc.write_series(
+            name.by_ref().with_suffix(Bucket),
+            labels
+                .by_ref()
+                .compose_with(HistogramLabelLe { le: f64::INFINITY }),
+            count as f64,
+        )?;
+        enc.write_series(name.by_ref().with_suffix(Sum), labels.by_ref(), sum)?;
+        enc.write_series(name.by_ref().with_suffix(Count), labels, count as f64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, SystemTime};
+
+    use measured::{
+        metric::{
+            group::Encoding,
+            histogram::Thresholds,
+            name::{MetricName, Total},
+            MetricFamilyEncoding,
+        },
+        CounterVec, FloatGauge, Histogram,
+    };
+    use prost::Message;
+
+    use super::RemoteWriteEncoder;
+
+    #[derive(Clone, PartialEq, Message)]
+    struct WriteRequest {
+        #[prost(message, repeated, tag = "1")]
+        timeseries: Vec<TimeSeries>,
+        #[prost(message, repeated, tag = "3")]
+        metadata: Vec<MetricMetadata>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct TimeSeries {
+        #[prost(message, repeated, tag = "1")]
+        labels: Vec<Label>,
+        #[prost(message, repeated, tag = "2")]
+        samples: Vec<Sample>,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Label {
+        #[prost(string, tag = "1")]
+        name: String,
+        #[prost(string, tag = "2")]
+        value: String,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct Sample {
+        #[prost(double, tag = "1")]
+        value: f64,
+        #[prost(int64, tag = "2")]
+        timestamp: i64,
+    }
+
+    #[derive(Clone, PartialEq, Message)]
+    struct MetricMetadata {
+        #[prost(int32, tag = "1")]
+        r#type: i32,
+        #[prost(string, tag = "2")]
+        metric_family_name: String,
+        #[prost(string, tag = "4")]
+        help: String,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::LabelGroup)]
+    #[label(set = RequestLabelSet)]
+    struct RequestLabels {
+        method: Method,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::FixedCardinalityLabel)]
+    #[label(rename_all = "snake_case")]
+    enum Method {
+        Post,
+        Get,
+    }
+
+    fn series(labels: &[(&str, &str)], value: f64, timestamp: i64) -> TimeSeries {
+        TimeSeries {
+            labels: labels
+                .iter()
+                .map(|(name, value)| Label {
+                    name: name.to_string(),
+                    value: value.to_string(),
+                })
+                .collect(),
+            samples: vec![Sample { value, timestamp }],
+        }
+    }
+
+    #[test]
+    fn write_request() {
+        let timestamp = SystemTime::UNIX_EPOCH + Duration::from_millis(1_700_000_000_123);
+        let mut enc = RemoteWriteEncoder::new(timestamp);
+
+        let requests = CounterVec::<RequestLabelSet>::new();
+        requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            3,
+        );
+        let name = MetricName::from_str("http_request").with_suffix(Total);
+        enc.write_help(&name, "The total number of HTTP requests.")
+            .unwrap();
+        requests.collect_family_into(&name, &mut enc).unwrap();
+
+        let temperature = FloatGauge::new();
+        temperature.set(-1.5);
+        temperature
+            .collect_family_into(MetricName::from_str("temperature"), &mut enc)
+            .unwrap();
+
+        let latency = Histogram::with_metadata(Thresholds::with_buckets([0.1, 1.0]));
+        latency.observe(0.5);
+        latency.observe(2.0);
+        latency
+            .collect_family_into(MetricName::from_str("latency"), &mut enc)
+            .unwrap();
+
+        let body = enc.finish();
+        let decoded = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        let request = WriteRequest::decode(&*decoded).unwrap();
+
+        let ts = 1_700_000_000_123;
+        assert_eq!(
+            request.timeseries,
+            [
+                series(
+                    &[("__name__", "http_request_total"), ("method", "get")],
+                    3.0,
+                    ts
+                ),
+                series(&[("__name__", "temperature")], -1.5, ts),
+                series(&[("__name__", "latency_bucket"), ("le", "0.1")], 0.0, ts),
+                series(&[("__name__", "latency_bucket"), ("le", "1.0")], 1.0, ts),
+                series(&[("__name__", "latency_bucket"), ("le", "+Inf")], 2.0, ts),
+                series(&[("__name__", "latency_sum")], 2.5, ts),
+                series(&[("__name__", "latency_count")], 2.0, ts),
+            ]
+        );
+        assert_eq!(
+            request.metadata,
+            [
+                MetricMetadata {
+                    r#type: 1,
+                    metric_family_name: "http_request_total".to_owned(),
+                    help: "The total number of HTTP requests.".to_owned(),
+                },
+                MetricMetadata {
+                    r#type: 2,
+                    metric_family_name: "temperature".to_owned(),
+                    help: String::new(),
+                },
+                MetricMetadata {
+                    r#type: 3,
+                    metric_family_name: "latency".to_owned(),
+                    help: String::new(),
+                },
+            ]
+        );
+
+        // the encoder is reset after finishing
+        let body = enc.finish();
+        let decoded = snap::raw::Decoder::new().decompress_vec(&body).unwrap();
+        assert_eq!(
+            WriteRequest::decode(&*decoded).unwrap(),
+            WriteRequest::default()
+        );
+    }
+}
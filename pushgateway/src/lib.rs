@@ -0,0 +1,462 @@
+//! Push metrics to a [prometheus pushgateway](https://github.com/prometheus/pushgateway).
+//!
+//! This is intended for batch jobs which might not live long enough to be scraped.
+//!
+//! # Usage
+//!
+//! ```no_run
+//! use measured::{Counter, MetricGroup};
+//! use measured_pushgateway::Pushgateway;
+//!
+//! #[derive(MetricGroup)]
+//! #[metric(new())]
+//! struct BatchMetrics {
+//!     /// number of records processed
+//!     records_processed: Counter,
+//! }
+//!
+//! let metrics = BatchMetrics::new();
+//! metrics.records_processed.inc_by(42);
+//!
+//! let gateway = Pushgateway::new("http://localhost:9091", "nightly_import")
+//!     .grouping("instance", "db-1");
+//!
+//! // replaces all metrics in the `{job="nightly_import",instance="db-1"}` group
+//! gateway.push(&metrics).unwrap();
+//!
+//! // once the metrics are no longer relevant, remove the group
+//! gateway.delete().unwrap();
+//!
+//! // or only keep the metrics around if the job fails
+//! let res = gateway.push_and_delete_on_success(&metrics, || {
+//!     metrics.records_processed.inc_by(8);
+//!     Ok::<(), std::io::Error>(())
+//! });
+//! res.unwrap().unwrap();
+//! ```
+
+use std::{fmt, thread, time::Duration};
+
+use base64::{engine::general_purpose::URL_SAFE, Engine};
+use measured::{text::BufferedTextEncoder, MetricGroup};
+use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+/// The content type of the 0.0.4 text format, as produced by [`BufferedTextEncoder`].
+pub const CONTENT_TYPE: &str = "text/plain; version=0.0.4";
+
+/// Path segments only need to escape the characters that are not unreserved (RFC 3986)
+const PATH_SEGMENT: &AsciiSet = &NON_ALPHANUMERIC
+    .remove(b'-')
+    .remove(b'.')
+    .remove(b'_')
+    .remove(b'~');
+
+/// A client for a single grouping key within a pushgateway.
+///
+/// The grouping key is made up of the job name, and any extra labels added with [`Pushgateway::grouping`].
+/// All metrics pushed with this client will have these labels attached by the pushgateway.
+pub struct Pushgateway {
+    agent: ureq::Agent,
+    url: String,
+    retries: u32,
+    backoff: Duration,
+}
+
+impl Pushgateway {
+    /// Create a client which pushes to the pushgateway at `url`, with the given job name.
+    ///
+    /// By default, requests are retried twice, with a backoff starting at 100ms.
+    pub fn new(url: &str, job: &str) -> Self {
+        let mut url = url.trim_end_matches('/').to_owned();
+        url.push_str("/metrics");
+        push_grouping(&mut url, "job", job);
+        Self {
+            agent: ureq::Agent::new(),
+            url,
+            retries: 2,
+            backoff: Duration::from_millis(100),
+        }
+    }
+
+    /// Add a label to the grouping key.
+    ///
+    /// Label values that cannot be represented in a URL path segment are base64 encoded,
+    /// as described in the [pushgateway docs](https://github.com/prometheus/pushgateway#url).
+    pub fn grouping(mut self, label: &str, value: &str) -> Self {
+        push_grouping(&mut self.url, label, value);
+        self
+    }
+
+    /// Set how many times a failed request is retried, and how long to wait before the first retry.
+    /// The backoff doubles after each retry.
+    ///
+    /// Only requests that failed to send, or that failed with a 5xx status, are retried.
+    pub fn retries(mut self, retries: u32, backoff: Duration) -> Self {
+        self.retries = retries;
+        self.backoff = backoff;
+        self
+    }
+
+    /// Use a custom [`ureq::Agent`], eg to configure timeouts or proxies.
+    pub fn agent(mut self, agent: ureq::Agent) -> Self {
+        self.agent = agent;
+        self
+    }
+
+    /// The full URL of this grouping key.
+    pub fn url(&self) -> &str {
+        &self.url
+    }
+
+    /// Replace all metrics within the grouping key with the metrics in `group`. Sends a `PUT` request.
+    pub fn push<G: MetricGroup<BufferedTextEncoder>>(&self, group: &G) -> Result<(), PushError> {
+        self.send("PUT", &encode(group))
+    }
+
+    /// Replace only the metrics within the grouping key that share a name with the metrics in `group`.
+    /// Sends a `POST` request.
+    pub fn push_add<G: MetricGroup<BufferedTextEncoder>>(
+        &self,
+        group: &G,
+    ) -> Result<(), PushError> {
+        self.send("POST", &encode(group))
+    }
+
+    /// Delete all metrics within the grouping key. Sends a `DELETE` request.
+    ///
+    /// The pushgateway accepts deletes before they are applied, so a successful delete
+    /// only means that the metrics will be removed, not that they have been.
+    pub fn delete(&self) -> Result<(), PushError> {
+        self.send("DELETE", &[])
+    }
+
+    /// Push the metrics in `group` while `job` runs, and delete them once it succeeds.
+    ///
+    /// The metrics are pushed before the job starts. If the job succeeds, the grouping key is deleted.
+    /// If the job fails, the metrics are pushed again and left in the pushgateway, so the failure can be alerted on.
+    ///
+    /// The outer error is returned if any request to the pushgateway failed, the inner result is the result of the job.
+    pub fn push_and_delete_on_success<G, T, E>(
+        &self,
+        group: &G,
+        job: impl FnOnce() -> Result<T, E>,
+    ) -> Result<Result<T, E>, PushError>
+    where
+        G: MetricGroup<BufferedTextEncoder>,
+    {
+        self.push(group)?;
+        let res = job();
+        match &res {
+            Ok(_) => self.delete()?,
+            Err(_) => self.push(group)?,
+        }
+        Ok(res)
+    }
+
+    fn send(&self, method: &str, body: &[u8]) -> Result<(), PushError> {
+        let mut backoff = self.backoff;
+        let mut attempt = 0;
+        loop {
+            let res = self
+                .agent
+                .request(method, &self.url)
+                .set("Content-Type", CONTENT_TYPE)
+                .send_bytes(body);
+
+            let err = match res {
+                Ok(_) => return Ok(()),
+                Err(ureq::Error::Status(status, response)) => PushError::Status {
+                    status,
+                    body: response.into_string().unwrap_or_default(),
+                },
+                Err(ureq::Error::Transport(transport)) => PushError::Transport(Box::new(transport)),
+            };
+
+            if attempt >= self.retries || !err.is_retryable() {
+                return Err(err);
+            }
+            attempt += 1;
+            thread::sleep(backoff);
+            backoff *= 2;
+        }
+    }
+}
+
+fn encode<G: MetricGroup<BufferedTextEncoder>>(group: &G) -> Vec<u8> {
+    let mut enc = BufferedTextEncoder::new();
+    match group.collect_group_into(&mut enc) {
+        Ok(()) => {}
+        Err(infallible) => match infallible {},
+    }
+    enc.finish().to_vec()
+}
+
+/// Append `/<label>/<value>` to the url.
+///
+/// Values that are empty, or contain a `/`, are written as `/<label>@base64/<value>`
+fn push_grouping(url: &mut String, label: &str, value: &str) {
+    url.push('/');
+    url.extend(utf8_percent_encode(label, PATH_SEGMENT));
+    if value.is_empty() {
+        url.push_str("@base64/=");
+    } else if value.contains('/') {
+        url.push_str("@base64/");
+        URL_SAFE.encode_string(value, url);
+    } else {
+        url.push('/');
+        url.extend(utf8_percent_encode(value, PATH_SEGMENT));
+    }
+}
+
+/// An error returned when pushing to a pushgateway
+#[derive(Debug)]
+pub enum PushError {
+    /// The pushgateway responded with an error status
+    Status {
+        /// The HTTP status code
+        status: u16,
+        /// The response body, which usually describes the error
+        body: String,
+    },
+    /// The request could not be sent, or the response could not be read
+    Transport(Box<ureq::Transport>),
+}
+
+impl PushError {
+    fn is_retryable(&self) -> bool {
+        match self {
+            PushError::Status { status, .. } => *status >= 500,
+            PushError::Transport(_) => true,
+        }
+    }
+}
+
+impl fmt::Display for PushError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PushError::Status { status, body } => {
+                write!(f, "pushgateway responded with status {status}: {body}")
+            }
+            PushError::Transport(t) => write!(f, "could not reach pushgateway: {t}"),
+        }
+    }
+}
+
+impl std::error::Error for PushError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            PushError::Status { .. } => None,
+            PushError::Transport(t) => Some(t),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        io::{BufRead, BufReader, Read, Write},
+        net::TcpListener,
+        thread::{self, JoinHandle},
+        time::Duration,
+    };
+
+    use measured::{Counter, MetricGroup};
+
+    use super::{PushError, Pushgateway};
+
+    #[derive(MetricGroup)]
+    #[metric(new())]
+    struct Metrics {
+        /// total number of records
+        records: Counter,
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Request {
+        method: String,
+        path: String,
+        content_type: Option<String>,
+        body: String,
+    }
+
+    /// A pushgateway stand-in. It responds to each request with the next status in `statuses`,
+    /// and returns all the requests it received.
+    fn stand_in(statuses: &'static [u16]) -> (String, JoinHandle<Vec<Request>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = vec![];
+            for status in statuses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+
+                let mut line = String::new();
+                reader.read_line(&mut line).unwrap();
+                let mut parts = line.split_whitespace();
+                let method = parts.next().unwrap().to_owned();
+                let path = parts.next().unwrap().to_owned();
+
+                let mut content_type = None;
+                let mut content_length = 0;
+                loop {
+                    let mut header = String::new();
+                    reader.read_line(&mut header).unwrap();
+                    let header = header.trim_end();
+                    if header.is_empty() {
+                        break;
+                    }
+                    let (name, value) = header.split_once(": ").unwrap();
+                    match &*name.to_ascii_lowercase() {
+                        "content-type" => content_type = Some(value.to_owned()),
+                        "content-length" => content_length = value.parse().unwrap(),
+                        _ => {}
+                    }
+                }
+
+                let mut body = vec![0; content_length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push(Request {
+                    method,
+                    path,
+                    content_type,
+                    body: String::from_utf8(body).unwrap(),
+                });
+
+                let mut stream = reader.into_inner();
+                write!(
+                    stream,
+                    "HTTP/1.1 {status} Status\r\ncontent-length: 4\r\nconnection: close\r\n\r\noops"
+                )
+                .unwrap();
+            }
+            requests
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn grouping_key() {
+        let gateway = Pushgateway::new("http://localhost:9091/", "some/job")
+            .grouping("instance", "a b")
+            .grouping("path", "/var/tmp")
+            .grouping("empty", "");
+
+        assert_eq!(
+            gateway.url(),
+            "http://localhost:9091/metrics/job@base64/c29tZS9qb2I=/instance/a%20b/path@base64/L3Zhci90bXA=/empty@base64/="
+        );
+    }
+
+    #[test]
+    fn push_then_delete() {
+        let (url, server) = stand_in(&[200, 202, 202]);
+        let metrics = Metrics::new();
+        metrics.records.inc_by(3);
+
+        let gateway = Pushgateway::new(&url, "batch").grouping("instance", "1");
+        gateway.push(&metrics).unwrap();
+        gateway.push_add(&metrics).unwrap();
+        gateway.delete().unwrap();
+
+        let body = "# HELP records total number of records\n# TYPE records counter\nrecords 3\n";
+        let requests = server.join().unwrap();
+        assert_eq!(
+            requests,
+            [
+                Request {
+                    method: "PUT".to_owned(),
+                    path: "/metrics/job/batch/instance/1".to_owned(),
+                    content_type: Some("text/plain; version=0.0.4".to_owned()),
+                    body: body.to_owned(),
+                },
+                Request {
+                    method: "POST".to_owned(),
+                    path: "/metrics/job/batch/instance/1".to_owned(),
+                    content_type: Some("text/plain; version=0.0.4".to_owned()),
+                    body: body.to_owned(),
+                },
+                Request {
+                    method: "DELETE".to_owned(),
+                    path: "/metrics/job/batch/instance/1".to_owned(),
+                    content_type: Some("text/plain; version=0.0.4".to_owned()),
+                    body: String::new(),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn retries_server_errors() {
+        let (url, server) = stand_in(&[503, 500, 200]);
+        let gateway = Pushgateway::new(&url, "batch").retries(2, Duration::from_millis(1));
+        gateway.push(&Metrics::new()).unwrap();
+        assert_eq!(server.join().unwrap().len(), 3);
+
+        let (url, server) = stand_in(&[503, 503]);
+        let gateway = Pushgateway::new(&url, "batch").retries(1, Duration::from_millis(1));
+        let err = gateway.push(&Metrics::new()).unwrap_err();
+        assert!(matches!(err, PushError::Status { status: 503, .. }));
+        assert_eq!(server.join().unwrap().len(), 2);
+    }
+
+    #[test]
+    fn client_errors_are_not_retried() {
+        let (url, server) = stand_in(&[400]);
+        let gateway = Pushgateway::new(&url, "batch").retries(3, Duration::from_millis(1));
+        let err = gateway.delete().unwrap_err();
+        match err {
+            PushError::Status { status, body } => {
+                assert_eq!(status, 400);
+                assert_eq!(body, "oops");
+            }
+            PushError::Transport(t) => panic!("unexpected transport error {t}"),
+        }
+        assert_eq!(server.join().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn delete_on_success() {
+        let (url, server) = stand_in(&[200, 202]);
+        let metrics = Metrics::new();
+        let gateway = Pushgateway::new(&url, "batch");
+        let res = gateway.push_and_delete_on_success(&metrics, || {
+            metrics.records.inc();
+            Ok::<_, ()>(1)
+        });
+        assert_eq!(res.unwrap(), Ok(1));
+
+        let methods: Vec<_> = server
+            .join()
+            .unwrap()
+            .into_iter()
+            .map(|r| r.method)
+            .collect();
+        assert_eq!(methods, ["PUT", "DELETE"]);
+    }
+
+    #[test]
+    fn keep_on_failure() {
+        let (url, server) = stand_in(&[200, 200]);
+        let metrics = Metrics::new();
+        let gateway = Pushgateway::new(&url, "batch");
+        let res = gateway.push_and_delete_on_success(&metrics, || {
+            metrics.records.inc_by(2);
+            Err::<(), _>("failed")
+        });
+        assert_eq!(res.unwrap(), Err("failed"));
+
+        let requests = server.join().unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            requests[0].body,
+            "# HELP records total number of records\n# TYPE records counter\nrecords 0\n"
+        );
+        assert_eq!(requests[1].method, "PUT");
+        assert_eq!(
+            requests[1].body,
+            "# HELP records total number of records\n# TYPE records counter\nrecords 2\n"
+        );
+    }
+}
@@ -0,0 +1,595 @@
+//! Push metrics to a [StatsD](https://github.com/statsd/statsd) or
+//! [DogStatsD](https://docs.datadoghq.com/developers/dogstatsd/) agent.
+//!
+//! * Counters are encoded as `name:delta|c`, where `delta` is the change since the previous flush.
+//! * Gauges are encoded as `name:value|g`. StatsD reads a signed value as a change to the gauge,
+//!   so a negative value is preceded by `name:0|g` to set it absolutely.
+//! * Histograms are encoded as DogStatsD distributions, `name:value|d|@rate`.
+//!   Each bucket that received observations since the previous flush is sent as a single value,
+//!   with a sample rate of `1/count`, so the agent counts it `count` times.
+//!
+//! Labels are encoded as DogStatsD tags, `|#label:value,label:value`.
+//!
+//! # Usage
+//!
+//! ```no_run
+//! use std::net::UdpSocket;
+//!
+//! use measured::{Counter, MetricGroup};
+//! use measured_statsd::StatsdEncoder;
+//!
+//! #[derive(MetricGroup)]
+//! #[metric(new())]
+//! struct MyAppMetrics {
+//!     requests: Counter,
+//! }
+//!
+//! let metrics = MyAppMetrics::new();
+//!
+//! let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//! socket.connect("127.0.0.1:8125").unwrap();
+//!
+//! let mut enc = StatsdEncoder::new();
+//! loop {
+//!     metrics.collect_group_into(&mut enc).unwrap();
+//!     enc.send(&socket).unwrap();
+//!
+//!     std::thread::sleep(std::time::Duration::from_secs(10));
+//! }
+//! ```
+
+use std::{collections::HashMap, io::Write, net::UdpSocket};
+
+use measured::{
+    label::{LabelGroupVisitor, LabelName, LabelValue, LabelVisitor},
+    metric::{
+        counter::CounterState,
+        gauge::{FloatGaugeState, GaugeState},
+        group::Encoding,
+        histogram::{HistogramState, Thresholds},
+        name::MetricNameEncoder,
+        MetricEncoding,
+    },
+    LabelGroup,
+};
+
+/// The default maximum size of a UDP packet. This avoids fragmentation on most networks.
+pub const DEFAULT_MAX_PACKET_SIZE: usize = 1432;
+
+/// The StatsD encoder.
+///
+/// The encoder remembers the counter and histogram values between flushes in order to compute deltas,
+/// so the same encoder should be re-used for every collection.
+/// Series that were not collected since the previous flush are forgotten.
+pub struct StatsdEncoder {
+    /// Newline separated StatsD lines
+    buf: Vec<u8>,
+    max_packet_size: usize,
+
+    /// The previously flushed counter values, keyed by name and tags
+    counters: HashMap<Box<[u8]>, Previous<u64>>,
+    /// The previously flushed histogram bucket counts, keyed by name and tags
+    histograms: HashMap<Box<[u8]>, Previous<Box<[u64]>>>,
+
+    /// Scratch space for the name and tags of the series currently being encoded
+    series: Vec<u8>,
+}
+
+impl Default for StatsdEncoder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl StatsdEncoder {
+    /// Create a new StatsD encoder.
+    pub fn new() -> Self {
+        Self {
+            buf: Vec::new(),
+            max_packet_size: DEFAULT_MAX_PACKET_SIZE,
+            counters: HashMap::new(),
+            histograms: HashMap::new(),
+            series: Vec::new(),
+        }
+    }
+
+    /// Set the maximum size of each packet sent with [`StatsdEncoder::send`].
+    pub fn with_max_packet_size(mut self, max_packet_size: usize) -> Self {
+        self.max_packet_size = max_packet_size;
+        self
+    }
+
+    /// Finish the encoding and extract the newline separated StatsD lines.
+    pub fn finish(&mut self) -> Vec<u8> {
+        // forget the series that have been removed since the previous flush
+        self.counters.retain(|_, p| std::mem::take(&mut p.seen));
+        self.histograms.retain(|_, p| std::mem::take(&mut p.seen));
+        std::mem::take(&mut self.buf)
+    }
+
+    /// Finish the encoding and send the lines to the connected socket.
+    ///
+    /// Lines are batched into as few packets as possible, without exceeding the max packet size.
+    /// A single line that exceeds the max packet size is sent in its own packet.
+    pub fn send(&mut self, socket: &UdpSocket) -> std::io::Result<()> {
+        let buf = self.finish();
+
+        let mut packet_start = 0;
+        let mut packet_end = 0;
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            let line_end = packet_end + line.len();
+            // the trailing newline does not need to be sent
+            if line_end - 1 - packet_start > self.max_packet_size && packet_end > packet_start {
+                socket.send(&buf[packet_start..packet_end - 1])?;
+                packet_start = packet_end;
+            }
+            packet_end = line_end;
+        }
+        if packet_end > packet_start {
+            socket.send(&buf[packet_start..packet_end - 1])?;
+        }
+
+        self.buf = buf;
+        self.buf.clear();
+        Ok(())
+    }
+
+    /// Write the name and tags of a series into the scratch space.
+    /// The series is formatted as `name|#label:value,label:value`
+    fn start_series(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+    ) -> Result<(), std::io::Error> {
+        self.series.clear();
+        name.encode_utf8(&mut self.series)?;
+        labels.visit_values(&mut Tags {
+            buf: &mut self.series,
+            first: true,
+        });
+        Ok(())
+    }
+
+    /// Write a line for the series in the scratch space.
+    fn write_line(&mut self, value: impl FnOnce(&mut Vec<u8>), kind: &str, sample_rate: f64) {
+        let (name, tags) = match self.series.iter().position(|&b| b == b'|') {
+            Some(i) => self.series.split_at(i),
+            None => (&*self.series, &[][..]),
+        };
+        self.buf.extend_from_slice(name);
+        self.buf.push(b':');
+        value(&mut self.buf);
+        self.buf.push(b'|');
+        self.buf.extend_from_slice(kind.as_bytes());
+        if sample_rate < 1.0 {
+            self.buf.extend_from_slice(b"|@");
+            write_float(&mut self.buf, sample_rate);
+        }
+        self.buf.extend_from_slice(tags);
+        self.buf.push(b'\n');
+    }
+
+    fn write_counter(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        count: u64,
+    ) -> Result<(), std::io::Error> {
+        self.start_series(name, labels)?;
+
+        let previous = match self.counters.get_mut(&*self.series) {
+            Some(previous) => previous.replace(count),
+            None => {
+                let previous = Previous {
+                    value: count,
+                    seen: true,
+                };
+                self.counters
+                    .insert(self.series.as_slice().into(), previous);
+                0
+            }
+        };
+
+        // if the counter was reset, report all of the new count
+        let delta = count.checked_sub(previous).unwrap_or(count);
+        if delta > 0 {
+            self.write_line(|buf| write_int(buf, delta), "c", 1.0);
+        }
+        Ok(())
+    }
+
+    fn write_gauge(
+        &mut self,
+        name: impl MetricNameEncoder,
+        labels: impl LabelGroup,
+        value: Number,
+    ) -> Result<(), std::io::Error> {
+        self.start_series(name, labels)?;
+        let negative = match value {
+            Number::Int(x) => x < 0,
+            Number::Float(x) => x.is_sign_negative() && x != 0.0,
+        };
+        if negative {
+            // a signed value would be read as a change to the gauge, so reset it to zero first
+            self.write_line(|buf| buf.push(b'0'), "g", 1.0);
+        }
+        match value {
+            Number::Int(x) => self.write_line(|buf| write_int(buf, x), "g", 1.0),
+            Number::Float(x) => self.write_line(|buf| write_float(buf, x), "g", 1.0),
+        }
+        Ok(())
+    }
+}
+
+/// The previously flushed value of a series
+struct Previous<T> {
+    value: T,
+    /// Whether the series was collected since the previous flush
+    seen: bool,
+}
+
+impl<T> Previous<T> {
+    fn replace(&mut self, value: T) -> T {
+        self.seen = true;
+        std::mem::replace(&mut self.value, value)
+    }
+}
+
+enum Number {
+    Int(i64),
+    Float(f64),
+}
+
+fn write_int(buf: &mut Vec<u8>, x: impl itoa::Integer) {
+    buf.extend_from_slice(itoa::Buffer::new().format(x).as_bytes());
+}
+
+fn write_float(buf: &mut Vec<u8>, x: f64) {
+    if x.is_finite() {
+        buf.extend_from_slice(ryu::Buffer::new().format_finite(x).as_bytes());
+    } else if x.is_nan() {
+        buf.extend_from_slice(b"NaN");
+    } else if x.is_sign_positive() {
+        buf.extend_from_slice(b"+Inf");
+    } else {
+        buf.extend_from_slice(b"-Inf");
+    }
+}
+
+impl Encoding for StatsdEncoder {
+    type Err = std::io::Error;
+
+    /// StatsD has no help text, so this does nothing
+    fn write_help(
+        &mut self,
+        _name: impl MetricNameEncoder,
+        _help: &str,
+    ) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+}
+
+/// Writes a label group as DogStatsD tags
+struct Tags<'a> {
+    buf: &'a mut Vec<u8>,
+    first: bool,
+}
+
+impl LabelGroupVisitor for Tags<'_> {
+    type Output = ();
+    fn write_value(&mut self, name: &LabelName, x: &impl LabelValue) {
+        if self.first {
+            self.buf.extend_from_slice(b"|#");
+            self.first = false;
+        } else {
+            self.buf.push(b',');
+        }
+        self.buf.extend_from_slice(name.as_str().as_bytes());
+        self.buf.push(b':');
+        x.visit(TagValue { buf: self.buf });
+    }
+}
+
+struct TagValue<'a> {
+    buf: &'a mut Vec<u8>,
+}
+
+impl LabelVisitor for TagValue<'_> {
+    type Output = ();
+    fn write_int(self, x: i64) {
+        write_int(self.buf, x);
+    }
+
+    fn write_float(self, x: f64) {
+        write_float(self.buf, x);
+    }
+
+    fn write_str(self, x: &str) {
+        // these characters separate the tags and fields of a line
+        for c in x.chars() {
+            match c {
+                ',' | '|' | '\n' => self.buf.push(b'_'),
+                c => {
+                    let _ = write!(self.buf, "{c}");
+                }
+            }
+        }
+    }
+}
+
+impl MetricEncoding<StatsdEncoder> for CounterState {
+    fn write_type(
+        _name: impl MetricNameEncoder,
+        _enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        let count = self.count.load(std::sync::atomic::Ordering::Relaxed);
+        enc.write_counter(name, labels, count)
+    }
+}
+
+impl MetricEncoding<StatsdEncoder> for GaugeState {
+    fn write_type(
+        _name: impl MetricNameEncoder,
+        _enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        let gauge = self.count.load(std::sync::atomic::Ordering::Relaxed);
+        enc.write_gauge(name, labels, Number::Int(gauge))
+    }
+}
+
+impl MetricEncoding<StatsdEncoder> for FloatGaugeState {
+    fn write_type(
+        _name: impl MetricNameEncoder,
+        _enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        _m: &(),
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        enc.write_gauge(name, labels, Number::Float(self.count.get()))
+    }
+}
+
+impl<const N: usize> MetricEncoding<StatsdEncoder> for HistogramState<N> {
+    fn write_type(
+        _name: impl MetricNameEncoder,
+        _enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        Ok(())
+    }
+
+    fn collect_into(
+        &self,
+        metadata: &Thresholds<N>,
+        labels: impl LabelGroup,
+        name: impl MetricNameEncoder,
+        enc: &mut StatsdEncoder,
+    ) -> Result<(), std::io::Error> {
+        let (buckets, inf, _sum) = self.sample();
+        enc.start_series(name, labels)?;
+
+        let previous = enc
+            .histograms
+            .entry(enc.series.as_slice().into())
+            .or_insert_with(|| Previous {
+                value: vec![0; N + 1].into_boxed_slice(),
+                seen: false,
+            });
+        previous.seen = true;
+        let previous = &mut previous.value;
+        let mut deltas = [0; N];
+        for ((d, b), p) in deltas.iter_mut().zip(buckets).zip(&mut previous[..N]) {
+            *d = b.checked_sub(*p).unwrap_or(b);
+            *p = b;
+        }
+        let inf_delta = inf.checked_sub(previous[N]).unwrap_or(inf);
+        previous[N] = inf;
+
+        // the +Inf bucket has no upper bound, so those observations are reported as the largest threshold
+        let thresholds = metadata.get();
+        let inf_le = thresholds.last().copied().unwrap_or(0.0);
+        let values = thresholds.iter().copied().zip(deltas);
+        for (le, count) in values.chain([(inf_le, inf_delta)]) {
+            if count == 0 {
+                continue;
+            }
+            enc.write_line(|buf| write_float(buf, le), "d", 1.0 / count as f64);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::UdpSocket;
+
+    use measured::{
+        metric::{histogram::Thresholds, name::MetricName, MetricFamilyEncoding},
+        Counter, CounterVec, FloatGauge, Gauge, Histogram, MetricGroup,
+    };
+
+    use super::StatsdEncoder;
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::LabelGroup)]
+    #[label(set = RequestLabelSet)]
+    struct RequestLabels {
+        method: Method,
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug, measured::FixedCardinalityLabel)]
+    #[label(rename_all = "snake_case")]
+    enum Method {
+        Post,
+        Get,
+    }
+
+    #[derive(MetricGroup)]
+    #[metric(new())]
+    struct Metrics {
+        #[metric(init = CounterVec::new())]
+        requests: CounterVec<RequestLabelSet>,
+        temperature: FloatGauge,
+        #[metric(metadata = Thresholds::with_buckets([0.1, 1.0]))]
+        latency: Histogram<2>,
+    }
+
+    fn lines(socket: &UdpSocket) -> Vec<String> {
+        let mut lines = vec![];
+        let mut buf = [0; 1500];
+        while let Ok(n) = socket.recv(&mut buf) {
+            let packet = std::str::from_utf8(&buf[..n]).unwrap();
+            lines.extend(packet.lines().map(str::to_owned));
+        }
+        lines
+    }
+
+    #[test]
+    fn deltas_over_udp() {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        agent.set_nonblocking(true).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(agent.local_addr().unwrap()).unwrap();
+
+        let metrics = Metrics::new();
+        let mut enc = StatsdEncoder::new();
+
+        metrics.requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            3,
+        );
+        metrics.temperature.set(21.5);
+        metrics.latency.observe(0.5);
+        metrics.latency.observe(0.7);
+        metrics.latency.observe(5.0);
+        metrics.collect_group_into(&mut enc).unwrap();
+        enc.send(&socket).unwrap();
+
+        assert_eq!(
+            lines(&agent),
+            [
+                "requests:3|c|#method:get",
+                "temperature:21.5|g",
+                "latency:1.0|d|@0.5",
+                "latency:1.0|d",
+            ]
+        );
+
+        metrics.requests.inc_by(
+            RequestLabels {
+                method: Method::Get,
+            },
+            2,
+        );
+        metrics.requests.inc(RequestLabels {
+            method: Method::Post,
+        });
+        metrics.latency.observe(0.05);
+        metrics.collect_group_into(&mut enc).unwrap();
+        enc.send(&socket).unwrap();
+
+        assert_eq!(
+            lines(&agent),
+            [
+                "requests:1|c|#method:post",
+                "requests:2|c|#method:get",
+                "temperature:21.5|g",
+                "latency:0.1|d",
+            ]
+        );
+    }
+
+    #[test]
+    fn packets_are_split_on_lines() {
+        let agent = UdpSocket::bind("127.0.0.1:0").unwrap();
+        agent.set_nonblocking(true).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.connect(agent.local_addr().unwrap()).unwrap();
+
+        let metrics = Metrics::new();
+        metrics.requests.inc(RequestLabels {
+            method: Method::Get,
+        });
+        metrics.requests.inc(RequestLabels {
+            method: Method::Post,
+        });
+
+        let mut enc = StatsdEncoder::new().with_max_packet_size(50);
+        metrics.collect_group_into(&mut enc).unwrap();
+        enc.send(&socket).unwrap();
+
+        let mut packets = vec![];
+        let mut buf = [0; 1500];
+        while let Ok(n) = agent.recv(&mut buf) {
+            packets.push(String::from_utf8(buf[..n].to_vec()).unwrap());
+        }
+        assert_eq!(
+            packets,
+            [
+                "requests:1|c|#method:post\nrequests:1|c|#method:get",
+                "temperature:0.0|g",
+            ]
+        );
+    }
+
+    #[test]
+    fn negative_gauge() {
+        let gauge = Gauge::new();
+        gauge.set(-5);
+
+        let mut enc = StatsdEncoder::new();
+        gauge
+            .collect_family_into(MetricName::from_str("queue_delta"), &mut enc)
+            .unwrap();
+        assert_eq!(enc.finish(), b"queue_delta:0|g\nqueue_delta:-5|g\n");
+    }
+
+    #[test]
+    fn removed_series_are_forgotten() {
+        let requests = Counter::new();
+        requests.inc_by(3);
+        let name = MetricName::from_str("requests");
+
+        let mut enc = StatsdEncoder::new();
+        requests.collect_family_into(name, &mut enc).unwrap();
+        assert_eq!(enc.finish(), b"requests:3|c\n");
+        assert_eq!(enc.counters.len(), 1);
+
+        // still remembered after a flush where the series was collected
+        requests.collect_family_into(name, &mut enc).unwrap();
+        assert_eq!(enc.finish(), b"");
+        assert_eq!(enc.counters.len(), 1);
+
+        // forgotten after a flush where the series was not collected
+        assert_eq!(enc.finish(), b"");
+        assert!(enc.counters.is_empty());
+    }
+}
@@ -14,7 +14,7 @@
 //!     tokio: measured_tokio::RuntimeCollector,
 //!
 //!     // other metrics
-//! }}
+//! }
 //!
 //! #[tokio::main]
 //! async fn main() {